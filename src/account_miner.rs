@@ -6,18 +6,62 @@
 //!
 //! ## Key Functions
 //! - `mine_create2_accounts`: Main entry point for mining CREATE2 contracts with auxiliary accounts
+//! - `write_create2_result`: Saves a `Create2MiningResult` JSON file
 //! - `calculate_create2_address`: Computes deterministic CREATE2 addresses
-//! - `mine_auxiliaries_for_contract`: Mines accounts whose hashes share prefixes with a contract
+//! - `mine_target_accounts`: Mines auxiliary accounts around existing accounts' trie keys
+//! - `write_target_result`: Saves a `TargetMiningResult` JSON file
+//! - `mine_auxiliaries`: Mines accounts whose hashes share prefixes with a contract or target
+//! - `calibrate`: Measures the hashrates of a config's searches without keeping anything
+//!
+//! With `aux_keys`, auxiliary accounts are mined as secret keys instead of bare addresses, so
+//! they are spendable EOAs; the keys are written (plain or encrypted) next to the addresses.
+//!
+//! Salts are full 32-byte words: an optional fixed prefix followed by a counter. By default
+//! contract `i` takes counter `i`; with salt mining, each contract takes the next counter whose
+//! address hash shares a number of leading nibbles with a cluster target, so the contracts sit
+//! together in the account trie instead of scattering randomly.
+//!
+//! Auxiliaries can also deepen the paths to existing accounts (e.g. WETH or a big token) given
+//! by address or account-trie key. On a dense state the accounts already around a key fill its
+//! first levels, so only the levels beyond `skip_nibbles` are mined.
+//!
+//! A run that exhausts its `Budget` or is interrupted ends early with the contracts (or
+//! targets) mined so far; the last one may hold fewer auxiliaries, and the result's `status`
+//! says why it ended.
 
 use log::{debug, info};
-use rand::Rng;
-use serde::{Deserialize, Serialize};
+use secp256k1::{All, Secp256k1};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fs;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tiny_keccak::{Hasher, Keccak};
 
+use crate::checkpoint::{Candidate, CheckpointFile, SearchRecord};
+use crate::error::Error;
+use crate::evm;
+use crate::keys::{KeyEncryption, KeyWalk, Keystore, StoredKey};
+use crate::mpt::{self, Account, Trie, TrieReport};
+use crate::search::{
+    Budget, Frontier, LevelSearch, MAX_DEPTH, NonceKeys, SearchStatus, check_search_params,
+    count_shared_nibbles, measure_hashrate, random_seed, run_workers,
+};
+use crate::storage_layout::{parse_address, parse_word, slot_from_u64};
+use crate::storage_miner::StorageSlot;
+
+/// Balance given to auxiliary (and existing) accounts in the account trie report; any
+/// nonzero balance keeps them from being pruned as empty accounts (EIP-161)
+pub const AUXILIARY_BALANCE: u128 = 1;
+
+/// A storage slot with its value
+pub type StorageEntry = ([u8; 32], [u8; 32]);
+
+/// A contract address with its auxiliary accounts
+pub type ContractAccounts = ([u8; 20], Vec<[u8; 20]>);
+
+/// An auxiliary address with its secret key
+pub type AccountKey = ([u8; 20], [u8; 32]);
+
 /// Result structure for CREATE2-based mining
 #[derive(Serialize, Deserialize)]
 pub struct Create2MiningResult {
@@ -25,32 +69,478 @@ pub struct Create2MiningResult {
     pub init_code_hash: String,
     pub init_code: String,
     pub deploy_code: String,
+    /// Mapping keys of the mined storage branch: addresses for address-keyed mappings, full
+    /// 32-byte words for `uint256`/`bytes32` keys
     pub storage_keys: Vec<String>,
+    /// Storage slots the contract writes for `storage_keys`, in the same order
+    #[serde(default)]
+    pub storage_slots: Vec<String>,
     pub target_depth: usize,
     pub num_contracts: usize,
+    /// Seed of the run, which reproduces the addresses (absent in older files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
     pub total_time: f64,
+    /// How the run ended; unless complete, `contracts` holds the ones mined before it stopped
+    #[serde(default)]
+    pub status: SearchStatus,
     pub contracts: Vec<ContractWithAuxiliaries>,
+    /// Account trie of the contracts and auxiliaries (plus any existing accounts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_trie: Option<TrieReport>,
+    /// Parameters to decrypt `auxiliary_keys`; absent when the keys are stored in plain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_encryption: Option<KeyEncryption>,
+    /// Prefix the contracts' account-trie keys share, when their salts were mined for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<SaltCluster>,
+}
+
+/// Account-trie key prefix the contracts of a salt-mined result share
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SaltCluster {
+    /// Account-trie key the contracts cluster around
+    pub target: String,
+    /// Leading nibbles every contract key shares with `target`
+    pub nibbles: usize,
+}
+
+impl Create2MiningResult {
+    /// Init code the contracts are deployed with
+    pub fn init_code(&self) -> Result<Vec<u8>, String> {
+        let code = self.init_code.strip_prefix("0x").unwrap_or(&self.init_code);
+        hex::decode(code).map_err(|e| format!("Invalid init_code hex: {e}"))
+    }
+
+    /// Runtime code of the contracts
+    pub fn deploy_code(&self) -> Result<Vec<u8>, String> {
+        let code = self
+            .deploy_code
+            .strip_prefix("0x")
+            .unwrap_or(&self.deploy_code);
+        hex::decode(code).map_err(|e| format!("Invalid deploy_code hex: {e}"))
+    }
+
+    /// Storage slots the contract writes. Assets predating `storage_slots` `sstore` to the raw
+    /// key in `storage_keys`, so those are used (left-padded to a word) instead. Assets that
+    /// record neither are decoded from the older Solidity templates' init code; empty if the
+    /// init code is not one of them
+    pub fn contract_storage_slots(&self) -> Result<Vec<[u8; 32]>, String> {
+        if !self.storage_slots.is_empty() {
+            return self
+                .storage_slots
+                .iter()
+                .map(|slot| parse_word(slot))
+                .collect();
+        }
+        if self.storage_keys.is_empty() {
+            return Ok(evm::legacy_slot_writes(&self.init_code()?));
+        }
+        self.storage_keys
+            .iter()
+            .map(|key| parse_word(key))
+            .collect()
+    }
+
+    /// Storage each contract holds after its constructor ran, as `contract_storage` derives it
+    /// from the recorded init code, deployer and slots
+    pub fn contract_storage(&self) -> Result<Vec<StorageEntry>, String> {
+        Ok(contract_storage(
+            &self.init_code()?,
+            &parse_address(&self.deployer)?,
+            &self.contract_storage_slots()?,
+        ))
+    }
+
+    /// Each contract address with its auxiliary accounts
+    pub fn mined_accounts(&self) -> Result<Vec<ContractAccounts>, String> {
+        self.contracts
+            .iter()
+            .map(|contract| {
+                let auxiliaries = contract
+                    .auxiliary_accounts
+                    .iter()
+                    .map(|a| parse_address(a))
+                    .collect::<Result<_, _>>()?;
+                Ok((parse_address(&contract.contract_address)?, auxiliaries))
+            })
+            .collect()
+    }
+
+    /// Auxiliary addresses with their secret keys, decrypted with `password` if the keys are
+    /// encrypted
+    pub fn auxiliary_keys(&self, password: Option<&str>) -> Result<Vec<AccountKey>, String> {
+        let keystore = match (&self.key_encryption, password) {
+            (Some(params), Some(password)) => Some(Keystore::from_params(params, password)?),
+            (Some(_), None) => return Err("Keys are encrypted; a password is required".to_string()),
+            (None, _) => None,
+        };
+
+        let mut keys = Vec::new();
+        for contract in &self.contracts {
+            for (address, key) in contract
+                .auxiliary_accounts
+                .iter()
+                .zip(&contract.auxiliary_keys)
+            {
+                let address = parse_address(address)?;
+                keys.push((
+                    address,
+                    decode_secret_key(&address, key, keystore.as_ref())?,
+                ));
+            }
+        }
+        Ok(keys)
+    }
 }
 
 /// Contract with its auxiliary accounts
 #[derive(Serialize, Deserialize)]
 pub struct ContractWithAuxiliaries {
-    pub salt: u32,
+    /// 32-byte salt as hex (older results hold the salt index as a number)
+    #[serde(
+        serialize_with = "serialize_salt",
+        deserialize_with = "deserialize_salt"
+    )]
+    pub salt: [u8; 32],
     pub contract_address: String,
     pub auxiliary_accounts: Vec<String>,
+    /// Secret keys of `auxiliary_accounts`, in the same order (v3 keystores when
+    /// `key_encryption` is set)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auxiliary_keys: Vec<StoredKey>,
+}
+
+fn serialize_salt<S: Serializer>(salt: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("0x{}", hex::encode(salt)))
+}
+
+fn deserialize_salt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Salt {
+        Index(u64),
+        Word(String),
+    }
+    match Salt::deserialize(deserializer)? {
+        Salt::Index(index) => Ok(slot_from_u64(index)),
+        Salt::Word(word) => parse_word(&word).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Most bytes a salt prefix can take, leaving 8 for the counter
+pub const MAX_SALT_PREFIX: usize = 24;
+
+/// Leading nibbles an existing account's neighbours already share with it on mainnet, where
+/// ~300M accounts put the nearest ones 7-8 nibbles deep
+pub const DEFAULT_SKIP_NIBBLES: usize = 7;
+
+/// How the salts of a run's contracts are chosen
+#[derive(Clone, Debug, Default)]
+pub struct SaltScheme {
+    /// Leading bytes of every salt (at most 24), e.g. the sender for factories that only
+    /// accept sender-bound salts
+    pub prefix: Vec<u8>,
+    /// Leading nibbles each contract key must share with the cluster target; 0 takes salt
+    /// counters `0..num_contracts` without mining
+    pub cluster_nibbles: usize,
+    /// Account-trie key the contracts cluster around; the first contract's key when unset
+    pub cluster_target: Option<[u8; 32]>,
+}
+
+impl SaltScheme {
+    /// Salt `counter`: the prefix, zeros, then the counter in the last 8 bytes
+    pub fn salt(&self, counter: u64) -> [u8; 32] {
+        let mut salt = slot_from_u64(counter);
+        salt[..self.prefix.len()].copy_from_slice(&self.prefix);
+        salt
+    }
+
+    /// The counter of a salt made by `salt`
+    fn counter(salt: &[u8; 32]) -> u64 {
+        u64::from_be_bytes(salt[24..].try_into().unwrap())
+    }
+
+    /// Summary recorded in checkpoints, so a resumed run picks salts the same way
+    fn describe(&self) -> String {
+        let target = self
+            .cluster_target
+            .map_or("the first contract".to_string(), |target| {
+                format!("0x{}", hex::encode(target))
+            });
+        format!(
+            "prefix 0x{}, {} nibbles shared with {target}",
+            hex::encode(&self.prefix),
+            self.cluster_nibbles
+        )
+    }
+}
+
+/// Parameters for a CREATE2 mining run
+#[derive(Clone)]
+pub struct Create2Config {
+    /// Address of the CREATE2 deployer contract
+    pub deployer: [u8; 20],
+    /// Number of contracts to deploy
+    pub num_contracts: usize,
+    /// How the contracts' salts are chosen
+    pub salts: SaltScheme,
+    /// Number of auxiliary accounts mined per contract
+    pub target_depth: usize,
+    /// Number of mining threads
+    pub num_threads: usize,
+    /// Seed of the candidate addresses, which determines the result
+    pub seed: u64,
+    /// Mine secret keys for the auxiliary accounts instead of bare addresses
+    pub aux_keys: bool,
+    /// Password to encrypt the mined keys with; they are written in plain without one
+    pub key_password: Option<String>,
+    /// Limits on the attempts and time of the run
+    pub budget: Arc<Budget>,
+    /// File recording the progress of every contract, if any
+    pub checkpoint: Option<Arc<CheckpointFile>>,
+}
+
+impl Create2Config {
+    /// Builder of a config deploying `num_contracts` contracts through `deployer`, each with
+    /// `target_depth` auxiliaries, with counter salts, bare auxiliary addresses and a random seed
+    pub fn builder(
+        deployer: [u8; 20],
+        num_contracts: usize,
+        target_depth: usize,
+    ) -> Create2ConfigBuilder {
+        Create2ConfigBuilder {
+            config: Create2Config {
+                deployer,
+                num_contracts,
+                salts: SaltScheme::default(),
+                target_depth,
+                num_threads: num_cpus::get(),
+                seed: random_seed(),
+                aux_keys: false,
+                key_password: None,
+                budget: Arc::new(Budget::new()),
+                checkpoint: None,
+            },
+        }
+    }
+
+    /// The searches of a run, each given by the shared nibbles its levels need: per contract
+    /// its salt when one is mined, then its auxiliaries
+    pub fn searches(&self) -> Vec<Vec<usize>> {
+        let auxiliaries: Vec<usize> = (1..=self.target_depth).collect();
+        (0..self.num_contracts)
+            .flat_map(|index| {
+                // The first contract sets the cluster's target unless one is given
+                let salt = (self.salts.cluster_nibbles > 0
+                    && (index > 0 || self.salts.cluster_target.is_some()))
+                .then(|| vec![self.salts.cluster_nibbles]);
+                salt.into_iter().chain([auxiliaries.clone()])
+            })
+            .collect()
+    }
+}
+
+/// Builds a `Create2Config`, checking it before any mining starts
+#[derive(Clone)]
+pub struct Create2ConfigBuilder {
+    config: Create2Config,
+}
+
+impl Create2ConfigBuilder {
+    pub fn salts(mut self, salts: SaltScheme) -> Self {
+        self.config.salts = salts;
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.config.num_threads = num_threads;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    /// Mine spendable auxiliary keys, encrypted with `key_password` if given
+    pub fn aux_keys(mut self, key_password: Option<String>) -> Self {
+        self.config.aux_keys = true;
+        self.config.key_password = key_password;
+        self
+    }
+
+    pub fn budget(mut self, budget: Arc<Budget>) -> Self {
+        self.config.budget = budget;
+        self
+    }
+
+    pub fn checkpoint(mut self, checkpoint: Arc<CheckpointFile>) -> Self {
+        self.config.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub fn build(self) -> Result<Create2Config, Error> {
+        let config = self.config;
+        check_search_params(config.target_depth, config.num_threads)?;
+        if config.num_contracts == 0 {
+            return Err(Error::InvalidInput(
+                "Number of contracts must be at least 1".to_string(),
+            ));
+        }
+        if config.salts.prefix.len() > MAX_SALT_PREFIX {
+            return Err(Error::InvalidInput(format!(
+                "Salt prefix must be at most {MAX_SALT_PREFIX} bytes, got {}",
+                config.salts.prefix.len()
+            )));
+        }
+        if config.salts.cluster_nibbles > MAX_DEPTH {
+            return Err(Error::InvalidInput(format!(
+                "Cluster nibbles must be at most {MAX_DEPTH}, got {}",
+                config.salts.cluster_nibbles
+            )));
+        }
+        Ok(config)
+    }
+}
+
+/// Parameters for mining auxiliaries around existing accounts
+#[derive(Clone)]
+pub struct TargetConfig {
+    /// Nibbles the deepest auxiliary shares with its target's key
+    pub target_depth: usize,
+    /// Leading nibbles the existing state already shares with each target; only deeper levels
+    /// are mined
+    pub skip_nibbles: usize,
+    /// Number of mining threads
+    pub num_threads: usize,
+    /// Seed of the candidate addresses, which determines the result
+    pub seed: u64,
+    /// Limits on the attempts and time of the run
+    pub budget: Arc<Budget>,
+    /// File recording the progress of every target, if any
+    pub checkpoint: Option<Arc<CheckpointFile>>,
+}
+
+impl TargetConfig {
+    /// Builder of a config mining auxiliaries `target_depth` nibbles deep around each target,
+    /// skipping the levels mainnet already fills and with a random seed
+    pub fn builder(target_depth: usize) -> TargetConfigBuilder {
+        TargetConfigBuilder {
+            config: TargetConfig {
+                target_depth,
+                skip_nibbles: DEFAULT_SKIP_NIBBLES,
+                num_threads: num_cpus::get(),
+                seed: random_seed(),
+                budget: Arc::new(Budget::new()),
+                checkpoint: None,
+            },
+        }
+    }
+
+    /// The searches of a run around `num_targets` targets, each given by the shared nibbles
+    /// its levels need
+    pub fn searches(&self, num_targets: usize) -> Vec<Vec<usize>> {
+        vec![(self.skip_nibbles + 1..=self.target_depth).collect(); num_targets]
+    }
+}
+
+/// Builds a `TargetConfig`, checking it before any mining starts
+#[derive(Clone)]
+pub struct TargetConfigBuilder {
+    config: TargetConfig,
+}
+
+impl TargetConfigBuilder {
+    pub fn skip_nibbles(mut self, skip_nibbles: usize) -> Self {
+        self.config.skip_nibbles = skip_nibbles;
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.config.num_threads = num_threads;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn budget(mut self, budget: Arc<Budget>) -> Self {
+        self.config.budget = budget;
+        self
+    }
+
+    pub fn checkpoint(mut self, checkpoint: Arc<CheckpointFile>) -> Self {
+        self.config.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub fn build(self) -> Result<TargetConfig, Error> {
+        let config = self.config;
+        check_search_params(config.target_depth, config.num_threads)?;
+        if config.skip_nibbles >= config.target_depth {
+            return Err(Error::InvalidInput(format!(
+                "Skipped nibbles ({}) must be below the depth ({})",
+                config.skip_nibbles, config.target_depth
+            )));
+        }
+        Ok(config)
+    }
+}
+
+/// Result of mining auxiliaries around existing accounts
+#[derive(Serialize, Deserialize)]
+pub struct TargetMiningResult {
+    pub target_depth: usize,
+    /// Levels left to the existing state; auxiliary `i` shares `skip_nibbles + i + 1` nibbles
+    pub skip_nibbles: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub total_time: f64,
+    /// How the run ended; unless complete, `targets` holds the ones mined before it stopped
+    #[serde(default)]
+    pub status: SearchStatus,
+    pub targets: Vec<TargetWithAuxiliaries>,
+}
+
+/// An existing account with the auxiliaries mined around it
+#[derive(Serialize, Deserialize)]
+pub struct TargetWithAuxiliaries {
+    /// Address or account-trie key, as given
+    pub target: String,
+    pub trie_key: String,
+    pub auxiliary_accounts: Vec<String>,
+}
+
+/// A mined auxiliary account, with its secret key when mined as an EOA
+struct MinedAccount {
+    address: [u8; 20],
+    secret_key: Option<[u8; 32]>,
 }
 
 /// Main entry point for CREATE2-based account mining
 pub fn mine_create2_accounts(
-    deployer: [u8; 20],
-    num_contracts: usize,
-    target_depth: usize,
-    num_threads: usize,
+    config: &Create2Config,
     init_code: &[u8],
     deploy_code: &[u8],
-    storage_keys: &[[u8; 20]],
-    output_path: &str,
-) {
+    storage_branch: &[StorageSlot],
+    existing_accounts: &[[u8; 32]],
+) -> Result<Create2MiningResult, Error> {
+    let Create2Config {
+        deployer,
+        num_contracts,
+        ref salts,
+        target_depth,
+        num_threads,
+        seed,
+        aux_keys,
+        ref key_password,
+        ref budget,
+        ref checkpoint,
+    } = *config;
+
     info!("");
     info!("╔════════════════════════════════════════════════════════════════════════╗");
     info!("║                      CREATE2 ACCOUNT MINING MODE                       ║");
@@ -58,8 +548,23 @@ pub fn mine_create2_accounts(
     info!("");
     info!("Deployer: 0x{}", hex::encode(deployer));
     info!("Contracts to deploy: {num_contracts}");
+    if !salts.prefix.is_empty() {
+        info!("Salt prefix: 0x{}", hex::encode(&salts.prefix));
+    }
+    if salts.cluster_nibbles > 0 {
+        info!("Salt mining: {}", salts.describe());
+    }
     info!("Target trie depth: {target_depth}");
     info!("Mining threads: {num_threads}");
+    info!("Seed: {seed}");
+    if aux_keys {
+        let storage = if key_password.is_some() {
+            "encrypted"
+        } else {
+            "plain"
+        };
+        info!("Auxiliary keys: mined, stored {storage}");
+    }
     info!("");
 
     let total_start = Instant::now();
@@ -67,26 +572,107 @@ pub fn mine_create2_accounts(
     // Calculate init code hash
     let init_code_hash = keccak256(init_code);
     info!("Init code hash: 0x{}", hex::encode(init_code_hash));
+    let checkpoint = checkpoint.as_deref();
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.bind_deployment(&deployer, &init_code_hash)?;
+        checkpoint.bind_salts(&salts.describe())?;
+    }
+
+    // Stretch the password once up front rather than per key. A resumed run keeps the derived
+    // key of the checkpoint, so keys mined before and after the interruption decrypt alike
+    let keystore = match (key_password.as_deref(), checkpoint) {
+        (None, _) => None,
+        (Some(password), checkpoint) => match checkpoint.and_then(CheckpointFile::key_encryption) {
+            Some(params) => Some(Keystore::from_params(&params, password).map_err(|e| {
+                Error::InvalidInput(format!("Invalid checkpoint key encryption: {e}"))
+            })?),
+            None => {
+                let keystore = Keystore::new(password);
+                if let Some(checkpoint) = checkpoint {
+                    checkpoint.set_key_encryption(keystore.params());
+                }
+                Some(keystore)
+            }
+        },
+    };
 
     let mut contracts = Vec::new();
+    let mut mined_accounts = Vec::new();
+    let mut cluster_target = salts.cluster_target;
+    let mut next_counter = 0;
+    let mut status = SearchStatus::Complete;
+    let monitor = budget.progress_monitor();
+    monitor.plan(&config.searches());
 
     // Process each contract
     for contract_idx in 0..num_contracts {
-        let salt = contract_idx as u32;
+        let salt = match cluster_target {
+            Some(target) if salts.cluster_nibbles > 0 => {
+                monitor.next_search();
+                let (salt, salt_status) = mine_salt(
+                    (&deployer, &init_code_hash),
+                    salts,
+                    &target,
+                    next_counter,
+                    num_threads,
+                    budget,
+                    &SearchRecord::new(checkpoint, &format!("salts-{contract_idx}")),
+                )?;
+                status = salt_status;
+                match salt {
+                    Some(salt) => salt,
+                    None => break,
+                }
+            }
+            // Without mining (or for the first contract, without a target) take the next counter
+            _ => salts.salt(next_counter),
+        };
+        next_counter = SaltScheme::counter(&salt) + 1;
 
         // Calculate CREATE2 address
-        let contract_address = calculate_create2_address(&deployer, salt, &init_code_hash);
+        let contract_address = calculate_create2_address(&deployer, &salt, &init_code_hash);
+        if salts.cluster_nibbles > 0 && cluster_target.is_none() {
+            cluster_target = Some(keccak256(&contract_address));
+        }
 
         info!(
-            "Contract {}/{} - Address: 0x{}...",
+            "Contract {}/{} - Address: 0x{}... (salt counter {})",
             contract_idx + 1,
             num_contracts,
-            hex::encode(&contract_address[..4])
+            hex::encode(&contract_address[..4]),
+            next_counter - 1
         );
 
         // Mine auxiliary accounts for this contract
-        let auxiliaries =
-            mine_auxiliaries_for_contract(&contract_address, target_depth, num_threads);
+        let stream = format!("auxiliaries-{contract_idx}");
+        let candidates = if aux_keys {
+            CandidateSource::Keys
+        } else {
+            CandidateSource::Seeded(NonceKeys::new(seed, &stream))
+        };
+        monitor.next_search();
+        let (mined, auxiliaries_status) = mine_auxiliaries(
+            (&keccak256(&contract_address), 0),
+            target_depth,
+            num_threads,
+            candidates,
+            budget,
+            &SearchRecord::new(checkpoint, &stream),
+            keystore.as_ref(),
+        )?;
+        status = auxiliaries_status;
+        let auxiliary_keys = mined
+            .iter()
+            .filter_map(|account| {
+                let secret_key = account.secret_key?;
+                Some(encode_secret_key(
+                    &account.address,
+                    &secret_key,
+                    keystore.as_ref(),
+                ))
+            })
+            .collect();
+        let auxiliaries: Vec<[u8; 20]> = mined.iter().map(|account| account.address).collect();
 
         contracts.push(ContractWithAuxiliaries {
             salt,
@@ -95,12 +681,32 @@ pub fn mine_create2_accounts(
                 .iter()
                 .map(|a| format!("0x{}", hex::encode(a)))
                 .collect(),
+            auxiliary_keys,
         });
 
         info!("  Mined {} auxiliary accounts", auxiliaries.len());
+        mined_accounts.push((contract_address, auxiliaries));
+        if !status.is_complete() {
+            break;
+        }
     }
 
     let total_time = total_start.elapsed().as_secs_f64();
+    if !status.is_complete() {
+        log::warn!(
+            "Mining stopped ({status}) after {} of {num_contracts} contracts",
+            contracts.len()
+        );
+    }
+
+    let storage_slots: Vec<[u8; 32]> = storage_branch.iter().map(|slot| slot.storage_key).collect();
+    let account_trie = account_trie_report(
+        &mined_accounts,
+        deploy_code,
+        &contract_storage(init_code, &deployer, &storage_slots),
+        existing_accounts,
+    );
+    account_trie.print("Account Trie");
 
     // Create result structure
     let result = Create2MiningResult {
@@ -108,45 +714,232 @@ pub fn mine_create2_accounts(
         init_code_hash: format!("0x{}", hex::encode(init_code_hash)),
         init_code: format!("0x{}", hex::encode(init_code)),
         deploy_code: format!("0x{}", hex::encode(deploy_code)),
-        storage_keys: storage_keys
+        storage_keys: storage_branch.iter().map(StorageSlot::key_hex).collect(),
+        storage_slots: storage_branch
             .iter()
-            .map(|k| format!("0x{}", hex::encode(k)))
+            .map(|slot| format!("0x{}", hex::encode(slot.storage_key)))
             .collect(),
         target_depth,
         num_contracts,
+        seed: Some(seed),
         total_time,
+        status,
         contracts,
+        account_trie: Some(account_trie),
+        key_encryption: keystore.map(|keystore| keystore.params().clone()),
+        cluster: cluster_target
+            .filter(|_| salts.cluster_nibbles > 0)
+            .map(|target| SaltCluster {
+                target: format!("0x{}", hex::encode(target)),
+                nibbles: salts.cluster_nibbles,
+            }),
     };
 
-    // Write to JSON file
-    match serde_json::to_string_pretty(&result) {
-        Ok(json) => {
-            if let Err(e) = fs::write(output_path, json) {
-                log::error!("Failed to write JSON: {e}");
-            } else {
-                info!("");
-                info!("═══ CREATE2 Mining Statistics ═══");
-                info!("Total contracts: {num_contracts}");
-                info!("Target depth: {target_depth}");
-                info!("Total auxiliary accounts: {}", num_contracts * target_depth);
-                info!("Total time: {total_time:.2} seconds");
-                info!(
-                    "Average time per contract: {:.2} seconds",
-                    total_time / num_contracts as f64
-                );
-                info!("Results saved to: {output_path}");
-            }
-        }
-        Err(e) => {
-            log::error!("Failed to serialize to JSON: {e}");
+    info!("");
+    info!("═══ CREATE2 Mining Statistics ═══");
+    info!("Total contracts: {}", result.contracts.len());
+    info!("Target depth: {target_depth}");
+    info!(
+        "Total auxiliary accounts: {}",
+        mined_accounts
+            .iter()
+            .map(|(_, auxiliaries)| auxiliaries.len())
+            .sum::<usize>()
+    );
+    info!("Total time: {total_time:.2} seconds");
+    if !result.contracts.is_empty() {
+        info!(
+            "Average time per contract: {:.2} seconds",
+            total_time / result.contracts.len() as f64
+        );
+    }
+    Ok(result)
+}
+
+/// Write a CREATE2 result to a JSON file
+pub fn write_create2_result(result: &Create2MiningResult, output_path: &str) -> Result<(), Error> {
+    write_json(result, output_path)
+}
+
+/// Mine auxiliary accounts around existing accounts, given as labels (address or key as the
+/// user wrote it) with their account-trie keys
+pub fn mine_target_accounts(
+    config: &TargetConfig,
+    targets: &[(String, [u8; 32])],
+) -> Result<TargetMiningResult, Error> {
+    let TargetConfig {
+        target_depth,
+        skip_nibbles,
+        num_threads,
+        seed,
+        ref budget,
+        ref checkpoint,
+    } = *config;
+
+    info!("");
+    info!("╔════════════════════════════════════════════════════════════════════════╗");
+    info!("║                      TARGET ACCOUNT MINING MODE                        ║");
+    info!("╚════════════════════════════════════════════════════════════════════════╝");
+    info!("");
+    info!("Targets: {}", targets.len());
+    info!("Target trie depth: {target_depth}");
+    info!("Levels left to the existing state: {skip_nibbles}");
+    info!("Mining threads: {num_threads}");
+    info!("Seed: {seed}");
+    info!("");
+
+    let checkpoint = checkpoint.as_deref();
+    if let Some(checkpoint) = checkpoint {
+        let keys: Vec<u8> = targets.iter().flat_map(|(_, key)| *key).collect();
+        let description = format!(
+            "0x{} skipping {skip_nibbles} nibbles",
+            hex::encode(keccak256(&keys))
+        );
+        checkpoint.bind_targets(&description)?;
+    }
+
+    let total_start = Instant::now();
+    let mut mined_targets = Vec::new();
+    let mut status = SearchStatus::Complete;
+    let monitor = budget.progress_monitor();
+    monitor.plan(&config.searches(targets.len()));
+    for (index, (target, trie_key)) in targets.iter().enumerate() {
+        info!(
+            "Target {}/{} - {target} (key 0x{}...)",
+            index + 1,
+            targets.len(),
+            hex::encode(&trie_key[..4])
+        );
+        let stream = format!("target-auxiliaries-{index}");
+        monitor.next_search();
+        let (mined, target_status) = mine_auxiliaries(
+            (trie_key, skip_nibbles),
+            target_depth,
+            num_threads,
+            CandidateSource::Seeded(NonceKeys::new(seed, &stream)),
+            budget,
+            &SearchRecord::new(checkpoint, &stream),
+            None,
+        )?;
+        status = target_status;
+        info!("  Mined {} auxiliary accounts", mined.len());
+
+        mined_targets.push(TargetWithAuxiliaries {
+            target: target.clone(),
+            trie_key: format!("0x{}", hex::encode(trie_key)),
+            auxiliary_accounts: mined
+                .iter()
+                .map(|account| format!("0x{}", hex::encode(account.address)))
+                .collect(),
+        });
+        if !status.is_complete() {
+            log::warn!(
+                "Mining stopped ({status}) after {} of {} targets",
+                index + 1,
+                targets.len()
+            );
+            break;
         }
     }
+
+    let total_time = total_start.elapsed().as_secs_f64();
+    info!("");
+    info!("═══ Target Mining Statistics ═══");
+    info!("Total targets: {}", mined_targets.len());
+    info!(
+        "Total auxiliary accounts: {}",
+        mined_targets
+            .iter()
+            .map(|target| target.auxiliary_accounts.len())
+            .sum::<usize>()
+    );
+    info!("Total time: {total_time:.2} seconds");
+
+    Ok(TargetMiningResult {
+        target_depth,
+        skip_nibbles,
+        seed: Some(seed),
+        total_time,
+        status,
+        targets: mined_targets,
+    })
+}
+
+/// Write a target result to a JSON file
+pub fn write_target_result(result: &TargetMiningResult, output_path: &str) -> Result<(), Error> {
+    write_json(result, output_path)
+}
+
+fn write_json(result: &impl Serialize, output_path: &str) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(result)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize to JSON: {e}")))?;
+    fs::write(output_path, json).map_err(|e| Error::io(output_path, e))?;
+    info!("Results saved to: {output_path}");
+    Ok(())
+}
+
+/// Storage a contract deployed by `deployer` holds after its constructor ran: the mint of a
+/// generated ERC20 constructor (see `evm::mint_writes`), then every mined slot set to 1
+pub fn contract_storage(
+    init_code: &[u8],
+    deployer: &[u8; 20],
+    storage_slots: &[[u8; 32]],
+) -> Vec<StorageEntry> {
+    let mut storage = evm::mint_writes(init_code, deployer);
+    storage.extend(storage_slots.iter().map(|&slot| (slot, slot_from_u64(1))));
+    storage
+}
+
+/// Storage trie of a contract holding `storage`; later writes to a slot replace earlier ones
+pub fn contract_storage_trie(storage: &[StorageEntry]) -> Trie {
+    mpt::storage_trie(storage.iter().copied())
+}
+
+/// Build the account trie holding every contract (with code `deploy_code` and storage root
+/// `storage_root`) and its auxiliaries, next to `existing` account-trie keys
+pub fn build_account_trie(
+    mined_accounts: &[ContractAccounts],
+    deploy_code: &[u8],
+    storage_root: [u8; 32],
+    existing: &[[u8; 32]],
+) -> Trie {
+    let contract = Account::contract(deploy_code, storage_root);
+    let funded = Account::funded(AUXILIARY_BALANCE);
+
+    let mut accounts: Vec<([u8; 32], Account)> =
+        existing.iter().map(|&key| (key, funded)).collect();
+    for (contract_address, auxiliaries) in mined_accounts {
+        accounts.push((mpt::account_key(contract_address), contract));
+        accounts.extend(
+            auxiliaries
+                .iter()
+                .map(|auxiliary| (mpt::account_key(auxiliary), funded)),
+        );
+    }
+    mpt::account_trie(accounts)
+}
+
+/// Report the account trie of a mining run with the path of each contract
+fn account_trie_report(
+    mined_accounts: &[ContractAccounts],
+    deploy_code: &[u8],
+    storage: &[StorageEntry],
+    existing: &[[u8; 32]],
+) -> TrieReport {
+    let storage_root = contract_storage_trie(storage).root_hash();
+    let trie = build_account_trie(mined_accounts, deploy_code, storage_root, existing);
+
+    let keys: Vec<[u8; 32]> = mined_accounts
+        .iter()
+        .map(|(contract_address, _)| mpt::account_key(contract_address))
+        .collect();
+    TrieReport::new(&trie, &keys)
 }
 
 /// Calculate CREATE2 address
-fn calculate_create2_address(
+pub fn calculate_create2_address(
     deployer: &[u8; 20],
-    salt: u32,
+    salt: &[u8; 32],
     init_code_hash: &[u8; 32],
 ) -> [u8; 20] {
     let mut data = Vec::with_capacity(85);
@@ -157,10 +950,8 @@ fn calculate_create2_address(
     // Deployer address (20 bytes)
     data.extend_from_slice(deployer);
 
-    // Salt (32 bytes) - convert u32 to 32 bytes with zero padding
-    let mut salt_bytes = [0u8; 32];
-    salt_bytes[28..32].copy_from_slice(&salt.to_be_bytes());
-    data.extend_from_slice(&salt_bytes);
+    // Salt (32 bytes)
+    data.extend_from_slice(salt);
 
     // Init code hash (32 bytes)
     data.extend_from_slice(init_code_hash);
@@ -173,143 +964,395 @@ fn calculate_create2_address(
     address
 }
 
-/// Mine auxiliary accounts for a single contract
-fn mine_auxiliaries_for_contract(
-    contract_address: &[u8; 20],
-    target_depth: usize,
+/// Measure the CPU hashrates of `config`'s searches: candidates per second for auxiliaries
+/// and, with salt mining, for salts, each mining a throwaway search too deep to finish for
+/// about `duration`
+pub fn calibrate(config: &Create2Config, duration: Duration) -> Result<(f64, Option<f64>), Error> {
+    let target = keccak256(b"calibration");
+    let candidates = if config.aux_keys {
+        CandidateSource::Keys
+    } else {
+        CandidateSource::Seeded(NonceKeys::new(config.seed, "calibration"))
+    };
+    let auxiliaries = measure_hashrate(duration, |budget| {
+        mine_auxiliaries(
+            (&target, 0),
+            MAX_DEPTH,
+            config.num_threads,
+            candidates,
+            budget,
+            &SearchRecord::new(None, "calibration"),
+            None,
+        )
+        .map(|_| ())
+    })?;
+
+    let salts = (config.salts.cluster_nibbles > 0)
+        .then(|| {
+            let salts = SaltScheme {
+                cluster_nibbles: MAX_DEPTH,
+                ..config.salts.clone()
+            };
+            measure_hashrate(duration, |budget| {
+                mine_salt(
+                    (&config.deployer, &target),
+                    &salts,
+                    &target,
+                    0,
+                    config.num_threads,
+                    budget,
+                    &SearchRecord::new(None, "calibration-salts"),
+                )
+                .map(|_| ())
+            })
+        })
+        .transpose()?;
+    Ok((auxiliaries, salts))
+}
+
+/// Mine the salt of one contract: the lowest counter from `start` on whose address hash shares
+/// `salts.cluster_nibbles` nibbles with `target`. No salt if the search ended before finding one
+fn mine_salt(
+    (deployer, init_code_hash): (&[u8; 20], &[u8; 32]),
+    salts: &SaltScheme,
+    target: &[u8; 32],
+    start: u64,
     num_threads: usize,
-) -> Vec<[u8; 20]> {
-    let mut auxiliaries = Vec::new();
+    budget: &Budget,
+    record: &SearchRecord,
+) -> Result<(Option<[u8; 32]>, SearchStatus), Error> {
+    let (progress, next) = record.resume(1, |candidate| parse_word(&candidate.value))?;
+    let progress = progress.requiring(vec![salts.cluster_nibbles]);
+    let goal = progress.all_slots();
 
-    // Calculate the hash of the contract address - this is the key in the account trie
-    let contract_hash = keccak256(contract_address);
+    let mut status = SearchStatus::Complete;
+    if let Some(scanned) = next {
+        let encode = |salt: &[u8; 32]| Candidate {
+            value: format!("0x{}", hex::encode(salt)),
+            secret_key: None,
+        };
+        run_workers(
+            &progress,
+            goal,
+            budget.nonces(start, scanned.max(start)),
+            num_threads,
+            budget,
+            |thread_id, frontier| {
+                mine_salt_worker(
+                    thread_id,
+                    num_threads,
+                    (deployer, init_code_hash),
+                    salts,
+                    target,
+                    &progress,
+                    frontier,
+                )
+            },
+            |scanned| record.save(&progress, scanned, encode),
+        )?;
+        status = progress.status(goal);
+        if status.is_complete() {
+            record.complete(&progress, encode);
+        }
+    }
 
-    for depth in 1..=target_depth {
-        debug!("  Mining auxiliary at depth {depth}/{target_depth}");
+    let salt = progress
+        .into_leading_slots()
+        .pop()
+        .map(|(salt, time_taken)| {
+            debug!(
+                "  Mined salt 0x{} in {time_taken:.2} seconds",
+                hex::encode(salt)
+            );
+            salt
+        });
+    Ok((salt, status))
+}
 
-        // Mine an account whose hash shares 'depth' nibbles with the contract hash
-        let auxiliary = mine_account_with_hash_prefix(&contract_hash, depth, num_threads);
+/// Worker thread for salt mining
+fn mine_salt_worker(
+    thread_id: usize,
+    num_threads: usize,
+    (deployer, init_code_hash): (&[u8; 20], &[u8; 32]),
+    salts: &SaltScheme,
+    target: &[u8; 32],
+    progress: &LevelSearch<[u8; 32]>,
+    frontier: &Frontier,
+) {
+    let mut counter = frontier.first(thread_id);
+    let mut attempts = 0u64;
+    let goal = progress.all_slots();
+    const BATCH_SIZE: u64 = 1000;
+
+    loop {
+        if attempts.is_multiple_of(BATCH_SIZE) {
+            frontier.record(thread_id, counter);
+            if progress.settled(goal, counter) {
+                break;
+            }
+        }
+        attempts += 1;
+
+        let salt = salts.salt(counter);
+        let address = calculate_create2_address(deployer, &salt, init_code_hash);
+        if count_shared_nibbles(&keccak256(&address), target) >= salts.cluster_nibbles
+            && progress.improves(0, counter)
+        {
+            progress.fill(0, counter, salt);
+        }
+
+        counter += num_threads as u64;
+    }
+}
+
+/// Mine auxiliary accounts around one account-trie key. Auxiliary `i` shares
+/// `skip_nibbles + i + 1` nibbles with the key (the last one at least `target_depth`). Every
+/// candidate is checked against all outstanding auxiliaries at once, so the deepest one
+/// dominates the cost. A search that ends early returns the auxiliaries up to the first one
+/// it did not find
+fn mine_auxiliaries(
+    (target, skip_nibbles): (&[u8; 32], usize),
+    target_depth: usize,
+    num_threads: usize,
+    candidates: CandidateSource,
+    budget: &Budget,
+    record: &SearchRecord,
+    keystore: Option<&Keystore>,
+) -> Result<(Vec<MinedAccount>, SearchStatus), Error> {
+    let (progress, next) = record.resume(target_depth - skip_nibbles, |candidate| {
+        let account = candidate_account(candidate, keystore)?;
+        if matches!(candidates, CandidateSource::Keys) && account.secret_key.is_none() {
+            return Err(format!("No secret key for {}", candidate.value));
+        }
+        Ok(account)
+    })?;
+    let progress = progress.requiring((skip_nibbles + 1..=target_depth).collect());
+    let goal = progress.all_slots();
+
+    let mut status = SearchStatus::Complete;
+    if let Some(start) = next {
+        let encode = |account: &MinedAccount| account_candidate(account, keystore);
+        run_workers(
+            &progress,
+            goal,
+            budget.nonces(0, start),
+            num_threads,
+            budget,
+            |thread_id, frontier| match candidates {
+                CandidateSource::Seeded(keys) => {
+                    let candidates = SeededAddresses(keys);
+                    mine_levels_worker(
+                        thread_id,
+                        num_threads,
+                        (target, skip_nibbles),
+                        candidates,
+                        &progress,
+                        frontier,
+                    );
+                }
+                CandidateSource::Keys => {
+                    let candidates = KeyCandidates::new();
+                    mine_levels_worker(
+                        thread_id,
+                        num_threads,
+                        (target, skip_nibbles),
+                        candidates,
+                        &progress,
+                        frontier,
+                    );
+                }
+            },
+            |scanned| record.save(&progress, scanned, encode),
+        )?;
+        status = progress.status(goal);
+        if status.is_complete() {
+            record.complete(&progress, encode);
+        }
+    }
+
+    let auxiliaries: Vec<MinedAccount> = progress
+        .into_leading_slots()
+        .into_iter()
+        .map(|(account, _)| account)
+        .collect();
 
+    for auxiliary in &auxiliaries {
         debug!(
             "  Found: 0x{} (hash shares {} nibbles)",
-            hex::encode(&auxiliary[..4]),
-            depth
+            hex::encode(&auxiliary.address[..4]),
+            count_shared_nibbles(&keccak256(&auxiliary.address), target)
         );
-
-        auxiliaries.push(auxiliary);
     }
 
-    auxiliaries
+    Ok((auxiliaries, status))
 }
 
-/// Mine an account whose hash shares exactly `depth` nibbles with the target hash
-fn mine_account_with_hash_prefix(
-    target_hash: &[u8; 32],
-    depth: usize,
-    num_threads: usize,
-) -> [u8; 20] {
-    let result = Arc::new(Mutex::new(None));
-    let found = Arc::new(Mutex::new(false));
+/// Where the candidate auxiliary accounts come from
+#[derive(Clone, Copy)]
+enum CandidateSource {
+    /// Addresses derived from the seed, which nobody holds a key for
+    Seeded(NonceKeys),
+    /// Addresses of secret keys walked from a random start (not reproducible from the seed,
+    /// which is stored in plain next to the keys)
+    Keys,
+}
 
-    let handles: Vec<_> = (0..num_threads)
-        .map(|thread_id| {
-            let result_clone = Arc::clone(&result);
-            let found_clone = Arc::clone(&found);
-            let target_hash_copy = *target_hash;
+/// Candidate accounts of one worker
+trait Candidates {
+    /// The candidate address for `nonce`
+    fn address(&mut self, nonce: u64) -> [u8; 20];
+    /// The mined account for the last candidate
+    fn account(&self, address: [u8; 20]) -> MinedAccount;
+}
 
-            thread::spawn(move || {
-                mine_hash_worker(
-                    thread_id,
-                    &target_hash_copy,
-                    depth,
-                    result_clone,
-                    found_clone,
-                );
-            })
-        })
-        .collect();
+/// Addresses derived from the seed
+struct SeededAddresses(NonceKeys);
+
+impl Candidates for SeededAddresses {
+    fn address(&mut self, nonce: u64) -> [u8; 20] {
+        self.0.address(nonce)
+    }
+
+    fn account(&self, address: [u8; 20]) -> MinedAccount {
+        MinedAccount {
+            address,
+            secret_key: None,
+        }
+    }
+}
 
-    for handle in handles {
-        handle.join().unwrap();
+/// Addresses of consecutive secret keys, so a match comes with its key
+struct KeyCandidates {
+    secp: Secp256k1<All>,
+    walk: KeyWalk,
+}
+
+impl KeyCandidates {
+    fn new() -> Self {
+        let secp = Secp256k1::new();
+        let walk = KeyWalk::new(&secp, &mut rand::thread_rng());
+        KeyCandidates { secp, walk }
     }
+}
 
-    result.lock().unwrap().expect("Failed to find account")
+impl Candidates for KeyCandidates {
+    fn address(&mut self, _nonce: u64) -> [u8; 20] {
+        if !self.walk.advance() {
+            self.walk = KeyWalk::new(&self.secp, &mut rand::thread_rng());
+        }
+        self.walk.address()
+    }
+
+    fn account(&self, address: [u8; 20]) -> MinedAccount {
+        MinedAccount {
+            address,
+            secret_key: Some(self.walk.secret_key()),
+        }
+    }
 }
 
-/// Worker thread for hash-based mining
-fn mine_hash_worker(
+/// Worker thread for hash-based mining of all auxiliary levels at once
+fn mine_levels_worker<C: Candidates>(
     thread_id: usize,
-    target_hash: &[u8; 32],
-    required_nibbles: usize,
-    result: Arc<Mutex<Option<[u8; 20]>>>,
-    found: Arc<Mutex<bool>>,
+    num_threads: usize,
+    (target, skip_nibbles): (&[u8; 32], usize),
+    mut candidates: C,
+    progress: &LevelSearch<MinedAccount>,
+    frontier: &Frontier,
 ) {
-    let mut rng = rand::thread_rng();
+    // Thread `t` walks nonces `start + t, start + t + n, ...`, disjoint from the other threads
+    let mut nonce = frontier.first(thread_id);
     let mut attempts = 0u64;
+    let goal = progress.all_slots();
     const BATCH_SIZE: u64 = 1000;
 
     loop {
-        // Check if another thread found a result
-        if attempts % BATCH_SIZE == 0 && *found.lock().unwrap() {
-            break;
+        // Check if no level can improve any more
+        if attempts.is_multiple_of(BATCH_SIZE) {
+            frontier.record(thread_id, nonce);
+            if progress.settled(goal, nonce) {
+                break;
+            }
         }
 
         attempts += 1;
-        if attempts % 1000000 == 0 {
-            debug!(
-                "Thread {} (depth {}): {} million attempts",
-                thread_id,
-                required_nibbles,
-                attempts / 1000000
-            );
-        }
-
-        // Generate random address
-        let mut address = [0u8; 20];
-        rng.fill(&mut address);
 
         // Hash the address - this is how it's indexed in the account trie
+        let address = candidates.address(nonce);
         let address_hash = keccak256(&address);
 
-        // Check if the hash matches the required prefix
-        if has_hash_prefix(&address_hash, target_hash, required_nibbles) {
-            let mut found_lock = found.lock().unwrap();
-            if !*found_lock {
-                *found_lock = true;
-                let mut result_lock = result.lock().unwrap();
-                *result_lock = Some(address);
-                debug!("Thread {thread_id} found match after {attempts} attempts");
-            }
-            break;
+        // One comparison covers every outstanding level: the shared length picks the level
+        let shared = count_shared_nibbles(&address_hash, target).saturating_sub(skip_nibbles);
+        if let Some(level) = progress.level_for(shared)
+            && progress.improves(level, nonce)
+            && progress.fill(level, nonce, candidates.account(address))
+        {
+            debug!(
+                "Thread {thread_id} filled level {} at nonce {nonce}",
+                level + 1
+            );
         }
+
+        nonce += num_threads as u64;
     }
 }
 
-/// Check if two hashes share the specified number of nibbles as prefix
-fn has_hash_prefix(hash_a: &[u8; 32], hash_b: &[u8; 32], nibbles: usize) -> bool {
-    if nibbles == 0 {
-        return true;
+/// A secret key as written to results: encrypted with `keystore`, or plain without one
+fn encode_secret_key(
+    address: &[u8; 20],
+    secret_key: &[u8; 32],
+    keystore: Option<&Keystore>,
+) -> StoredKey {
+    match keystore {
+        Some(keystore) => StoredKey::Encrypted(Box::new(keystore.encrypt(address, secret_key))),
+        None => StoredKey::Plain(format!("0x{}", hex::encode(secret_key))),
     }
+}
 
-    let full_bytes = nibbles / 2;
-    let has_half_byte = nibbles % 2 == 1;
-
-    // Check full bytes must match
-    for i in 0..full_bytes {
-        if hash_a[i] != hash_b[i] {
-            return false;
+/// A secret key read back from results, decrypted with `keystore` if given
+fn decode_secret_key(
+    address: &[u8; 20],
+    key: &StoredKey,
+    keystore: Option<&Keystore>,
+) -> Result<[u8; 32], String> {
+    match (key, keystore) {
+        (StoredKey::Encrypted(key_file), Some(keystore)) => keystore.decrypt(address, key_file),
+        (StoredKey::Plain(key), None) => hex::decode(key.strip_prefix("0x").unwrap_or(key))
+            .map_err(|e| format!("Invalid key hex: {e}"))?
+            .try_into()
+            .map_err(|_| "Plain keys must be 32 bytes".to_string()),
+        (StoredKey::Plain(_), Some(_)) => Err("Expected an encrypted key".to_string()),
+        (StoredKey::Encrypted(_), None) => {
+            Err("Key is encrypted; a password is required".to_string())
         }
     }
+}
 
-    // Check the half byte if needed
-    if has_half_byte && full_bytes < 32 {
-        let mask = 0xF0; // Check only the high nibble
-        if (hash_a[full_bytes] & mask) != (hash_b[full_bytes] & mask) {
-            return false;
-        }
+/// An auxiliary account as written to a checkpoint
+fn account_candidate(account: &MinedAccount, keystore: Option<&Keystore>) -> Candidate {
+    Candidate {
+        value: format!("0x{}", hex::encode(account.address)),
+        secret_key: account
+            .secret_key
+            .map(|secret_key| encode_secret_key(&account.address, &secret_key, keystore)),
     }
+}
 
-    true
+/// An auxiliary account read back from a checkpoint
+fn candidate_account(
+    candidate: &Candidate,
+    keystore: Option<&Keystore>,
+) -> Result<MinedAccount, String> {
+    let address = parse_address(&candidate.value)?;
+    let secret_key = candidate
+        .secret_key
+        .as_ref()
+        .map(|key| decode_secret_key(&address, key, keystore))
+        .transpose()?;
+    Ok(MinedAccount {
+        address,
+        secret_key,
+    })
 }
 
 /// Compute Keccak256 hash
@@ -320,3 +1363,86 @@ fn keccak256(data: &[u8]) -> [u8; 32] {
     hasher.finalize(&mut output);
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mined_salts_cluster_and_roundtrip() {
+        let salts = SaltScheme {
+            prefix: vec![0xab; 20],
+            cluster_nibbles: 2,
+            cluster_target: Some([0x5a; 32]),
+        };
+        let salt = salts.salt(7);
+        assert_eq!(&salt[..20], &[0xab; 20]);
+        assert_eq!(SaltScheme::counter(&salt), 7);
+
+        let deployer = [0x11; 20];
+        let init_code_hash = keccak256(b"init code");
+        let target = salts.cluster_target.unwrap();
+        let record = SearchRecord::new(None, "salts-0");
+        let budget = Budget::new();
+        let salt = |start, threads| {
+            let (salt, status) = mine_salt(
+                (&deployer, &init_code_hash),
+                &salts,
+                &target,
+                start,
+                threads,
+                &budget,
+                &record,
+            )
+            .unwrap();
+            assert!(status.is_complete());
+            salt.unwrap()
+        };
+        let first = salt(0, 2);
+        let address = calculate_create2_address(&deployer, &first, &init_code_hash);
+        assert!(count_shared_nibbles(&keccak256(&address), &target) >= 2);
+
+        // The next contract continues after the first salt; a single thread finds the same one
+        let start = SaltScheme::counter(&first) + 1;
+        let second = salt(start, 1);
+        assert!(SaltScheme::counter(&second) > SaltScheme::counter(&first));
+        assert_eq!(salt(0, 1), first);
+
+        // Salts are written as hex and read back from hex or, in older files, a number
+        let contract: ContractWithAuxiliaries = serde_json::from_str(
+            r#"{"salt": 3, "contract_address": "0x00", "auxiliary_accounts": []}"#,
+        )
+        .unwrap();
+        assert_eq!(contract.salt, slot_from_u64(3));
+        let json = serde_json::to_string(&contract).unwrap();
+        let contract: ContractWithAuxiliaries = serde_json::from_str(&json).unwrap();
+        assert_eq!(contract.salt, slot_from_u64(3));
+    }
+
+    #[test]
+    fn test_target_auxiliaries_skip_existing_levels() {
+        let target = [0x5a; 32];
+        let record = SearchRecord::new(None, "target-auxiliaries-0");
+        let candidates = CandidateSource::Seeded(NonceKeys::new(5, "target-auxiliaries-0"));
+        let (mined, status) = mine_auxiliaries(
+            (&target, 2),
+            4,
+            2,
+            candidates,
+            &Budget::new(),
+            &record,
+            None,
+        )
+        .unwrap();
+        assert!(status.is_complete());
+
+        // Only the levels below the skipped two are mined: 3 nibbles, then at least 4
+        assert_eq!(mined.len(), 2);
+        let shared: Vec<usize> = mined
+            .iter()
+            .map(|account| count_shared_nibbles(&keccak256(&account.address), &target))
+            .collect();
+        assert_eq!(shared[0], 3);
+        assert!(shared[1] >= 4);
+    }
+}
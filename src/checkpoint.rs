@@ -0,0 +1,390 @@
+//! # Checkpoint Module
+//!
+//! Progress of long mining runs, so an interrupted run continues with `--resume` instead of
+//! starting over. Each search of a run (the storage levels, the siblings of one level, the
+//! salt or the auxiliaries of one contract) is recorded under the name of its candidate stream
+//! whenever a level is mined, together with the nonce below which every candidate has been
+//! tried. Candidates are derived from the seed, so a resumed search continues at that nonce and
+//! ends with the same result as an uninterrupted run.
+//!
+//! A checkpoint also records what its run mines for (seed, depth, storage target, deployer,
+//! init-code hash, salt scheme and targets) and refuses to resume a run that differs in any of
+//! them.
+//!
+//! ## Key Functions
+//! - `CheckpointFile::create`: Starts the checkpoint file of a new run
+//! - `CheckpointFile::resume`: Continues the checkpoint file of an interrupted run
+//! - `SearchRecord`: Resumes one search from a checkpoint and saves its progress
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::keys::{KeyEncryption, StoredKey};
+use crate::search::LevelSearch;
+
+/// Contents of a checkpoint file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub target_depth: usize,
+    /// Key every storage level is mined against (the mined key of the deepest level)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_code_hash: Option<String>,
+    /// How contract salts are picked (prefix and salt mining)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salts: Option<String>,
+    /// Existing accounts auxiliaries are mined around (hash of their keys and skipped levels)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<String>,
+    /// Parameters of the auxiliary key encryption, so resumed keys use the same derived key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_encryption: Option<KeyEncryption>,
+    /// Progress of each search, by candidate stream
+    pub searches: BTreeMap<String, SearchCheckpoint>,
+}
+
+/// Progress of one search
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchCheckpoint {
+    /// Every candidate below this nonce has been tried
+    pub scanned: u64,
+    /// Seconds the search has run for
+    pub elapsed: f64,
+    /// Whether the search finished, so its slots are final
+    pub complete: bool,
+    pub slots: Vec<Option<SlotCheckpoint>>,
+}
+
+/// The candidate holding one slot of a search
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SlotCheckpoint {
+    pub nonce: u64,
+    /// Seconds into the search when the candidate was found
+    #[serde(alias = "time_taken")]
+    pub found_after_secs: f64,
+    #[serde(flatten)]
+    pub candidate: Candidate,
+}
+
+/// A mined candidate as written to a checkpoint
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    /// Mapping key or salt (32-byte hex), or auxiliary address
+    pub value: String,
+    /// Secret key of an auxiliary account, plain or encrypted as in the results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key: Option<StoredKey>,
+}
+
+/// A checkpoint file, rewritten on every update
+#[derive(Debug)]
+pub struct CheckpointFile {
+    path: String,
+    checkpoint: Mutex<Checkpoint>,
+}
+
+impl CheckpointFile {
+    /// Start the checkpoint file of a new run. An existing file holds the progress of another
+    /// run, so it is only replaced with `overwrite`
+    pub fn create(
+        path: &str,
+        seed: u64,
+        target_depth: usize,
+        overwrite: bool,
+    ) -> Result<Self, Error> {
+        if !overwrite && Path::new(path).exists() {
+            return Err(Error::InvalidInput(format!(
+                "Checkpoint {path} already exists; continue its run with --resume {path}, or \
+                 start over with --overwrite-checkpoint"
+            )));
+        }
+        let checkpoint = Checkpoint {
+            seed,
+            target_depth,
+            ..Default::default()
+        };
+        let file = CheckpointFile {
+            path: path.to_string(),
+            checkpoint: Mutex::new(checkpoint.clone()),
+        };
+        file.write(&checkpoint)?;
+        info!("Recording progress in: {path}");
+        Ok(file)
+    }
+
+    /// Continue the checkpoint file of an interrupted run, which must have the same depth
+    pub fn resume(path: &str, target_depth: usize) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&content)
+            .map_err(|e| Error::InvalidInput(format!("Invalid checkpoint {path}: {e}")))?;
+        if checkpoint.target_depth != target_depth {
+            return Err(Error::InvalidInput(format!(
+                "Checkpoint is for depth {}, not {target_depth}",
+                checkpoint.target_depth
+            )));
+        }
+
+        let finished = checkpoint.searches.values().filter(|s| s.complete).count();
+        info!(
+            "Resuming from: {path} (seed {}, {finished} searches finished)",
+            checkpoint.seed
+        );
+        Ok(CheckpointFile {
+            path: path.to_string(),
+            checkpoint: Mutex::new(checkpoint),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.checkpoint.lock().unwrap().seed
+    }
+
+    /// Record the storage target, or check it against the recorded one
+    pub fn bind_storage_target(&self, target: &[u8; 32]) -> Result<(), Error> {
+        let target = format!("0x{}", hex::encode(target));
+        self.update(|c| bind(&mut c.storage_target, "storage target", target))
+    }
+
+    /// Record the deployer and init-code hash, or check them against the recorded ones
+    pub fn bind_deployment(
+        &self,
+        deployer: &[u8; 20],
+        init_code_hash: &[u8; 32],
+    ) -> Result<(), Error> {
+        let deployer = format!("0x{}", hex::encode(deployer));
+        let init_code_hash = format!("0x{}", hex::encode(init_code_hash));
+        self.update(|c| {
+            bind(&mut c.deployer, "deployer", deployer)?;
+            bind(&mut c.init_code_hash, "init code hash", init_code_hash)
+        })
+    }
+
+    /// Record how contract salts are picked, or check it against the recorded scheme
+    pub fn bind_salts(&self, salts: &str) -> Result<(), Error> {
+        self.update(|c| bind(&mut c.salts, "salts", salts.to_string()))
+    }
+
+    /// Record the existing accounts mined around, or check them against the recorded ones
+    pub fn bind_targets(&self, targets: &str) -> Result<(), Error> {
+        self.update(|c| bind(&mut c.targets, "targets", targets.to_string()))
+    }
+
+    pub fn key_encryption(&self) -> Option<KeyEncryption> {
+        self.checkpoint.lock().unwrap().key_encryption.clone()
+    }
+
+    pub fn set_key_encryption(&self, params: &KeyEncryption) {
+        self.update(|c| c.key_encryption = Some(params.clone()));
+    }
+
+    /// Change the checkpoint and write it out. A failed write is logged, so mining goes on
+    fn update<R>(&self, f: impl FnOnce(&mut Checkpoint) -> R) -> R {
+        let mut checkpoint = self.checkpoint.lock().unwrap();
+        let result = f(&mut checkpoint);
+        if let Err(e) = self.write(&checkpoint) {
+            log::error!("Failed to write checkpoint: {e}");
+        }
+        result
+    }
+
+    /// Write out `checkpoint`. The file is replaced in one step, so an interruption never
+    /// leaves it half-written
+    fn write(&self, checkpoint: &Checkpoint) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(checkpoint)
+            .map_err(|e| Error::InvalidInput(format!("Failed to serialize checkpoint: {e}")))?;
+        let temp_path = format!("{}.tmp", self.path);
+        fs::write(&temp_path, json)
+            .and_then(|()| fs::rename(&temp_path, &self.path))
+            .map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+/// Record `value` as a property of the run, or check it against the recorded one
+fn bind(recorded: &mut Option<String>, name: &str, value: String) -> Result<(), Error> {
+    match recorded {
+        Some(recorded) if *recorded != value => Err(Error::InvalidInput(format!(
+            "Checkpoint is for {name} {recorded}, not {value}"
+        ))),
+        Some(_) => Ok(()),
+        None => {
+            *recorded = Some(value);
+            Ok(())
+        }
+    }
+}
+
+/// One search of a run, in a checkpoint file if the run keeps one
+pub struct SearchRecord<'a> {
+    file: Option<&'a CheckpointFile>,
+    stream: String,
+}
+
+impl<'a> SearchRecord<'a> {
+    pub fn new(file: Option<&'a CheckpointFile>, stream: &str) -> Self {
+        SearchRecord {
+            file,
+            stream: stream.to_string(),
+        }
+    }
+
+    /// Name of the candidate stream the search is recorded under
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// The search as the checkpoint left it, with the nonce to continue from (`None` if it
+    /// is complete). Without a checkpoint or progress, a new search starting at nonce 0
+    pub fn resume<T>(
+        &self,
+        num_slots: usize,
+        decode: impl Fn(&Candidate) -> Result<T, String>,
+    ) -> Result<(LevelSearch<T>, Option<u64>), Error> {
+        let saved = self.file.and_then(|file| {
+            let checkpoint = file.checkpoint.lock().unwrap();
+            checkpoint.searches.get(&self.stream).cloned()
+        });
+        let Some(saved) = saved else {
+            return Ok((LevelSearch::new(num_slots), Some(0)));
+        };
+        if saved.slots.len() != num_slots {
+            return Err(Error::InvalidInput(format!(
+                "Checkpoint has {} slots for {}, expected {num_slots}",
+                saved.slots.len(),
+                self.stream
+            )));
+        }
+
+        let progress = LevelSearch::resumed(num_slots, saved.elapsed);
+        for (index, slot) in saved.slots.iter().enumerate() {
+            if let Some(slot) = slot {
+                let value = decode(&slot.candidate).map_err(|e| {
+                    Error::InvalidInput(format!("Invalid checkpoint slot in {}: {e}", self.stream))
+                })?;
+                progress.restore(index, slot.nonce, value, slot.found_after_secs);
+            }
+        }
+        if !saved.complete {
+            info!("Resuming {} from nonce {}", self.stream, saved.scanned);
+        }
+        Ok((progress, (!saved.complete).then_some(saved.scanned)))
+    }
+
+    /// Save the search's progress, with every candidate below `scanned` tried
+    pub fn save<T>(
+        &self,
+        progress: &LevelSearch<T>,
+        scanned: u64,
+        encode: impl Fn(&T) -> Candidate,
+    ) {
+        self.write(progress, scanned, false, encode);
+    }
+
+    /// Save the search as finished
+    pub fn complete<T>(&self, progress: &LevelSearch<T>, encode: impl Fn(&T) -> Candidate) {
+        self.write(progress, u64::MAX, true, encode);
+    }
+
+    fn write<T>(
+        &self,
+        progress: &LevelSearch<T>,
+        scanned: u64,
+        complete: bool,
+        encode: impl Fn(&T) -> Candidate,
+    ) {
+        let Some(file) = self.file else {
+            return;
+        };
+        let search = SearchCheckpoint {
+            scanned,
+            elapsed: progress.elapsed(),
+            complete,
+            slots: progress
+                .snapshot(encode)
+                .into_iter()
+                .map(|slot| {
+                    slot.map(|(candidate, nonce, found_after_secs)| SlotCheckpoint {
+                        nonce,
+                        found_after_secs,
+                        candidate,
+                    })
+                })
+                .collect(),
+        };
+        file.update(|c| c.searches.insert(self.stream.clone(), search));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(value: &u64) -> Candidate {
+        Candidate {
+            value: value.to_string(),
+            secret_key: None,
+        }
+    }
+
+    fn decode(candidate: &Candidate) -> Result<u64, String> {
+        candidate.value.parse().map_err(|e| format!("{e}"))
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let _ = fs::remove_file(path);
+        let file = CheckpointFile::create(path, 7, 3, false).unwrap();
+        file.bind_storage_target(&[1; 32]).unwrap();
+        let progress = LevelSearch::new(2);
+        progress.fill(0, 4, 40u64);
+        SearchRecord::new(Some(&file), "storage").save(&progress, 12, candidate);
+        SearchRecord::new(Some(&file), "siblings-0").complete(&progress, candidate);
+
+        assert!(CheckpointFile::resume(path, 4).is_err());
+        let file = CheckpointFile::resume(path, 3).unwrap();
+        assert_eq!(file.seed(), 7);
+        assert!(file.bind_storage_target(&[1; 32]).is_ok());
+        assert!(file.bind_storage_target(&[2; 32]).is_err());
+
+        let (resumed, next) = SearchRecord::new(Some(&file), "storage")
+            .resume(2, decode)
+            .unwrap();
+        assert_eq!(next, Some(12));
+        assert_eq!(
+            resumed.snapshot(|v| *v)[0].map(|(v, nonce, _)| (v, nonce)),
+            Some((40, 4))
+        );
+        assert!(
+            SearchRecord::new(Some(&file), "storage")
+                .resume(3, decode)
+                .is_err()
+        );
+        let (_, next) = SearchRecord::new(Some(&file), "siblings-0")
+            .resume(2, decode)
+            .unwrap();
+        assert_eq!(next, None);
+        let (_, next) = SearchRecord::new(Some(&file), "siblings-1")
+            .resume(2, decode)
+            .unwrap();
+        assert_eq!(next, Some(0));
+
+        // A new run keeps the progress unless told to start over
+        assert!(CheckpointFile::create(path, 8, 3, false).is_err());
+        assert_eq!(CheckpointFile::resume(path, 3).unwrap().seed(), 7);
+        CheckpointFile::create(path, 8, 3, true).unwrap();
+        assert_eq!(CheckpointFile::resume(path, 3).unwrap().seed(), 8);
+
+        fs::remove_file(path).unwrap();
+    }
+}
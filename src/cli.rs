@@ -0,0 +1,928 @@
+//! # CLI Module
+//!
+//! Command-line interface for the miner. Each mining mode is a subcommand with its own
+//! argument struct, so flags that only make sense for one mode (e.g. `--deployer`) are
+//! rejected by the others instead of being silently ignored.
+//!
+//! ## Subcommands
+//! - `storage`: Mines a deep branch in an ERC20 contract's storage trie
+//! - `create2`: Mines CREATE2 contracts plus auxiliary accounts that deepen the account trie
+//! - `targets`: Mines auxiliary accounts that deepen the paths to existing accounts
+//! - `plan`: Estimates the time of a storage and CREATE2 run from a short hashrate calibration
+//! - `proof`: Generates `eth_getProof`-style proofs for a CREATE2 result and sums witness sizes
+//! - `decrypt-keys`: Recovers the plain auxiliary keys of a CREATE2 result
+//! - `verify`: Recomputes every derived value of mined result files and reports mismatches
+//! - `genesis`: Writes a devnet genesis `alloc` with a CREATE2 result's accounts in place
+//! - `state-test`: Writes ethereum/tests state test fillers attacking mined contracts
+//! - `deploy-txs`: Signs the transactions deploying a CREATE2 result on a live chain
+
+use clap::{Args, Parser, Subcommand};
+use std::time::Duration;
+
+use worst_case_miner::account_miner::{DEFAULT_SKIP_NIBBLES, MAX_SALT_PREFIX, SaltScheme};
+use worst_case_miner::evm::{Codegen, Runtime};
+use worst_case_miner::key_derivation::KeyScheme;
+use worst_case_miner::mpt::parse_account_key;
+use worst_case_miner::search::{Budget, MAX_DEPTH};
+use worst_case_miner::storage_layout::{KeyType, StorageLayout, parse_address, parse_word};
+use worst_case_miner::storage_miner::{KeyMode, SlotWrite};
+use worst_case_miner::transactions::TxFormat;
+
+/// A mining program to create deep branches in ERC20 contract storage and account trie
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Mine storage slots that form a deep branch in a contract's storage trie
+    Storage(StorageArgs),
+    /// Mine CREATE2 contract addresses with auxiliary accounts that deepen the account trie
+    Create2(Create2Args),
+    /// Mine auxiliary accounts that deepen the account-trie paths to existing accounts
+    Targets(TargetsArgs),
+    /// Estimate the expected and 99th-percentile time of a storage branch and a CREATE2 run
+    /// from a short calibration of the local hashrate, without mining
+    Plan(PlanArgs),
+    /// Generate `eth_getProof`-style proofs for a CREATE2 result and report witness sizes
+    Proof(ProofArgs),
+    /// Decrypt the auxiliary account keys of a CREATE2 result mined with `--aux-keys`
+    DecryptKeys(DecryptKeysArgs),
+    /// Re-check CREATE2, target and storage result files and init code; exits with 1 on
+    /// mismatches, 7 if a file cannot be verified at all
+    Verify(VerifyArgs),
+    /// Write a genesis `alloc` with the contracts and auxiliaries of a CREATE2 result in place
+    Genesis(GenesisArgs),
+    /// Write ethereum/tests state test fillers calling `attack(uint256)` on mined contracts
+    StateTest(StateTestArgs),
+    /// Sign EIP-1559 transactions deploying the contracts of a CREATE2 result and funding
+    /// their auxiliary accounts
+    DeployTxs(DeployTxsArgs),
+}
+
+/// Arguments for the `storage` subcommand
+#[derive(Args, Debug)]
+pub struct StorageArgs {
+    /// Target depth for the storage branch
+    #[arg(short, long, value_parser = parse_depth)]
+    pub depth: usize,
+
+    /// Number of threads to use for mining (default: number of CPU cores)
+    #[arg(short, long, default_value_t = num_cpus::get(), value_parser = parse_threads)]
+    pub threads: usize,
+
+    /// Use CUDA acceleration if available
+    #[arg(long)]
+    pub cuda: bool,
+
+    /// Key whose prefix is mined: the secure trie key `keccak(slot)` or the raw slot (legacy)
+    #[arg(long, value_enum, default_value_t = KeyMode::TrieKey)]
+    pub key_mode: KeyMode,
+
+    /// How the generated contract writes the mined slots: `sstore` to the precomputed
+    /// storage key, or assignment through the `balanceOf` mapping
+    #[arg(long, value_enum, default_value_t = SlotWrite::Key)]
+    pub slot_write: SlotWrite,
+
+    /// Also mine siblings for every level, one for each nibble the branch leaves free, so each
+    /// branch node on the path has all 16 children (CPU only; the deepest level's siblings
+    /// dominate the time)
+    #[arg(long)]
+    pub full_width: bool,
+
+    /// Seed of the candidate keys; a seed always mines the same branch, whatever the thread count
+    /// (except with CUDA). A random seed is picked and recorded when omitted
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+
+    #[command(flatten)]
+    pub codegen: CodegenArgs,
+
+    /// JSON object of existing `{"slot": "value"}` storage to build the reported storage
+    /// trie with, next to the mined slots
+    #[arg(long)]
+    pub existing_storage: Option<String>,
+
+    /// Output file for the mined storage branch JSON
+    #[arg(short, long, default_value = "storage_branch.json")]
+    pub output: String,
+
+    /// File recording progress after every mined level, so an interrupted run can be resumed
+    #[arg(long, default_value = "storage_checkpoint.json")]
+    pub checkpoint: String,
+
+    /// Replace an existing checkpoint file, discarding the progress it holds
+    #[arg(long)]
+    pub overwrite_checkpoint: bool,
+
+    /// Continue an interrupted run from its checkpoint file, which keeps recording progress.
+    /// The seed comes from the checkpoint, and the depth and target must match it
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["seed", "checkpoint", "overwrite_checkpoint"]
+    )]
+    pub resume: Option<String>,
+
+    #[command(flatten)]
+    pub budget: BudgetArgs,
+
+    #[command(flatten)]
+    pub progress: ProgressArgs,
+}
+
+impl StorageArgs {
+    /// Check argument combinations clap cannot express
+    pub fn validate(&self) -> Result<(), String> {
+        validate_slot_write(self.slot_write, &self.layout, &self.codegen)
+    }
+}
+
+/// Arguments for the `create2` subcommand
+#[derive(Args, Debug)]
+pub struct Create2Args {
+    /// Target depth for the account branch (and the storage branch of an auto-generated contract)
+    #[arg(short, long, value_parser = parse_depth)]
+    pub depth: usize,
+
+    /// Number of threads to use for mining (default: number of CPU cores)
+    #[arg(short, long, default_value_t = num_cpus::get(), value_parser = parse_threads)]
+    pub threads: usize,
+
+    /// Deployer address for CREATE2 (hex string, default: 0x0000...)
+    #[arg(
+        long,
+        value_parser = parse_address,
+        default_value = "0x0000000000000000000000000000000000000000"
+    )]
+    pub deployer: [u8; 20],
+
+    /// Number of contracts to deploy via CREATE2
+    #[arg(long, value_parser = parse_num_contracts)]
+    pub num_contracts: usize,
+
+    /// Leading bytes of every salt (hex, at most 24 bytes), e.g. the sender for factories that
+    /// only accept sender-bound salts; a counter fills the last 8 bytes
+    #[arg(long, value_parser = parse_salt_prefix)]
+    pub salt_prefix: Option<SaltPrefix>,
+
+    /// Mine each salt so the contract's account-trie key shares this many leading nibbles with
+    /// `--cluster-target` (or with the first contract), clustering the contracts in the trie
+    #[arg(long, value_parser = parse_cluster_nibbles)]
+    pub cluster_nibbles: Option<usize>,
+
+    /// Address (or 32-byte account-trie key) the contracts cluster around, e.g. an existing
+    /// contract
+    #[arg(long, requires = "cluster_nibbles", value_parser = parse_account_key)]
+    pub cluster_target: Option<[u8; 32]>,
+
+    /// Path to contract init code for CREATE2 hash calculation (.sol, .hex/.bin or raw bytes).
+    /// When omitted, a contract with a mined storage branch of `--depth` is generated
+    #[arg(long, conflicts_with_all = ["LayoutArgs", "CodegenArgs"])]
+    pub init_code: Option<String>,
+
+    /// Storage key mined for an auto-generated contract (see `storage --key-mode`)
+    #[arg(long, value_enum, default_value_t = KeyMode::TrieKey, conflicts_with = "init_code")]
+    pub key_mode: KeyMode,
+
+    /// How an auto-generated contract writes the mined slots (see `storage --slot-write`)
+    #[arg(long, value_enum, default_value_t = SlotWrite::Key, conflicts_with = "init_code")]
+    pub slot_write: SlotWrite,
+
+    /// Give an auto-generated contract a full-width storage branch (see `storage --full-width`)
+    #[arg(long, conflicts_with = "init_code")]
+    pub full_width: bool,
+
+    /// Seed of the candidate keys and addresses (see `storage --seed`); also recorded
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+
+    #[command(flatten)]
+    pub codegen: CodegenArgs,
+
+    /// JSON array of existing addresses (or 32-byte account-trie keys) to build the reported
+    /// account trie with, next to the mined accounts
+    #[arg(long)]
+    pub existing_accounts: Option<String>,
+
+    /// Mine secret keys for the auxiliary accounts so they are spendable EOAs, and write the
+    /// keys next to the addresses
+    #[arg(long)]
+    pub aux_keys: bool,
+
+    /// File holding a password to encrypt the mined keys with (keys are written in plain
+    /// without one)
+    #[arg(long, requires = "aux_keys")]
+    pub key_password_file: Option<String>,
+
+    /// Output file for CREATE2 accounts JSON
+    #[arg(long, default_value = "create2_accounts.json")]
+    pub accounts_output: String,
+
+    /// File recording progress after every mined level and contract, so an interrupted run
+    /// can be resumed
+    #[arg(long, default_value = "create2_checkpoint.json")]
+    pub checkpoint: String,
+
+    /// Replace an existing checkpoint file, discarding the progress it holds
+    #[arg(long)]
+    pub overwrite_checkpoint: bool,
+
+    /// Continue an interrupted run from its checkpoint file, which keeps recording progress.
+    /// The seed comes from the checkpoint, and the depth, deployer, init code hash and storage
+    /// target must match it
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["seed", "checkpoint", "overwrite_checkpoint"]
+    )]
+    pub resume: Option<String>,
+
+    #[command(flatten)]
+    pub budget: BudgetArgs,
+
+    #[command(flatten)]
+    pub progress: ProgressArgs,
+}
+
+impl Create2Args {
+    /// Check argument combinations clap cannot express
+    pub fn validate(&self) -> Result<(), String> {
+        validate_slot_write(self.slot_write, &self.layout, &self.codegen)
+    }
+
+    /// The salt scheme selected by the salt flags
+    pub fn salt_scheme(&self) -> SaltScheme {
+        SaltScheme {
+            prefix: self.salt_prefix.clone().unwrap_or_default().0,
+            cluster_nibbles: self.cluster_nibbles.unwrap_or(0),
+            cluster_target: self.cluster_target,
+        }
+    }
+}
+
+/// Arguments for the `targets` subcommand
+#[derive(Args, Debug)]
+pub struct TargetsArgs {
+    /// Nibbles the deepest auxiliary account shares with each target's account-trie key
+    #[arg(short, long, value_parser = parse_depth)]
+    pub depth: usize,
+
+    /// Number of threads to use for mining (default: number of CPU cores)
+    #[arg(short, long, default_value_t = num_cpus::get(), value_parser = parse_threads)]
+    pub threads: usize,
+
+    /// Address (or 32-byte account-trie key) of an existing account to mine around; repeatable
+    #[arg(long = "target", value_parser = parse_account_key_arg)]
+    pub targets: Vec<(String, [u8; 32])>,
+
+    /// File of targets: a JSON array, or one address or account-trie key per line (blank
+    /// lines and lines starting with `#` are skipped), e.g. keys exported from a node
+    #[arg(long, required_unless_present = "targets")]
+    pub targets_file: Option<String>,
+
+    /// Leading nibbles the existing state already shares with each target. Mainnet's ~300M
+    /// accounts put a key's nearest neighbours 7-8 nibbles deep, so only deeper levels are mined
+    #[arg(long, default_value_t = DEFAULT_SKIP_NIBBLES)]
+    pub skip_nibbles: usize,
+
+    /// Seed of the candidate addresses (see `storage --seed`); also recorded
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Output file for the targets and their auxiliary accounts
+    #[arg(long, default_value = "target_accounts.json")]
+    pub output: String,
+
+    /// File recording progress after every mined level and target, so an interrupted run can
+    /// be resumed
+    #[arg(long, default_value = "targets_checkpoint.json")]
+    pub checkpoint: String,
+
+    /// Replace an existing checkpoint file, discarding the progress it holds
+    #[arg(long)]
+    pub overwrite_checkpoint: bool,
+
+    /// Continue an interrupted run from its checkpoint file, which keeps recording progress.
+    /// The seed comes from the checkpoint, and the depth and targets must match it
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["seed", "checkpoint", "overwrite_checkpoint"]
+    )]
+    pub resume: Option<String>,
+
+    #[command(flatten)]
+    pub budget: BudgetArgs,
+
+    #[command(flatten)]
+    pub progress: ProgressArgs,
+}
+
+impl TargetsArgs {
+    /// Check argument combinations clap cannot express
+    pub fn validate(&self) -> Result<(), String> {
+        if self.skip_nibbles >= self.depth {
+            return Err(format!(
+                "--skip-nibbles ({}) must be below --depth ({})",
+                self.skip_nibbles, self.depth
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes of a `--salt-prefix`
+#[derive(Clone, Debug, Default)]
+pub struct SaltPrefix(pub Vec<u8>);
+
+/// Arguments for the `plan` subcommand
+#[derive(Args, Debug)]
+pub struct PlanArgs {
+    /// Depth of the storage branch and of each contract's auxiliary accounts
+    #[arg(short, long, value_parser = parse_depth)]
+    pub depth: usize,
+
+    /// Number of threads the run will use; the calibration runs on as many
+    #[arg(short, long, default_value_t = num_cpus::get(), value_parser = parse_threads)]
+    pub threads: usize,
+
+    /// Also plan a CREATE2 run with this many contracts (see `create2 --num-contracts`)
+    #[arg(long, value_parser = parse_num_contracts)]
+    pub num_contracts: Option<usize>,
+
+    /// Plan mined salts clustering the contracts (see `create2 --cluster-nibbles`); the first
+    /// contract sets the cluster's target
+    #[arg(long, requires = "num_contracts", value_parser = parse_cluster_nibbles)]
+    pub cluster_nibbles: Option<usize>,
+
+    /// Plan auxiliaries mined as secret keys (see `create2 --aux-keys`), which are slower
+    #[arg(long, requires = "num_contracts")]
+    pub aux_keys: bool,
+
+    /// Storage key mined (see `storage --key-mode`)
+    #[arg(long, value_enum, default_value_t = KeyMode::TrieKey)]
+    pub key_mode: KeyMode,
+
+    /// Plan a full-width storage branch (see `storage --full-width`)
+    #[arg(long)]
+    pub full_width: bool,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+
+    /// How long each search path is mined to measure its hashrate, e.g. `5s`
+    #[arg(long, value_parser = parse_duration, default_value = "3s")]
+    pub calibration: Duration,
+}
+
+/// Arguments for the `proof` subcommand
+#[derive(Args, Debug)]
+pub struct ProofArgs {
+    /// CREATE2 mining result JSON (e.g. `mined_assets/s12_acc5.json`)
+    #[arg(short, long)]
+    pub input: String,
+
+    /// JSON array of existing addresses (or 32-byte account-trie keys) to build the state with
+    #[arg(long)]
+    pub existing_accounts: Option<String>,
+
+    /// JSON object of each contract's full `{"slot": "value"}` storage, for init code whose
+    /// constructor writes more than the mined slots (and a generated ERC20's mint)
+    #[arg(long)]
+    pub storage: Option<String>,
+
+    /// Number of contracts whose full proofs are written (the summary covers all of them)
+    #[arg(long, default_value_t = 1)]
+    pub max_proofs: usize,
+
+    /// Output file for the proofs and witness summary JSON
+    #[arg(short, long, default_value = "proofs.json")]
+    pub output: String,
+}
+
+/// Arguments for the `decrypt-keys` subcommand
+#[derive(Args, Debug)]
+pub struct DecryptKeysArgs {
+    /// CREATE2 mining result JSON with `auxiliary_keys`
+    #[arg(short, long)]
+    pub input: String,
+
+    /// File holding the password the keys were encrypted with (not needed for plain keys)
+    #[arg(long)]
+    pub key_password_file: Option<String>,
+
+    /// Output file for a JSON object mapping auxiliary addresses to secret keys
+    #[arg(short, long, default_value = "auxiliary_keys.json")]
+    pub output: String,
+}
+
+/// Arguments for the `verify` subcommand
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// CREATE2, target or storage result JSON files (e.g. `mined_assets/*.json`), and init
+    /// code `.bin`/`.hex` files, checked against the CREATE2 results passed with them
+    #[arg(required = true)]
+    pub inputs: Vec<String>,
+
+    /// Init code the results were mined for (.sol, .hex/.bin or raw bytes), checked against
+    /// the init code hash and the mined storage slots
+    #[arg(long)]
+    pub init_code: Option<String>,
+
+    /// File holding the password of encrypted auxiliary keys, so they are checked too
+    #[arg(long)]
+    pub key_password_file: Option<String>,
+}
+
+/// Arguments for the `genesis` subcommand
+#[derive(Args, Debug)]
+pub struct GenesisArgs {
+    /// CREATE2 mining result JSON (e.g. `mined_assets/s12_acc5.json`)
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Balance of each auxiliary account in wei (decimal or 0x-prefixed hex)
+    #[arg(long, default_value = "1000000000000000000", value_parser = parse_wei)]
+    pub balance: u128,
+
+    /// Genesis file to add the accounts to, keeping its config and other accounts (without
+    /// it, the output holds only the `alloc` section)
+    #[arg(long)]
+    pub genesis: Option<String>,
+
+    /// JSON object of each contract's full `{"slot": "value"}` storage (see `proof --storage`)
+    #[arg(long)]
+    pub storage: Option<String>,
+
+    /// Output file for the genesis JSON
+    #[arg(short, long, default_value = "genesis.json")]
+    pub output: String,
+}
+
+/// Arguments for the `state-test` subcommand
+#[derive(Args, Debug)]
+pub struct StateTestArgs {
+    /// CREATE2 mining result JSON (e.g. `mined_assets/s12_acc5.json`)
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Number of contracts to attack, one test each
+    #[arg(long, default_value_t = 1, value_parser = parse_num_contracts)]
+    pub num_tests: usize,
+
+    /// Value `attack(uint256)` writes to the deepest slot (the mined slots hold 1)
+    #[arg(long, default_value_t = 2)]
+    pub value: u64,
+
+    /// First fork the expectations apply to
+    #[arg(long, default_value = "Cancun")]
+    pub fork: String,
+
+    /// Output file for the fillers
+    #[arg(short, long, default_value = "worstCaseFiller.json")]
+    pub output: String,
+}
+
+/// Arguments for the `deploy-txs` subcommand
+#[derive(Args, Debug)]
+pub struct DeployTxsArgs {
+    /// CREATE2 mining result JSON (e.g. `mined_assets/s12_acc5.json`)
+    #[arg(short, long)]
+    pub input: String,
+
+    /// File holding the hex secret key of the sender
+    #[arg(long)]
+    pub sender_key_file: String,
+
+    /// Chain id the transactions are signed for
+    #[arg(long)]
+    pub chain_id: u64,
+
+    /// Nonce of the first transaction; the rest follow consecutively
+    #[arg(long, default_value_t = 0)]
+    pub nonce: u64,
+
+    /// Max fee per gas in wei (decimal or 0x-prefixed hex)
+    #[arg(long, default_value = "30000000000", value_parser = parse_wei)]
+    pub max_fee_per_gas: u128,
+
+    /// Max priority fee per gas in wei (decimal or 0x-prefixed hex)
+    #[arg(long, default_value = "1000000000", value_parser = parse_wei)]
+    pub max_priority_fee_per_gas: u128,
+
+    /// Gas limit of each deployment (default: an upper estimate from the code size and the
+    /// number of storage writes). Required when the result's storage slots are unknown
+    #[arg(long)]
+    pub deploy_gas_limit: Option<u64>,
+
+    /// Wei sent to each auxiliary account (decimal or 0x-prefixed hex)
+    #[arg(long, default_value = "1", value_parser = parse_wei)]
+    pub fund_value: u128,
+
+    /// Output format: raw hex lines or JSON-RPC `eth_sendRawTransaction` requests
+    #[arg(long, value_enum, default_value_t = TxFormat::Raw)]
+    pub format: TxFormat,
+
+    /// Output file for the signed transactions
+    #[arg(short, long, default_value = "transactions.txt")]
+    pub output: String,
+}
+
+impl DeployTxsArgs {
+    /// Check argument combinations clap cannot express
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return Err("--max-priority-fee-per-gas cannot exceed --max-fee-per-gas".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How the generated contract is turned into bytecode
+#[derive(Args, Debug)]
+pub struct CodegenArgs {
+    /// Compile the Solidity template with `solc`, or emit the bytecode natively
+    /// (deterministic, no compiler needed)
+    #[arg(long, value_enum, default_value_t = Codegen::Solc)]
+    pub codegen: Codegen,
+
+    /// Runtime code of a natively emitted contract
+    #[arg(long, value_enum, default_value_t = Runtime::Erc20)]
+    pub runtime: Runtime,
+}
+
+/// Limits on the attempts and time of a mining run. Once one runs out, the run writes what it
+/// mined so far and exits with code 5 (130 on Ctrl-C)
+#[derive(Args, Debug)]
+pub struct BudgetArgs {
+    /// Candidates each search may try; the levels of a branch (or of one account's
+    /// auxiliaries) are searched together, so a level not found within them is given up.
+    /// Reproducible: a seed always stops at the same levels
+    #[arg(long, value_parser = parse_attempts)]
+    pub level_attempts: Option<u64>,
+
+    /// Time each search may take, e.g. `90s`, `30m` or `2h` (plain numbers are seconds)
+    #[arg(long, value_parser = parse_duration)]
+    pub level_timeout: Option<Duration>,
+
+    /// Candidates the whole run may try
+    #[arg(long, value_parser = parse_attempts)]
+    pub max_attempts: Option<u64>,
+
+    /// Time the whole run may take, e.g. `90s`, `30m` or `2h` (plain numbers are seconds)
+    #[arg(long, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+}
+
+impl BudgetArgs {
+    /// The budget of a run starting now
+    pub fn to_budget(&self) -> Budget {
+        let mut budget = Budget::new();
+        if let Some(attempts) = self.level_attempts {
+            budget = budget.level_attempts(attempts);
+        }
+        if let Some(timeout) = self.level_timeout {
+            budget = budget.level_timeout(timeout);
+        }
+        if let Some(attempts) = self.max_attempts {
+            budget = budget.max_attempts(attempts);
+        }
+        if let Some(timeout) = self.timeout {
+            budget = budget.timeout(timeout);
+        }
+        budget
+    }
+}
+
+/// Progress reports of a mining run: hashrate, the work expected of the open levels, and
+/// ETAs for the next level, the running search and the whole run
+#[derive(Args, Debug)]
+pub struct ProgressArgs {
+    /// Time between progress reports, e.g. `30s` or `5m`
+    #[arg(long, value_parser = parse_duration, default_value = "10s")]
+    pub progress_interval: Duration,
+
+    /// Append every progress report to this file as one line of JSON (`-` for stdout)
+    #[arg(long)]
+    pub status_file: Option<String>,
+}
+
+/// Storage layout of the mapping whose keys are mined
+#[derive(Args, Debug)]
+pub struct LayoutArgs {
+    /// Declared slot of the (outermost) mapping: decimal or 0x-prefixed hex
+    #[arg(long, value_parser = parse_word, default_value = "0", conflicts_with = "erc7201")]
+    pub mapping_slot: [u8; 32],
+
+    /// ERC-7201 namespace of the struct holding the mapping (e.g. "openzeppelin.storage.ERC20")
+    #[arg(long)]
+    pub erc7201: Option<String>,
+
+    /// Slot offset of the mapping within the ERC-7201 namespaced struct
+    #[arg(long, default_value_t = 0, requires = "erc7201")]
+    pub erc7201_offset: u64,
+
+    /// Fixed key of an enclosing mapping (repeatable, outermost first), e.g. the owner
+    /// in `allowance[owner][spender]`; the mined key indexes the innermost mapping
+    #[arg(long = "outer-key", value_parser = parse_word)]
+    pub outer_keys: Vec<[u8; 32]>,
+
+    /// Solidity type of the mined mapping key
+    #[arg(long, value_enum, default_value_t = KeyType::Address)]
+    pub key_type: KeyType,
+
+    /// How mapping keys are hashed with their slot: Solidity `keccak(key . slot)`,
+    /// Vyper `keccak(slot . key)` or `keccak(abi.encodePacked(key, slot))`
+    #[arg(long, value_enum, default_value_t = KeyScheme::Solidity)]
+    pub key_scheme: KeyScheme,
+}
+
+impl LayoutArgs {
+    pub fn to_layout(&self) -> StorageLayout {
+        let mut layout = match &self.erc7201 {
+            Some(namespace) => StorageLayout::erc7201(namespace, self.erc7201_offset),
+            None => StorageLayout {
+                root_slot: self.mapping_slot,
+                ..StorageLayout::default()
+            },
+        };
+        layout.outer_keys = self.outer_keys.clone();
+        layout.key_type = self.key_type;
+        layout
+    }
+}
+
+/// The mapping-write template hardcodes a Solidity `balanceOf` at slot 0 with address keys
+fn validate_slot_write(
+    slot_write: SlotWrite,
+    layout: &LayoutArgs,
+    codegen: &CodegenArgs,
+) -> Result<(), String> {
+    if slot_write == SlotWrite::Mapping
+        && (layout.to_layout() != StorageLayout::default()
+            || layout.key_scheme != KeyScheme::Solidity)
+    {
+        return Err(
+            "--slot-write mapping only supports the default layout (Solidity address keys at \
+             slot 0); use --slot-write key for custom layouts"
+                .to_string(),
+        );
+    }
+    // Native code always stores to the precomputed slots
+    if slot_write == SlotWrite::Mapping && codegen.codegen == Codegen::Native {
+        return Err("--slot-write mapping requires --codegen solc".to_string());
+    }
+    Ok(())
+}
+
+fn parse_depth(s: &str) -> Result<usize, String> {
+    let depth: usize = s.parse().map_err(|e| format!("Invalid depth: {e}"))?;
+    if depth == 0 || depth > MAX_DEPTH {
+        return Err(format!(
+            "Depth must be between 1 and {MAX_DEPTH}, got {depth}"
+        ));
+    }
+    Ok(depth)
+}
+
+fn parse_threads(s: &str) -> Result<usize, String> {
+    let threads: usize = s
+        .parse()
+        .map_err(|e| format!("Invalid thread count: {e}"))?;
+    if threads == 0 {
+        return Err("Thread count must be at least 1".to_string());
+    }
+    Ok(threads)
+}
+
+fn parse_attempts(s: &str) -> Result<u64, String> {
+    let attempts: u64 = s
+        .replace('_', "")
+        .parse()
+        .map_err(|e| format!("Invalid attempt count: {e}"))?;
+    if attempts == 0 {
+        return Err("Attempt count must be at least 1".to_string());
+    }
+    Ok(attempts)
+}
+
+/// A duration as a number with an optional unit: `s` (the default), `m`, `h` or `d`
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let scale = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        _ => return Err(format!("Unknown time unit {unit:?}; use s, m, h or d")),
+    };
+    let value: f64 = number
+        .parse()
+        .map_err(|e| format!("Invalid duration {s:?}: {e}"))?;
+    if !(value > 0.0 && value.is_finite()) {
+        return Err(format!("Duration must be positive, got {s:?}"));
+    }
+    Ok(Duration::from_secs_f64(value * scale))
+}
+
+fn parse_salt_prefix(s: &str) -> Result<SaltPrefix, String> {
+    let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))
+        .map_err(|e| format!("Invalid salt prefix hex: {e}"))?;
+    if bytes.len() > MAX_SALT_PREFIX {
+        return Err(format!(
+            "Salt prefix must be at most {MAX_SALT_PREFIX} bytes, got {}",
+            bytes.len()
+        ));
+    }
+    Ok(SaltPrefix(bytes))
+}
+
+fn parse_cluster_nibbles(s: &str) -> Result<usize, String> {
+    let nibbles: usize = s
+        .parse()
+        .map_err(|e| format!("Invalid nibble count: {e}"))?;
+    if nibbles == 0 || nibbles > MAX_DEPTH {
+        return Err(format!(
+            "Cluster nibbles must be between 1 and {MAX_DEPTH}, got {nibbles}"
+        ));
+    }
+    Ok(nibbles)
+}
+
+/// An account as written on the command line, with its account-trie key
+fn parse_account_key_arg(s: &str) -> Result<(String, [u8; 32]), String> {
+    Ok((s.to_string(), parse_account_key(s)?))
+}
+
+fn parse_num_contracts(s: &str) -> Result<usize, String> {
+    let num: usize = s
+        .parse()
+        .map_err(|e| format!("Invalid number of contracts: {e}"))?;
+    if num == 0 {
+        return Err("Number of contracts must be at least 1".to_string());
+    }
+    Ok(num)
+}
+
+fn parse_wei(s: &str) -> Result<u128, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| format!("Invalid wei amount: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_storage_rejects_create2_flags() {
+        let result = Cli::try_parse_from([
+            "worst_case_miner",
+            "storage",
+            "--depth",
+            "5",
+            "--deployer",
+            "0x4e59b44847b379578588920ca78fbf26c0b4956c",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_create2_requires_num_contracts() {
+        let result = Cli::try_parse_from(["worst_case_miner", "create2", "--depth", "5"]);
+        assert!(result.is_err());
+
+        let cli = Cli::try_parse_from([
+            "worst_case_miner",
+            "create2",
+            "--depth",
+            "5",
+            "--num-contracts",
+            "10",
+            "--deployer",
+            "0x4e59b44847b379578588920ca78fbf26c0b4956c",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Create2(args) => {
+                assert_eq!(args.num_contracts, 10);
+                assert_eq!(args.deployer[0], 0x4e);
+            }
+            _ => panic!("Expected create2 subcommand"),
+        }
+    }
+
+    /// Parse `create2 --init-code` with extra arguments
+    fn parse_create2_with_init_code(extra: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(
+            [
+                "worst_case_miner",
+                "create2",
+                "--depth",
+                "3",
+                "--num-contracts",
+                "1",
+                "--init-code",
+                "contract.hex",
+            ]
+            .iter()
+            .chain(extra),
+        )
+    }
+
+    #[test]
+    fn test_create2_rejects_generator_flags_with_init_code() {
+        assert!(parse_create2_with_init_code(&[]).is_ok());
+        // These only shape the auto-generated contract
+        assert!(parse_create2_with_init_code(&["--key-mode", "slot"]).is_err());
+        assert!(parse_create2_with_init_code(&["--slot-write", "mapping"]).is_err());
+        for layout in [
+            &["--mapping-slot", "1"][..],
+            &["--erc7201", "example.main"],
+            &["--outer-key", "0x01"],
+            &["--key-type", "uint256"],
+            &["--key-scheme", "vyper"],
+        ] {
+            assert!(parse_create2_with_init_code(layout).is_err());
+        }
+        assert!(parse_create2_with_init_code(&["--codegen", "native"]).is_err());
+        assert!(parse_create2_with_init_code(&["--runtime", "minimal"]).is_err());
+    }
+
+    #[test]
+    fn test_layout_args() {
+        let cli = Cli::try_parse_from([
+            "worst_case_miner",
+            "storage",
+            "--depth",
+            "3",
+            "--mapping-slot",
+            "1",
+            "--outer-key",
+            "0x4e59b44847b379578588920ca78fbf26c0b4956c",
+            "--slot-write",
+            "mapping",
+        ])
+        .unwrap();
+        let Command::Storage(args) = cli.command else {
+            panic!("Expected storage subcommand");
+        };
+        let layout = args.layout.to_layout();
+        assert_eq!(layout.root_slot[31], 1);
+        assert_eq!(layout.outer_keys.len(), 1);
+        assert_eq!(layout.outer_keys[0][12], 0x4e);
+        // Nested layouts cannot be written through the balanceOf template
+        assert!(args.validate().is_err());
+
+        let result = Cli::try_parse_from([
+            "worst_case_miner",
+            "storage",
+            "--depth",
+            "3",
+            "--mapping-slot",
+            "1",
+            "--erc7201",
+            "example.main",
+        ]);
+        assert!(result.is_err());
+
+        // Vyper slots cannot be written through a Solidity mapping either
+        let cli = Cli::try_parse_from([
+            "worst_case_miner",
+            "storage",
+            "--depth",
+            "3",
+            "--key-scheme",
+            "vyper",
+            "--slot-write",
+            "mapping",
+        ])
+        .unwrap();
+        let Command::Storage(args) = cli.command else {
+            panic!("Expected storage subcommand");
+        };
+        assert_eq!(args.layout.key_scheme, KeyScheme::Vyper);
+        assert!(args.validate().is_err());
+    }
+}
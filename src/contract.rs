@@ -0,0 +1,127 @@
+//! # Contract Module
+//!
+//! Loads the contract a CREATE2 run mines addresses for: Solidity sources are compiled with
+//! `solc` (optimized, without the metadata hash so the bytecode is reproducible), hex files
+//! and raw files are taken as init code.
+//!
+//! ## Key Functions
+//! - `load_init_code`: Loads init code from a `.sol`, `.hex`/`.bin` or raw bytecode file
+//! - `compile_solidity`: Compiles a Solidity file into init and runtime code
+
+use log::info;
+use std::process::Command;
+
+use crate::error::Error;
+
+/// Load init code from a `.sol` file (compiled with solc), a hex file or raw bytes
+pub fn load_init_code(init_code_path: &str) -> Result<CompiledContract, Error> {
+    // Check if it's a .sol file or a hex file
+    if init_code_path.ends_with(".sol") {
+        // Compile the Solidity file to get bytecode
+        info!("Compiling Solidity contract: {}", init_code_path);
+        compile_solidity(init_code_path)
+    } else if init_code_path.ends_with(".hex") || init_code_path.ends_with(".bin") {
+        // Read hex bytecode from file
+        info!("Loading bytecode from: {}", init_code_path);
+        let hex_content =
+            std::fs::read_to_string(init_code_path).map_err(|e| Error::io(init_code_path, e))?;
+        let hex_content = hex_content.trim();
+        let hex_content = hex_content.strip_prefix("0x").unwrap_or(hex_content);
+        let init_code = hex::decode(hex_content).map_err(|e| {
+            Error::InvalidInput(format!(
+                "Invalid hex in bytecode file {init_code_path}: {e}"
+            ))
+        })?;
+        // For raw bytecode, we don't have deploy_code
+        Ok(CompiledContract {
+            init_code,
+            deploy_code: Vec::new(),
+        })
+    } else {
+        // Assume it's raw bytecode
+        let init_code = std::fs::read(init_code_path).map_err(|e| Error::io(init_code_path, e))?;
+        Ok(CompiledContract {
+            init_code,
+            deploy_code: Vec::new(),
+        })
+    }
+}
+
+/// Result of compiling a Solidity contract
+pub struct CompiledContract {
+    /// Init code (constructor + runtime) - used for CREATE2 address calculation
+    pub init_code: Vec<u8>,
+    /// Deploy code (runtime only) - what ends up on chain
+    pub deploy_code: Vec<u8>,
+}
+
+/// Compile a Solidity file and return both init code and runtime code
+pub fn compile_solidity(sol_path: &str) -> Result<CompiledContract, Error> {
+    // Run solc to compile the contract with both --bin and --bin-runtime
+    let output = Command::new("solc")
+        .args([
+            "--optimize",
+            "--optimize-runs",
+            "200",
+            "--bin",
+            "--bin-runtime",
+            "--metadata-hash",
+            "none",
+            sol_path,
+        ])
+        .output()
+        .map_err(|e| {
+            Error::Compiler(format!(
+                "Failed to run solc: {}. Make sure solc is installed.",
+                e
+            ))
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Compiler(format!(
+            "Solidity compilation failed: {}",
+            stderr
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    let mut init_code: Option<Vec<u8>> = None;
+    let mut deploy_code: Option<Vec<u8>> = None;
+    let mut next_is_binary = false;
+    let mut next_is_runtime = false;
+
+    for line in lines {
+        if next_is_binary {
+            let bytecode_hex = line.trim();
+            if !bytecode_hex.is_empty() {
+                init_code = Some(hex::decode(bytecode_hex).map_err(|e| {
+                    Error::Compiler(format!("Failed to decode init code hex: {}", e))
+                })?);
+            }
+            next_is_binary = false;
+        } else if next_is_runtime {
+            let bytecode_hex = line.trim();
+            if !bytecode_hex.is_empty() {
+                deploy_code = Some(hex::decode(bytecode_hex).map_err(|e| {
+                    Error::Compiler(format!("Failed to decode runtime code hex: {}", e))
+                })?);
+            }
+            next_is_runtime = false;
+        }
+
+        if line.contains("Binary:") && !line.contains("Binary of the runtime") {
+            next_is_binary = true;
+        } else if line.contains("Binary of the runtime") {
+            next_is_runtime = true;
+        }
+    }
+
+    let missing = |what: &str| Error::Compiler(format!("Could not find {what} in solc output"));
+    Ok(CompiledContract {
+        init_code: init_code.ok_or_else(|| missing("init code"))?,
+        deploy_code: deploy_code.ok_or_else(|| missing("runtime code"))?,
+    })
+}
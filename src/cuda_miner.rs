@@ -7,6 +7,7 @@ unsafe extern "C" {
         target_prefix: *const u8,
         required_nibbles: i32,
         base_slot: u64,
+        hash_slot: bool,
         result_address: *mut u8,
         result_storage_key: *mut u8,
         found: *mut bool,
@@ -17,11 +18,14 @@ unsafe extern "C" {
     );
 }
 
+/// Mine an address whose storage slot (or `keccak(slot)` when `hash_slot` is set)
+/// shares `required_nibbles` with `target_prefix`
 #[cfg(feature = "cuda")]
 pub fn mine_with_cuda(
     target_prefix: &[u8; 32],
     required_nibbles: usize,
     base_slot: u64,
+    hash_slot: bool,
 ) -> Option<([u8; 20], [u8; 32])> {
     let mut result_address = [0u8; 20];
     let mut result_storage_key = [0u8; 32];
@@ -38,7 +42,7 @@ pub fn mine_with_cuda(
     // Each nibble adds 4 bits of difficulty (16x harder)
     // Base: 100k attempts, scale up for higher nibble counts
     let attempts_per_thread: u64 = match required_nibbles {
-        0..=3 => 1_000,       // Very easy, just for testing
+        0..=3 => 1_000, // Very easy, just for testing
         4..=5 => 10_000,
         6 => 100_000,
         7 => 1_000_000,
@@ -59,11 +63,14 @@ pub fn mine_with_cuda(
         _ => 200,
     };
 
-    let total_attempts_per_iteration = blocks as u64 * threads_per_block as u64 * attempts_per_thread;
+    let total_attempts_per_iteration =
+        blocks as u64 * threads_per_block as u64 * attempts_per_thread;
 
     info!(
         "Mining with CUDA: {} blocks, {} threads/block, {} attempts/thread ({:.2}B attempts/iteration, max {} iterations)",
-        blocks, threads_per_block, attempts_per_thread,
+        blocks,
+        threads_per_block,
+        attempts_per_thread,
         total_attempts_per_iteration as f64 / 1_000_000_000.0,
         max_iterations
     );
@@ -89,6 +96,7 @@ pub fn mine_with_cuda(
                 target_prefix.as_ptr(),
                 required_nibbles as i32,
                 base_slot,
+                hash_slot,
                 result_address.as_mut_ptr(),
                 result_storage_key.as_mut_ptr(),
                 &mut found as *mut bool,
@@ -115,6 +123,7 @@ pub fn mine_with_cuda(
     _target_prefix: &[u8; 32],
     _required_nibbles: usize,
     _base_slot: u64,
+    _hash_slot: bool,
 ) -> Option<([u8; 20], [u8; 32])> {
     panic!("CUDA support not enabled. Build with --features cuda");
 }
@@ -134,15 +143,13 @@ pub fn cuda_available() -> bool {
 
 #[cfg(all(test, feature = "cuda"))]
 mod tests {
+    use crate::key_derivation::SolidityDerivation;
+    use crate::storage_layout::{StorageLayout, address_key};
     use crate::storage_miner::calculate_storage_slot;
 
     // Test-only FFI bindings
     unsafe extern "C" {
-        fn cuda_verify_keccak(
-            test_address: *const u8,
-            base_slot: u64,
-            result_storage_key: *mut u8,
-        );
+        fn cuda_verify_keccak(test_address: *const u8, base_slot: u64, result_storage_key: *mut u8);
 
         fn cuda_debug_prng(
             seed: u64,
@@ -164,7 +171,12 @@ mod tests {
         let mut address = [0u8; 20];
         let mut storage_key = [0u8; 32];
         unsafe {
-            cuda_debug_prng(seed, base_slot, address.as_mut_ptr(), storage_key.as_mut_ptr());
+            cuda_debug_prng(
+                seed,
+                base_slot,
+                address.as_mut_ptr(),
+                storage_key.as_mut_ptr(),
+            );
         }
         (address, storage_key)
     }
@@ -173,15 +185,20 @@ mod tests {
     #[test]
     fn test_cuda_keccak_matches_cpu() {
         let test_addr: [u8; 20] = [
-            0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x11, 0x22,
-            0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc,
+            0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66,
+            0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc,
         ];
 
-        let cpu_result = calculate_storage_slot(&test_addr, 0);
+        let cpu_result = calculate_storage_slot(
+            &address_key(&test_addr),
+            &StorageLayout::mapping(0),
+            &SolidityDerivation,
+        );
         let cuda_result = verify_cuda_keccak(&test_addr, 0);
 
         assert_eq!(
-            cpu_result, cuda_result,
+            cpu_result,
+            cuda_result,
             "CUDA keccak mismatch!\nCPU:  0x{}\nCUDA: 0x{}",
             hex::encode(&cpu_result),
             hex::encode(&cuda_result)
@@ -194,10 +211,15 @@ mod tests {
         // Test multiple seeds to ensure consistency
         for seed in [0u64, 1, 12345, 999999, u64::MAX - 1] {
             let (prng_addr, cuda_key) = debug_cuda_prng(seed, 0);
-            let cpu_key = calculate_storage_slot(&prng_addr, 0);
+            let cpu_key = calculate_storage_slot(
+                &address_key(&prng_addr),
+                &StorageLayout::mapping(0),
+                &SolidityDerivation,
+            );
 
             assert_eq!(
-                cuda_key, cpu_key,
+                cuda_key,
+                cpu_key,
                 "CUDA PRNG key mismatch for seed {}!\nAddress: 0x{}\nCUDA key: 0x{}\nCPU key:  0x{}",
                 seed,
                 hex::encode(&prng_addr),
@@ -213,11 +235,16 @@ mod tests {
         let test_addr: [u8; 20] = [0xaa; 20];
 
         for slot in [0u64, 1, 2, 100, u64::MAX] {
-            let cpu_result = calculate_storage_slot(&test_addr, slot);
+            let cpu_result = calculate_storage_slot(
+                &address_key(&test_addr),
+                &StorageLayout::mapping(slot),
+                &SolidityDerivation,
+            );
             let cuda_result = verify_cuda_keccak(&test_addr, slot);
 
             assert_eq!(
-                cpu_result, cuda_result,
+                cpu_result,
+                cuda_result,
                 "CUDA keccak mismatch for slot {}!\nCPU:  0x{}\nCUDA: 0x{}",
                 slot,
                 hex::encode(&cpu_result),
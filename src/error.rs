@@ -0,0 +1,81 @@
+//! # Error Module
+//!
+//! The error type returned by the library's fallible entry points, so embedders can tell
+//! kinds of failure apart instead of matching on messages, and the CLI can exit with a
+//! distinct code for each.
+//!
+//! ## Key Types
+//! - `Error`: Kind of failure, with a message for the user
+
+use std::any::Any;
+use std::fmt;
+
+/// A failure of the library, by kind
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A configuration, file content or checkpoint the miners cannot work with
+    InvalidInput(String),
+    /// `solc` failed, or a contract could not be generated
+    Compiler(String),
+    /// A file could not be read or written
+    Io(String),
+    /// A search ended before filling every level it was asked for
+    BudgetExhausted(String),
+    /// A mining thread panicked
+    WorkerPanic(String),
+    /// A search was stopped by Ctrl-C
+    Interrupted(String),
+    /// `verify` recomputed a value that differs from the recorded one
+    VerifyMismatch(String),
+    /// `verify` could not read, parse or check a file
+    Unverifiable(String),
+}
+
+impl Error {
+    /// An I/O failure on `path`
+    pub fn io(path: &str, error: impl fmt::Display) -> Self {
+        Error::Io(format!("{path}: {error}"))
+    }
+
+    /// A worker panic, with the message of its payload when it has one
+    pub fn worker_panic(payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Error::WorkerPanic(message)
+    }
+
+    /// Process exit code of the CLI for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::VerifyMismatch(_) => 1,
+            Error::InvalidInput(_) => 2,
+            Error::Io(_) => 3,
+            Error::Compiler(_) => 4,
+            Error::BudgetExhausted(_) => 5,
+            Error::WorkerPanic(_) => 6,
+            Error::Unverifiable(_) => 7,
+            // The shell's code for a process ended by SIGINT
+            Error::Interrupted(_) => 130,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
+            Error::Compiler(msg) => write!(f, "Compilation failed: {msg}"),
+            Error::Io(msg) => write!(f, "I/O error: {msg}"),
+            Error::BudgetExhausted(msg) => write!(f, "Search budget exhausted: {msg}"),
+            Error::WorkerPanic(msg) => write!(f, "Mining thread panicked: {msg}"),
+            Error::Interrupted(msg) => write!(f, "Interrupted: {msg}"),
+            Error::VerifyMismatch(msg) => write!(f, "Verification failed: {msg}"),
+            Error::Unverifiable(msg) => write!(f, "Cannot verify: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,511 @@
+//! # EVM Bytecode Module
+//!
+//! Emits the bytecode of the worst-case contract directly, without `solc`. The useful part of
+//! the generated contract is tiny: a constructor doing `PUSH1 1 PUSH32 <slot> SSTORE` for every
+//! mined slot, followed by a copy of the runtime code. Emitting it here makes the init code (and
+//! therefore the CREATE2 init-code hash) deterministic and independent of the compiler version.
+//!
+//! ## Key Functions
+//! - `Assembler`: Minimal assembler with forward-referenced jump labels
+//! - `seeding_contract`: Builds the init code and runtime code of a storage-seeding contract
+//! - `selector`: Computes a 4-byte function selector from its signature
+//! - `mint_writes`: Storage the generated ERC20 constructors write besides the mined slots
+//! - `legacy_slot_writes`: Mined slots of init code compiled from the older Solidity templates
+
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::account_miner::StorageEntry;
+use crate::storage_layout::{address_key, slot_from_u64};
+use crate::storage_miner::ERC20_BALANCES_SLOT;
+
+/// Opcodes used by the emitted contracts
+pub mod op {
+    pub const STOP: u8 = 0x00;
+    pub const ADD: u8 = 0x01;
+    pub const SUB: u8 = 0x03;
+    pub const LT: u8 = 0x10;
+    pub const EQ: u8 = 0x14;
+    pub const AND: u8 = 0x16;
+    pub const SHR: u8 = 0x1c;
+    pub const KECCAK256: u8 = 0x20;
+    pub const CALLER: u8 = 0x33;
+    pub const CALLDATALOAD: u8 = 0x35;
+    pub const CALLDATASIZE: u8 = 0x36;
+    pub const CODECOPY: u8 = 0x39;
+    pub const POP: u8 = 0x50;
+    pub const MSTORE: u8 = 0x52;
+    pub const SLOAD: u8 = 0x54;
+    pub const SSTORE: u8 = 0x55;
+    pub const JUMPI: u8 = 0x57;
+    pub const JUMPDEST: u8 = 0x5b;
+    pub const PUSH1: u8 = 0x60;
+    pub const PUSH2: u8 = 0x61;
+    pub const PUSH32: u8 = 0x7f;
+    pub const PUSH20: u8 = 0x73;
+    pub const DUP1: u8 = 0x80;
+    pub const DUP2: u8 = 0x81;
+    pub const DUP3: u8 = 0x82;
+    pub const SWAP1: u8 = 0x90;
+    pub const SWAP2: u8 = 0x91;
+    pub const RETURN: u8 = 0xf3;
+    pub const REVERT: u8 = 0xfd;
+}
+
+/// Storage slot of `totalSupply` in the ERC20-like runtime (same as the Solidity template)
+pub const TOTAL_SUPPLY_SLOT: u64 = 2;
+
+/// Supply minted to the deployer by the ERC20-like constructor: 1 billion tokens, 18 decimals
+const TOTAL_SUPPLY: u128 = 1_000_000_000 * 10u128.pow(18);
+
+/// How the generated contract's bytecode is produced
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Codegen {
+    /// Render the Solidity template and compile it with `solc` (must be on `PATH`)
+    #[default]
+    Solc,
+    /// Emit the bytecode directly; deterministic and needs no compiler
+    Native,
+}
+
+/// Runtime code of a natively emitted contract
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Runtime {
+    /// A single `STOP`: the contract only exists to hold the mined storage
+    Minimal,
+    /// `attack(uint256)` and `getDeepest()` operating on the deepest mined slot
+    Attack,
+    /// `attack`/`getDeepest` plus `balanceOf`, `totalSupply` and `transfer`, with the total
+    /// supply minted to the deployer like the Solidity template
+    #[default]
+    Erc20,
+}
+
+/// Init code and runtime code of an emitted contract
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bytecode {
+    /// Constructor followed by the runtime it returns - used for CREATE2 address calculation
+    pub init_code: Vec<u8>,
+    /// Runtime code - what ends up on chain
+    pub runtime_code: Vec<u8>,
+}
+
+/// A jump target, resolved when the assembler finishes
+#[derive(Clone, Copy, Debug)]
+pub struct Label(usize);
+
+/// Minimal EVM assembler: appends opcodes and patches label references on `finish`
+#[derive(Default)]
+pub struct Assembler {
+    code: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    /// Offsets of `PUSH2` immediates waiting for a label's position
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a single opcode
+    pub fn op(&mut self, opcode: u8) -> &mut Self {
+        self.code.push(opcode);
+        self
+    }
+
+    /// Push a big-endian value with the shortest `PUSHn`. Zero is pushed as `PUSH1 0` rather
+    /// than `PUSH0`, so the code also runs on pre-Shanghai forks
+    pub fn push(&mut self, value: &[u8]) -> &mut Self {
+        let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+        let value = if start == value.len() {
+            &[0u8][..]
+        } else {
+            &value[start..]
+        };
+        assert!(value.len() <= 32, "PUSH immediates are at most 32 bytes");
+        self.code.push(op::PUSH1 + value.len() as u8 - 1);
+        self.code.extend_from_slice(value);
+        self
+    }
+
+    /// Push a number with the shortest `PUSHn`
+    pub fn push_u64(&mut self, value: u64) -> &mut Self {
+        self.push(&value.to_be_bytes())
+    }
+
+    /// Push a full 32-byte word with `PUSH32`, keeping its leading zeros
+    pub fn push_word(&mut self, word: &[u8; 32]) -> &mut Self {
+        self.code.push(op::PUSH32);
+        self.code.extend_from_slice(word);
+        self
+    }
+
+    /// Create a label that can be referenced before it is bound
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Bind `label` to the current position without emitting anything (e.g. data offsets)
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        self.labels[label.0] = Some(self.code.len());
+        self
+    }
+
+    /// Bind `label` to a `JUMPDEST` at the current position
+    pub fn jumpdest(&mut self, label: Label) -> &mut Self {
+        self.bind(label).op(op::JUMPDEST)
+    }
+
+    /// Push the position of `label` as a `PUSH2`
+    pub fn push_label(&mut self, label: Label) -> &mut Self {
+        self.code.push(op::PUSH2);
+        self.fixups.push((self.code.len(), label));
+        self.code.extend_from_slice(&[0, 0]);
+        self
+    }
+
+    /// Jump to `label` if the top of the stack is nonzero
+    pub fn jumpi(&mut self, label: Label) -> &mut Self {
+        self.push_label(label).op(op::JUMPI)
+    }
+
+    /// Append raw bytes (e.g. embedded runtime code)
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.code.extend_from_slice(bytes);
+        self
+    }
+
+    /// Resolve all label references and return the code
+    pub fn finish(mut self) -> Vec<u8> {
+        for (offset, label) in self.fixups {
+            let position = self.labels[label.0].expect("Label referenced but never bound");
+            let position = u16::try_from(position).expect("Code too large for PUSH2 labels");
+            self.code[offset..offset + 2].copy_from_slice(&position.to_be_bytes());
+        }
+        self.code
+    }
+}
+
+/// 4-byte function selector: the first bytes of `keccak256(signature)`
+pub fn selector(signature: &str) -> [u8; 4] {
+    let mut hasher = Keccak::v256();
+    let mut hash = [0u8; 32];
+    hasher.update(signature.as_bytes());
+    hasher.finalize(&mut hash);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Storage the generated ERC20 constructors write besides the mined slots, when deployed by
+/// `deployer` (`msg.sender` of a CREATE2 constructor): `totalSupply` and the deployer's balance,
+/// both `TOTAL_SUPPLY`. The Solidity templates and `seeding_contract` all push `TOTAL_SUPPLY`,
+/// which is how their init code is told apart; any other init code gets no writes
+pub fn mint_writes(init_code: &[u8], deployer: &[u8; 20]) -> Vec<StorageEntry> {
+    let mut push = Assembler::new();
+    push.push(&TOTAL_SUPPLY.to_be_bytes());
+    let push = push.finish();
+    if !init_code.windows(push.len()).any(|window| window == push) {
+        return Vec::new();
+    }
+
+    let mut supply = [0u8; 32];
+    supply[16..].copy_from_slice(&TOTAL_SUPPLY.to_be_bytes());
+    let mut hasher = Keccak::v256();
+    let mut balance_slot = [0u8; 32];
+    hasher.update(&address_key(deployer));
+    hasher.update(&slot_from_u64(ERC20_BALANCES_SLOT));
+    hasher.finalize(&mut balance_slot);
+    vec![
+        (slot_from_u64(TOTAL_SUPPLY_SLOT), supply),
+        (balance_slot, supply),
+    ]
+}
+
+/// Slots the older Solidity templates (`mined_assets/depth_*.sol`) set to 1, in order: after
+/// `PUSH1 1`, solc emits `PUSH20 <address> DUP2 SWAP1 SSTORE` for each raw-address `sstore`,
+/// and `PUSH20 <address> SSTORE` for the last one. Init code without that sequence gets none
+pub fn legacy_slot_writes(init_code: &[u8]) -> Vec<[u8; 32]> {
+    let start = init_code
+        .windows(3)
+        .position(|window| window == [op::PUSH1, 1, op::PUSH20]);
+    let Some(start) = start else {
+        return Vec::new();
+    };
+
+    let mut slots = Vec::new();
+    let mut code = &init_code[start + 2..];
+    while let [op::PUSH20, rest @ ..] = code
+        && rest.len() > 20
+    {
+        let (address, rest) = rest.split_at(20);
+        let address: [u8; 20] = address.try_into().expect("20-byte slice");
+        match rest {
+            [op::DUP2, op::SWAP1, op::SSTORE, rest @ ..] => {
+                slots.push(address_key(&address));
+                code = rest;
+            }
+            [op::SSTORE, ..] => {
+                slots.push(address_key(&address));
+                break;
+            }
+            _ => break,
+        }
+    }
+    slots
+}
+
+/// Build a contract whose constructor sets every slot in `slots` to 1 and returns `runtime`.
+/// The last slot is the deepest one, targeted by `attack`/`getDeepest`
+pub fn seeding_contract(slots: &[[u8; 32]], runtime: Runtime) -> Bytecode {
+    let runtime_code = runtime_code(runtime, slots.last());
+
+    let mut asm = Assembler::new();
+    for slot in slots {
+        asm.push_u64(1).push_word(slot).op(op::SSTORE);
+    }
+
+    if runtime == Runtime::Erc20 {
+        // totalSupply = balanceOf[msg.sender] = TOTAL_SUPPLY
+        asm.push(&TOTAL_SUPPLY.to_be_bytes())
+            .op(op::DUP1)
+            .push_u64(TOTAL_SUPPLY_SLOT)
+            .op(op::SSTORE)
+            .op(op::CALLER);
+        balance_slot(&mut asm);
+        asm.op(op::SSTORE);
+    }
+
+    // codecopy(0, runtime_start, runtime_len); return(0, runtime_len)
+    let runtime_start = asm.label();
+    let runtime_len = u16::try_from(runtime_code.len()).expect("Runtime code too large");
+    asm.push(&runtime_len.to_be_bytes())
+        .op(op::DUP1)
+        .push_label(runtime_start)
+        .push_u64(0)
+        .op(op::CODECOPY)
+        .push_u64(0)
+        .op(op::RETURN)
+        .bind(runtime_start)
+        .raw(&runtime_code);
+
+    Bytecode {
+        init_code: asm.finish(),
+        runtime_code,
+    }
+}
+
+/// Emit the runtime code. `deepest` is required for every runtime but `Minimal`
+fn runtime_code(runtime: Runtime, deepest: Option<&[u8; 32]>) -> Vec<u8> {
+    let mut asm = Assembler::new();
+    if runtime == Runtime::Minimal {
+        asm.op(op::STOP);
+        return asm.finish();
+    }
+    let deepest = deepest.expect("attack/getDeepest need at least one mined slot");
+
+    let revert = asm.label();
+    let attack = asm.label();
+    let get_deepest = asm.label();
+    let balance_of = asm.label();
+    let total_supply = asm.label();
+    let transfer = asm.label();
+
+    let mut functions = vec![("attack(uint256)", attack), ("getDeepest()", get_deepest)];
+    if runtime == Runtime::Erc20 {
+        functions.extend([
+            ("balanceOf(address)", balance_of),
+            ("totalSupply()", total_supply),
+            ("transfer(address,uint256)", transfer),
+        ]);
+    }
+
+    // Dispatch on the selector, reverting on short calldata or unknown functions
+    asm.push_u64(4)
+        .op(op::CALLDATASIZE)
+        .op(op::LT)
+        .jumpi(revert);
+    asm.push_u64(0)
+        .op(op::CALLDATALOAD)
+        .push_u64(0xe0)
+        .op(op::SHR);
+    for (signature, label) in &functions {
+        asm.op(op::DUP1)
+            .push(&selector(signature))
+            .op(op::EQ)
+            .jumpi(*label);
+    }
+    asm.jumpdest(revert).push_u64(0).op(op::DUP1).op(op::REVERT);
+
+    // attack(uint256 value): sstore(deepest, value)
+    asm.jumpdest(attack)
+        .push_u64(4)
+        .op(op::CALLDATALOAD)
+        .push_word(deepest)
+        .op(op::SSTORE)
+        .op(op::STOP);
+
+    // getDeepest(): return sload(deepest)
+    asm.jumpdest(get_deepest).push_word(deepest).op(op::SLOAD);
+    return_word(&mut asm);
+
+    if runtime == Runtime::Erc20 {
+        // balanceOf(address owner): return balanceOf[owner]
+        asm.jumpdest(balance_of);
+        load_address_arg(&mut asm, 4);
+        balance_slot(&mut asm);
+        asm.op(op::SLOAD);
+        return_word(&mut asm);
+
+        // totalSupply(): return sload(TOTAL_SUPPLY_SLOT)
+        asm.jumpdest(total_supply)
+            .push_u64(TOTAL_SUPPLY_SLOT)
+            .op(op::SLOAD);
+        return_word(&mut asm);
+
+        // transfer(address to, uint256 amount)
+        asm.jumpdest(transfer).op(op::CALLER);
+        balance_slot(&mut asm);
+        // [from_slot, balance, amount]: revert if balance < amount
+        asm.op(op::DUP1)
+            .op(op::SLOAD)
+            .push_u64(0x24)
+            .op(op::CALLDATALOAD)
+            .op(op::DUP1)
+            .op(op::DUP3)
+            .op(op::LT)
+            .jumpi(revert);
+        // balanceOf[from] = balance - amount, leaving [amount]
+        asm.op(op::DUP1)
+            .op(op::SWAP2)
+            .op(op::SUB)
+            .op(op::DUP3)
+            .op(op::SSTORE)
+            .op(op::SWAP1)
+            .op(op::POP);
+        // balanceOf[to] += amount (cannot overflow: bounded by the total supply)
+        load_address_arg(&mut asm, 4);
+        balance_slot(&mut asm);
+        asm.op(op::DUP1)
+            .op(op::SLOAD)
+            .op(op::DUP3)
+            .op(op::ADD)
+            .op(op::SWAP1)
+            .op(op::SSTORE)
+            .op(op::POP)
+            .push_u64(1);
+        return_word(&mut asm);
+    }
+
+    asm.finish()
+}
+
+/// Load the address argument at calldata `offset`, masking off dirty upper bits
+fn load_address_arg(asm: &mut Assembler, offset: u64) {
+    asm.push_u64(offset)
+        .op(op::CALLDATALOAD)
+        .push(&[0xff; 20])
+        .op(op::AND);
+}
+
+/// Replace the address on top of the stack with its `balanceOf` slot: `keccak256(addr . slot)`
+fn balance_slot(asm: &mut Assembler) {
+    asm.push_u64(0)
+        .op(op::MSTORE)
+        .push_u64(ERC20_BALANCES_SLOT)
+        .push_u64(0x20)
+        .op(op::MSTORE)
+        .push_u64(0x40)
+        .push_u64(0)
+        .op(op::KECCAK256);
+}
+
+/// Return the word on top of the stack
+fn return_word(asm: &mut Assembler) {
+    asm.push_u64(0)
+        .op(op::MSTORE)
+        .push_u64(0x20)
+        .push_u64(0)
+        .op(op::RETURN);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector() {
+        assert_eq!(hex::encode(selector("balanceOf(address)")), "70a08231");
+        assert_eq!(
+            hex::encode(selector("transfer(address,uint256)")),
+            "a9059cbb"
+        );
+    }
+
+    #[test]
+    fn test_minimal_seeding_contract() {
+        let slot = [0xaa; 32];
+        let bytecode = seeding_contract(&[slot], Runtime::Minimal);
+        assert_eq!(bytecode.runtime_code, vec![op::STOP]);
+
+        let expected = format!(
+            "6001 7f{} 55 6001 80 61{:04x} 6000 39 6000 f3 00",
+            hex::encode(slot),
+            36 + 12
+        )
+        .replace(' ', "");
+        assert_eq!(hex::encode(&bytecode.init_code), expected);
+    }
+
+    #[test]
+    fn test_legacy_slot_writes() {
+        // Constructor tail of mined_assets/depth_*.bin: PUSH1 1, then one raw-address sstore
+        // per level, the last one consuming the 1
+        let code = hex::decode(
+            "6002819055 6001 73c23303a7ec42f89ca5bf674b83a9141500ac18f0 819055 \
+             73c5f82341cec8f50dcc8cad69423974ca8ada0f20 55 6105b4"
+                .replace(' ', ""),
+        )
+        .unwrap();
+        let slots = legacy_slot_writes(&code);
+        assert_eq!(slots.len(), 2);
+        assert_eq!(
+            hex::encode(slots[1]),
+            "000000000000000000000000c5f82341cec8f50dcc8cad69423974ca8ada0f20"
+        );
+        assert!(
+            legacy_slot_writes(&seeding_contract(&[[0x11; 32]], Runtime::Erc20).init_code)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_labels_resolve_to_jumpdests() {
+        let slots = [[0x11; 32], [0x22; 32]];
+        let bytecode = seeding_contract(&slots, Runtime::Erc20);
+        let runtime = &bytecode.runtime_code;
+
+        // The init code ends with the runtime it returns
+        assert!(bytecode.init_code.ends_with(runtime));
+
+        // Every PUSH2 followed by JUMPI targets a JUMPDEST
+        let mut pc = 0;
+        let mut jumps = 0;
+        while pc < runtime.len() {
+            let opcode = runtime[pc];
+            if opcode == op::PUSH2 && runtime.get(pc + 3) == Some(&op::JUMPI) {
+                let target = u16::from_be_bytes([runtime[pc + 1], runtime[pc + 2]]) as usize;
+                assert_eq!(runtime[target], op::JUMPDEST);
+                jumps += 1;
+            }
+            if (op::PUSH1..=op::PUSH32).contains(&opcode) {
+                pc += (opcode - op::PUSH1 + 1) as usize;
+            }
+            pc += 1;
+        }
+        // Short-calldata check, five selectors and the transfer balance check
+        assert_eq!(jumps, 7);
+
+        // Same inputs, same bytes
+        assert_eq!(seeding_contract(&slots, Runtime::Erc20), bytecode);
+    }
+}
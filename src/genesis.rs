@@ -0,0 +1,236 @@
+//! # Genesis Module
+//!
+//! Exports a `Create2MiningResult` as the `alloc` section of a geth/reth `genesis.json`, so a
+//! devnet starts with the worst-case state at block 0 instead of deploying every contract
+//! through transactions.
+//!
+//! Each contract gets the runtime code and nonce 1 of a CREATE2 deployment, with the storage
+//! its constructor writes (the mined slots set to 1 and a generated ERC20's mint) or an
+//! explicitly given storage, as in the proofs. Each auxiliary account gets a configurable
+//! balance.
+//!
+//! ## Key Functions
+//! - `genesis_alloc`: Builds the `alloc` entries of a CREATE2 result
+//! - `genesis_json`: Puts them into an existing genesis file, or into one of its own
+//! - `write_genesis`: Saves the genesis JSON file
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::account_miner::{Create2MiningResult, StorageEntry};
+use crate::error::Error;
+
+/// One account of a genesis `alloc`, in the format geth and reth both read
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GenesisAccount {
+    pub balance: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Storage slot to value, both as 32-byte hex
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, String>,
+}
+
+/// `alloc` entries by address: every contract of `result` holding `storage` if given, otherwise
+/// what the recorded constructor writes, and every auxiliary account holding `balance` wei
+pub fn genesis_alloc(
+    result: &Create2MiningResult,
+    balance: u128,
+    storage: Option<&[StorageEntry]>,
+) -> Result<BTreeMap<String, GenesisAccount>, String> {
+    let deploy_code = result.deploy_code()?;
+    if deploy_code.is_empty() {
+        return Err("Result has no deploy_code".to_string());
+    }
+    let storage = match storage {
+        Some(storage) => storage.to_vec(),
+        None if result.contract_storage_slots()?.is_empty() => {
+            return Err(
+                "Result records no storage slots and its init code is not a known template; \
+                 pass --storage"
+                    .to_string(),
+            );
+        }
+        None => result.contract_storage()?,
+    };
+    // The last write to a slot is the one that stays, and zero slots are not stored
+    let storage: BTreeMap<[u8; 32], [u8; 32]> = storage.into_iter().collect();
+    let storage: BTreeMap<String, String> = storage
+        .iter()
+        .filter(|(_, value)| value.iter().any(|&b| b != 0))
+        .map(|(slot, value)| {
+            (
+                format!("0x{}", hex::encode(slot)),
+                format!("0x{}", hex::encode(value)),
+            )
+        })
+        .collect();
+
+    let mut alloc = BTreeMap::new();
+    for (contract_address, auxiliaries) in result.mined_accounts()? {
+        alloc.insert(
+            format!("0x{}", hex::encode(contract_address)),
+            GenesisAccount {
+                balance: "0x0".to_string(),
+                nonce: Some("0x1".to_string()),
+                code: Some(format!("0x{}", hex::encode(&deploy_code))),
+                storage: storage.clone(),
+            },
+        );
+        for auxiliary in auxiliaries {
+            alloc.insert(
+                format!("0x{}", hex::encode(auxiliary)),
+                GenesisAccount {
+                    balance: format!("0x{balance:x}"),
+                    nonce: None,
+                    code: None,
+                    storage: BTreeMap::new(),
+                },
+            );
+        }
+    }
+    Ok(alloc)
+}
+
+/// Genesis JSON holding `alloc`: `base` with the entries added to its own `alloc` (replacing
+/// accounts at the same address), or a bare `{"alloc": ...}` object without a base
+pub fn genesis_json(
+    alloc: BTreeMap<String, GenesisAccount>,
+    base: Option<Value>,
+) -> Result<Value, String> {
+    let mut genesis = match base {
+        Some(Value::Object(genesis)) => genesis,
+        Some(_) => return Err("Genesis file must hold a JSON object".to_string()),
+        None => Map::new(),
+    };
+    let entry = genesis
+        .entry("alloc")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Value::Object(existing) = entry else {
+        return Err("Genesis alloc must be a JSON object".to_string());
+    };
+
+    for (address, account) in alloc {
+        let account =
+            serde_json::to_value(account).map_err(|e| format!("Failed to serialize: {e}"))?;
+        existing.insert(address, account);
+    }
+    Ok(Value::Object(genesis))
+}
+
+/// Write the genesis JSON file
+pub fn write_genesis(genesis: &Value, output_path: &str) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(genesis)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize to JSON: {e}")))?;
+    fs::write(output_path, json).map_err(|e| Error::io(output_path, e))?;
+    info!("Genesis saved to: {output_path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_miner::ContractWithAuxiliaries;
+    use crate::evm::{Runtime, seeding_contract};
+    use crate::storage_layout::slot_from_u64;
+
+    #[test]
+    fn test_genesis_alloc_places_contracts_and_auxiliaries() {
+        let result = Create2MiningResult {
+            deployer: format!("0x{}", hex::encode([0u8; 20])),
+            init_code_hash: String::new(),
+            init_code: String::new(),
+            deploy_code: "0x6000".to_string(),
+            storage_keys: Vec::new(),
+            storage_slots: vec![format!("0x{}", hex::encode(slot_from_u64(7)))],
+            target_depth: 1,
+            num_contracts: 1,
+            seed: None,
+            total_time: 0.0,
+            status: Default::default(),
+            contracts: vec![ContractWithAuxiliaries {
+                salt: [0; 32],
+                contract_address: format!("0x{}", hex::encode([0x42; 20])),
+                auxiliary_accounts: vec![format!("0x{}", hex::encode([0x43; 20]))],
+                auxiliary_keys: Vec::new(),
+            }],
+            account_trie: None,
+            key_encryption: None,
+            cluster: None,
+        };
+        let alloc = genesis_alloc(&result, 1000, None).unwrap();
+        assert_eq!(alloc.len(), 2);
+
+        let contract = &alloc[&format!("0x{}", hex::encode([0x42; 20]))];
+        assert_eq!(contract.code.as_deref(), Some("0x6000"));
+        assert_eq!(contract.nonce.as_deref(), Some("0x1"));
+        assert_eq!(
+            contract.storage[&format!("0x{}", hex::encode(slot_from_u64(7)))],
+            format!("0x{}", hex::encode(slot_from_u64(1)))
+        );
+        let auxiliary = &alloc[&format!("0x{}", hex::encode([0x43; 20]))];
+        assert_eq!(auxiliary.balance, "0x3e8");
+        assert!(auxiliary.code.is_none() && auxiliary.storage.is_empty());
+
+        // A base genesis keeps its config and other accounts
+        let base = serde_json::json!({
+            "config": {"chainId": 1337},
+            "alloc": {"0x0000000000000000000000000000000000000001": {"balance": "0x1"}}
+        });
+        let genesis = genesis_json(alloc, Some(base)).unwrap();
+        assert_eq!(genesis["config"]["chainId"], 1337);
+        assert_eq!(genesis["alloc"].as_object().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_genesis_storage_matches_proof_storage() {
+        let slot = slot_from_u64(7);
+        let mut result: Create2MiningResult = serde_json::from_value(serde_json::json!({
+            "deployer": format!("0x{}", hex::encode([0x4e; 20])),
+            "init_code_hash": "",
+            "init_code": "",
+            "deploy_code": "0x00",
+            "storage_keys": [],
+            "target_depth": 1,
+            "num_contracts": 1,
+            "total_time": 0.0,
+            "contracts": [{
+                "salt": format!("0x{}", hex::encode([0u8; 32])),
+                "contract_address": format!("0x{}", hex::encode([0x42; 20])),
+                "auxiliary_accounts": [],
+            }],
+        }))
+        .unwrap();
+        let contract = format!("0x{}", hex::encode([0x42; 20]));
+        let word = |w: &[u8; 32]| format!("0x{}", hex::encode(w));
+
+        // Nothing tells what the constructor writes
+        assert!(genesis_alloc(&result, 0, None).is_err());
+        let explicit = [(slot, slot_from_u64(0x2a)), (slot_from_u64(8), [0; 32])];
+        let alloc = genesis_alloc(&result, 0, Some(&explicit)).unwrap();
+        assert_eq!(alloc[&contract].storage.len(), 1);
+        assert_eq!(
+            alloc[&contract].storage[&word(&slot)],
+            word(&slot_from_u64(0x2a))
+        );
+
+        // A generated ERC20 also holds its mint
+        let bytecode = seeding_contract(&[slot], Runtime::Erc20);
+        result.init_code = format!("0x{}", hex::encode(&bytecode.init_code));
+        result.storage_slots = vec![word(&slot)];
+        let alloc = genesis_alloc(&result, 0, None).unwrap();
+        let expected: BTreeMap<String, String> = result
+            .contract_storage()
+            .unwrap()
+            .iter()
+            .map(|(slot, value)| (word(slot), word(value)))
+            .collect();
+        assert_eq!(expected.len(), 3);
+        assert_eq!(alloc[&contract].storage, expected);
+    }
+}
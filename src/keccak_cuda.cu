@@ -149,6 +149,32 @@ __device__ void calculate_storage_slot(uint8_t address[20], uint64_t base_slot,
     }
 }
 
+// Hash a 32-byte storage slot to get its secure trie key: keccak256(slot)
+__device__ void keccak256_32(const uint8_t input[32], uint8_t output[32]) {
+    uint64_t state[25] = {0};
+
+    // Load input into state (little-endian)
+    for (int i = 0; i < 4; i++) {
+        for (int j = 0; j < 8; j++) {
+            state[i] |= ((uint64_t)input[i * 8 + j]) << (j * 8);
+        }
+    }
+
+    // Add padding
+    state[4] = 0x01;
+    state[16] = 0x8000000000000000ULL;
+
+    // Apply Keccak-f[1600]
+    keccak_f1600(state);
+
+    // Extract output (first 32 bytes)
+    for (int i = 0; i < 4; i++) {
+        for (int j = 0; j < 8; j++) {
+            output[i * 8 + j] = (state[i] >> (j * 8)) & 0xFF;
+        }
+    }
+}
+
 // Check if two byte arrays share a prefix of n nibbles
 // Optimized with early rejection using word-level comparisons
 __device__ bool check_nibble_prefix(const uint8_t* a, const uint8_t* b, int nibbles) {
@@ -187,6 +213,7 @@ __global__ void mine_storage_slots(
     uint8_t* target_prefix,      // Target storage key prefix to match
     int required_nibbles,         // Number of nibbles that must match
     uint64_t base_slot,          // ERC20 balance mapping slot (usually 0)
+    bool hash_slot,              // Match keccak(slot) (secure trie key) instead of the slot
     uint64_t start_nonce,        // Starting nonce for this kernel
     uint64_t max_attempts,       // Maximum attempts per thread
     uint8_t* result_address,     // Output: found address (20 bytes)
@@ -216,8 +243,16 @@ __global__ void mine_storage_slots(
         // Calculate storage slot
         calculate_storage_slot(address, base_slot, storage_key);
 
+        // In trie-key mode, the prefix is matched against keccak(slot)
+        uint8_t trie_key[32];
+        const uint8_t* mined_key = storage_key;
+        if (hash_slot) {
+            keccak256_32(storage_key, trie_key);
+            mined_key = trie_key;
+        }
+
         // Check if it matches the required prefix
-        if (check_nibble_prefix(storage_key, target_prefix, required_nibbles)) {
+        if (check_nibble_prefix(mined_key, target_prefix, required_nibbles)) {
             // Use atomic compare-and-swap to ensure only one thread wins
             int old = atomicCAS(found, 0, 1);
             if (old == 0) {
@@ -332,6 +367,7 @@ extern "C" {
         uint8_t* target_prefix,
         int required_nibbles,
         uint64_t base_slot,
+        bool hash_slot,
         uint8_t* result_address,
         uint8_t* result_storage_key,
         bool* found,
@@ -358,6 +394,7 @@ extern "C" {
             d_target,
             required_nibbles,
             base_slot,
+            hash_slot,
             start_nonce,
             attempts_per_thread,
             d_result_addr,
@@ -0,0 +1,146 @@
+//! # Key Derivation Module
+//!
+//! Defines how a mapping key and the slot of its mapping combine into a storage slot.
+//! Compilers disagree on this: Solidity hashes `key || slot`, Vyper hashes `slot || key`,
+//! and hand-written contracts often hash `abi.encodePacked(...)` of the raw values.
+//!
+//! ## Built-in Derivations
+//! - `SolidityDerivation`: `keccak256(abi.encode(key, slot))`
+//! - `VyperDerivation`: `keccak256(abi.encode(slot, key))`
+//! - `PackedDerivation`: `keccak256(abi.encodePacked(key, slot))`
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::storage_layout::KeyType;
+
+/// Derives the storage slot of a mapping entry from its key and the mapping's slot
+pub trait KeyDerivation: Debug + Send + Sync {
+    /// Storage slot of `key` (ABI-encoded to 32 bytes) in the mapping stored at `slot`
+    fn derive(&self, key: &[u8; 32], key_type: KeyType, slot: &[u8; 32]) -> [u8; 32];
+
+    /// Short name recorded in result files
+    fn name(&self) -> &'static str;
+
+    /// Whether the CUDA kernel (which hard-codes Solidity's scheme) can mine this derivation
+    fn cuda_compatible(&self) -> bool {
+        false
+    }
+}
+
+/// Solidity mappings: `keccak256(key || slot)`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SolidityDerivation;
+
+impl KeyDerivation for SolidityDerivation {
+    fn derive(&self, key: &[u8; 32], _key_type: KeyType, slot: &[u8; 32]) -> [u8; 32] {
+        keccak_concat(key, slot)
+    }
+
+    fn name(&self) -> &'static str {
+        "solidity"
+    }
+
+    fn cuda_compatible(&self) -> bool {
+        true
+    }
+}
+
+/// Vyper `HashMap`s: `keccak256(slot || key)`, operands reversed compared to Solidity
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VyperDerivation;
+
+impl KeyDerivation for VyperDerivation {
+    fn derive(&self, key: &[u8; 32], _key_type: KeyType, slot: &[u8; 32]) -> [u8; 32] {
+        keccak_concat(slot, key)
+    }
+
+    fn name(&self) -> &'static str {
+        "vyper"
+    }
+}
+
+/// Custom schemes hashing `abi.encodePacked(key, slot)`: the key is not padded, so an
+/// address key contributes 20 bytes instead of 32
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PackedDerivation;
+
+impl KeyDerivation for PackedDerivation {
+    fn derive(&self, key: &[u8; 32], key_type: KeyType, slot: &[u8; 32]) -> [u8; 32] {
+        keccak_concat(&key[32 - key_type.width()..], slot)
+    }
+
+    fn name(&self) -> &'static str {
+        "packed"
+    }
+}
+
+/// Built-in derivations selectable from the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyScheme {
+    /// `keccak256(key || slot)`
+    #[default]
+    Solidity,
+    /// `keccak256(slot || key)`
+    Vyper,
+    /// `keccak256(abi.encodePacked(key, slot))`
+    Packed,
+}
+
+impl KeyScheme {
+    pub fn derivation(self) -> Arc<dyn KeyDerivation> {
+        match self {
+            KeyScheme::Solidity => Arc::new(SolidityDerivation),
+            KeyScheme::Vyper => Arc::new(VyperDerivation),
+            KeyScheme::Packed => Arc::new(PackedDerivation),
+        }
+    }
+
+    /// The scheme recorded in result files as `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        [KeyScheme::Solidity, KeyScheme::Vyper, KeyScheme::Packed]
+            .into_iter()
+            .find(|scheme| scheme.derivation().name() == name)
+    }
+}
+
+fn keccak_concat(a: &[u8], b: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(a);
+    hasher.update(b);
+    hasher.finalize(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_layout::{address_key, slot_from_u64};
+
+    #[test]
+    fn test_builtin_derivations() {
+        let address = [0xab; 20];
+        let key = address_key(&address);
+        let slot = slot_from_u64(3);
+
+        assert_eq!(
+            SolidityDerivation.derive(&key, KeyType::Address, &slot),
+            keccak_concat(&key, &slot)
+        );
+        assert_eq!(
+            VyperDerivation.derive(&key, KeyType::Address, &slot),
+            keccak_concat(&slot, &key)
+        );
+        assert_eq!(
+            PackedDerivation.derive(&key, KeyType::Address, &slot),
+            keccak_concat(&address, &slot)
+        );
+        // Full-width keys are packed unchanged
+        assert_eq!(
+            PackedDerivation.derive(&key, KeyType::Uint256, &slot),
+            keccak_concat(&key, &slot)
+        );
+    }
+}
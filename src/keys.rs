@@ -0,0 +1,434 @@
+//! # Keys Module
+//!
+//! Secret keys for mined auxiliary accounts, so they are spendable EOAs instead of bare
+//! addresses nobody controls. Covers address derivation, a fast candidate walk for mining,
+//! and optional password encryption of the keys written to result files.
+//!
+//! Encrypted keys are Web3 Secret Storage v3 keystores (scrypt, aes-128-ctr and a keccak MAC),
+//! so clients can import them directly. The password is stretched once per run: the keystores
+//! of a file share their scrypt salt, and each gets its own random IV. Decryption also accepts
+//! pbkdf2-sha256 and rejects parameters below fixed minimums.
+//!
+//! ## Key Functions
+//! - `address_of`: Derives an address from a public key
+//! - `KeyWalk`: Walks consecutive secret keys with one point addition per candidate
+//! - `Keystore`: Encrypts and decrypts secret keys with a password
+//! - `KeyFile`: A secret key as a v3 keystore
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use rand::Rng;
+use secp256k1::{All, PublicKey, Scalar, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tiny_keccak::{Hasher, Keccak};
+
+/// scrypt parameters of new keystores, the ones geth uses by default
+const SCRYPT_N: u64 = 1 << 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Weakest parameters accepted on decryption (geth's "light" scrypt, and the pbkdf2 rounds of
+/// the v3 test vectors), so a tampered file cannot make the password cheap to guess
+const MIN_SCRYPT_N: u64 = 1 << 12;
+const MIN_SCRYPT_R: u32 = 8;
+const MIN_PBKDF2_ROUNDS: u32 = 1 << 18;
+
+/// Cipher of the keystores
+const CIPHER: &str = "aes-128-ctr";
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+/// Ethereum address of a public key: the last 20 bytes of `keccak256(x || y)`
+pub fn address_of(public_key: &PublicKey) -> [u8; 20] {
+    let uncompressed = public_key.serialize_uncompressed();
+    let hash = keccak256(&[&uncompressed[1..]]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Walks the secret keys `base, base + 1, base + 2, ...` from a random base. Each step is a
+/// single point addition, much cheaper than deriving every public key from scratch
+pub struct KeyWalk {
+    base: SecretKey,
+    offset: u64,
+    public_key: PublicKey,
+    generator: PublicKey,
+}
+
+impl KeyWalk {
+    pub fn new<R: Rng>(secp: &Secp256k1<All>, rng: &mut R) -> Self {
+        let base = SecretKey::new(rng);
+        let one = SecretKey::from_slice(&secp256k1::constants::ONE).expect("One is a valid key");
+        KeyWalk {
+            base,
+            offset: 0,
+            public_key: PublicKey::from_secret_key(secp, &base),
+            generator: PublicKey::from_secret_key(secp, &one),
+        }
+    }
+
+    /// Address of the current key
+    pub fn address(&self) -> [u8; 20] {
+        address_of(&self.public_key)
+    }
+
+    /// Move to the next key. Returns `false` in the (negligible) case the walk hits the point
+    /// at infinity; start a new walk then
+    pub fn advance(&mut self) -> bool {
+        match self.public_key.combine(&self.generator) {
+            Ok(next) => {
+                self.public_key = next;
+                self.offset += 1;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Secret key of the current position
+    pub fn secret_key(&self) -> [u8; 32] {
+        let mut tweak = [0u8; 32];
+        tweak[24..].copy_from_slice(&self.offset.to_be_bytes());
+        let tweak = Scalar::from_be_bytes(tweak).expect("Offset is below the curve order");
+        self.base
+            .add_tweak(&tweak)
+            .expect("Walk never reaches the zero key")
+            .secret_bytes()
+    }
+}
+
+/// Key-derivation function of a keystore with its parameters, as in Web3 Secret Storage v3
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kdf", content = "kdfparams", rename_all = "lowercase")]
+pub enum Kdf {
+    Scrypt {
+        dklen: usize,
+        n: u64,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        dklen: usize,
+        c: u32,
+        prf: String,
+        salt: String,
+    },
+}
+
+impl Kdf {
+    /// Derive the 32-byte key from `password`, rejecting parameters weaker than the minimums
+    fn derive(&self, password: &str) -> Result<[u8; 32], String> {
+        let mut derived_key = [0u8; 32];
+        match self {
+            Kdf::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                salt,
+            } => {
+                if *dklen != derived_key.len() {
+                    return Err(format!("Unsupported scrypt dklen: {dklen}"));
+                }
+                if !n.is_power_of_two() || *n < MIN_SCRYPT_N || *r < MIN_SCRYPT_R || *p == 0 {
+                    return Err(format!(
+                        "scrypt parameters too weak: n={n}, r={r}, p={p} (minimum n={MIN_SCRYPT_N}, r={MIN_SCRYPT_R}, p=1)"
+                    ));
+                }
+                let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p, *dklen)
+                    .map_err(|e| format!("Invalid scrypt parameters: {e}"))?;
+                scrypt::scrypt(
+                    password.as_bytes(),
+                    &decode_hex(salt)?,
+                    &params,
+                    &mut derived_key,
+                )
+                .map_err(|e| format!("scrypt failed: {e}"))?;
+            }
+            Kdf::Pbkdf2 {
+                dklen,
+                c,
+                prf,
+                salt,
+            } => {
+                if *dklen != derived_key.len() || prf != "hmac-sha256" {
+                    return Err(format!("Unsupported pbkdf2 dklen {dklen} or prf {prf}"));
+                }
+                if *c < MIN_PBKDF2_ROUNDS {
+                    return Err(format!(
+                        "pbkdf2 rounds too weak: c={c} (minimum {MIN_PBKDF2_ROUNDS})"
+                    ));
+                }
+                pbkdf2::pbkdf2_hmac::<Sha256>(
+                    password.as_bytes(),
+                    &decode_hex(salt)?,
+                    *c,
+                    &mut derived_key,
+                );
+            }
+        }
+        Ok(derived_key)
+    }
+}
+
+/// Parameters shared by the keystores of a result file, so the password is stretched once per
+/// run rather than once per key
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyEncryption {
+    pub cipher: String,
+    #[serde(flatten)]
+    pub kdf: Kdf,
+}
+
+/// The `crypto` section of a Web3 Secret Storage v3 keystore
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    #[serde(flatten)]
+    pub kdf: Kdf,
+    pub mac: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+/// One secret key as a Web3 Secret Storage v3 keystore, importable by Ethereum clients
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyFile {
+    pub version: u32,
+    pub id: String,
+    pub address: String,
+    pub crypto: KeystoreCrypto,
+}
+
+/// A secret key as written to results and checkpoints: plain hex, or a v3 keystore
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoredKey {
+    Plain(String),
+    Encrypted(Box<KeyFile>),
+}
+
+/// A password-derived key that encrypts secret keys into v3 keystores with aes-128-ctr
+pub struct Keystore {
+    derived_key: [u8; 32],
+    params: KeyEncryption,
+}
+
+impl Keystore {
+    /// Derive a fresh key from `password` with scrypt and a random salt
+    pub fn new(password: &str) -> Self {
+        let salt: [u8; 32] = rand::thread_rng().r#gen();
+        let params = KeyEncryption {
+            cipher: CIPHER.to_string(),
+            kdf: Kdf::Scrypt {
+                dklen: 32,
+                n: SCRYPT_N,
+                r: SCRYPT_R,
+                p: SCRYPT_P,
+                salt: hex::encode(salt),
+            },
+        };
+        Keystore {
+            derived_key: params
+                .kdf
+                .derive(password)
+                .expect("Default parameters are valid"),
+            params,
+        }
+    }
+
+    /// Re-derive the key used for a result file
+    pub fn from_params(params: &KeyEncryption, password: &str) -> Result<Self, String> {
+        if params.cipher != CIPHER {
+            return Err(format!("Unsupported cipher: {}", params.cipher));
+        }
+        Ok(Keystore {
+            derived_key: params.kdf.derive(password)?,
+            params: params.clone(),
+        })
+    }
+
+    pub fn params(&self) -> &KeyEncryption {
+        &self.params
+    }
+
+    /// Encrypt the secret key of `address` into a keystore with a fresh random IV
+    pub fn encrypt(&self, address: &[u8; 20], secret_key: &[u8; 32]) -> KeyFile {
+        let mut rng = rand::thread_rng();
+        let iv: [u8; 16] = rng.r#gen();
+        let mut ciphertext = *secret_key;
+        self.apply_keystream(&iv, &mut ciphertext);
+        let mac = keccak256(&[&self.derived_key[16..], &ciphertext]);
+        KeyFile {
+            version: 3,
+            id: uuid_v4(rng.r#gen()),
+            address: hex::encode(address),
+            crypto: KeystoreCrypto {
+                cipher: CIPHER.to_string(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                ciphertext: hex::encode(ciphertext),
+                kdf: self.params.kdf.clone(),
+                mac: hex::encode(mac),
+            },
+        }
+    }
+
+    /// Decrypt the secret key of `address`, rejecting a wrong password or corrupted data
+    pub fn decrypt(&self, address: &[u8; 20], key_file: &KeyFile) -> Result<[u8; 32], String> {
+        let crypto = &key_file.crypto;
+        if crypto.cipher != CIPHER || crypto.kdf != self.params.kdf {
+            return Err("Keystore was encrypted with different parameters".to_string());
+        }
+        if decode_hex(&key_file.address)? != address {
+            return Err(format!("Keystore is for address 0x{}", key_file.address));
+        }
+        let ciphertext: [u8; 32] = decode_hex(&crypto.ciphertext)?
+            .try_into()
+            .map_err(|_| "Ciphertext must be 32 bytes".to_string())?;
+        let iv: [u8; 16] = decode_hex(&crypto.cipherparams.iv)?
+            .try_into()
+            .map_err(|_| "IV must be 16 bytes".to_string())?;
+        let mac = keccak256(&[&self.derived_key[16..], &ciphertext]);
+        if mac[..] != decode_hex(&crypto.mac)?[..] {
+            return Err("MAC mismatch: wrong password or corrupted key".to_string());
+        }
+        let mut secret_key = ciphertext;
+        self.apply_keystream(&iv, &mut secret_key);
+        Ok(secret_key)
+    }
+
+    /// aes-128-ctr with the first half of the derived key
+    fn apply_keystream(&self, iv: &[u8; 16], data: &mut [u8]) {
+        let mut cipher = Aes128Ctr::new(self.derived_key[..16].into(), iv.into());
+        cipher.apply_keystream(data);
+    }
+}
+
+/// A random (version 4) UUID, as keystores are identified by
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value)).map_err(|e| format!("Invalid hex: {e}"))
+}
+
+fn keccak256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_of_known_key() {
+        // Secret key 1 controls 0x7e5f4552091a69125d5dfcb7b8c2659029395bdf
+        let secp = Secp256k1::new();
+        let one = SecretKey::from_slice(&secp256k1::constants::ONE).unwrap();
+        let address = address_of(&PublicKey::from_secret_key(&secp, &one));
+        assert_eq!(
+            hex::encode(address),
+            "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+    }
+
+    #[test]
+    fn test_key_walk_matches_derivation() {
+        let secp = Secp256k1::new();
+        let mut walk = KeyWalk::new(&secp, &mut rand::thread_rng());
+        for _ in 0..3 {
+            assert!(walk.advance());
+        }
+        let secret_key = SecretKey::from_slice(&walk.secret_key()).unwrap();
+        assert_eq!(
+            address_of(&PublicKey::from_secret_key(&secp, &secret_key)),
+            walk.address()
+        );
+    }
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let params = KeyEncryption {
+            cipher: CIPHER.to_string(),
+            kdf: Kdf::Scrypt {
+                dklen: 32,
+                n: MIN_SCRYPT_N,
+                r: MIN_SCRYPT_R,
+                p: 1,
+                salt: "0102".to_string(),
+            },
+        };
+        let keystore = Keystore::from_params(&params, "hunter2").unwrap();
+        let address = [0x11; 20];
+        let secret_key = [0x22; 32];
+
+        let key_file = keystore.encrypt(&address, &secret_key);
+        assert_eq!(key_file.version, 3);
+        assert_ne!(key_file.crypto.ciphertext, hex::encode(secret_key));
+        assert_eq!(keystore.decrypt(&address, &key_file), Ok(secret_key));
+        // Same key, fresh IV
+        assert_ne!(keystore.encrypt(&address, &secret_key), key_file);
+
+        let wrong = Keystore::from_params(&params, "hunter3").unwrap();
+        assert!(wrong.decrypt(&address, &key_file).is_err());
+        assert!(keystore.decrypt(&[0x12; 20], &key_file).is_err());
+    }
+
+    #[test]
+    fn test_v3_test_vector_and_weak_parameters() {
+        // PBKDF2 test vector of the Web3 Secret Storage definition
+        let key_file: KeyFile = serde_json::from_str(
+            r#"{"crypto":{"cipher":"aes-128-ctr","cipherparams":{"iv":"6087dab2f9fdbbfaddc31a909735c1e6"},"ciphertext":"5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46","kdf":"pbkdf2","kdfparams":{"c":262144,"dklen":32,"prf":"hmac-sha256","salt":"ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"},"mac":"517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"},"id":"3198bc9c-6672-5ab3-d995-4942343ae5b6","version":3,"address":"008aeeda4d805471df9b2a5b0f38a0c3bcba786b"}"#,
+        )
+        .unwrap();
+        let params = KeyEncryption {
+            cipher: key_file.crypto.cipher.clone(),
+            kdf: key_file.crypto.kdf.clone(),
+        };
+        let keystore = Keystore::from_params(&params, "testpassword").unwrap();
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hex::decode(&key_file.address).unwrap());
+        assert_eq!(
+            hex::encode(keystore.decrypt(&address, &key_file).unwrap()),
+            "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+        );
+
+        let weak = KeyEncryption {
+            cipher: CIPHER.to_string(),
+            kdf: Kdf::Scrypt {
+                dklen: 32,
+                n: 2,
+                r: 1,
+                p: 1,
+                salt: "00".to_string(),
+            },
+        };
+        assert!(Keystore::from_params(&weak, "hunter2").is_err());
+    }
+}
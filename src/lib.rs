@@ -0,0 +1,76 @@
+//! # Worst Case Miner
+//!
+//! Mines storage slots and accounts whose trie keys share long prefixes, creating worst-case
+//! deep branches in Ethereum's storage and account tries. The `worst_case_miner` binary is a
+//! thin command-line front end; benchmarking harnesses can call the same API directly.
+//!
+//! Mining runs are configured through builders, which check the parameters before any work
+//! starts, and return plain result types that serialize to the files the CLI writes:
+//!
+//! ```no_run
+//! use worst_case_miner::{Create2Config, StorageMiningConfig, mine_deep_branch};
+//!
+//! let config = StorageMiningConfig::builder(6).seed(1).build()?;
+//! let mined = mine_deep_branch(&config)?;
+//! assert_eq!(mined.branch.len(), 6);
+//!
+//! let config = Create2Config::builder([0u8; 20], 10, 4).num_threads(8).build()?;
+//! # Ok::<(), worst_case_miner::Error>(())
+//! ```
+//!
+//! ## Key Modules
+//! - `storage_miner`: Deep branches in a contract's storage trie
+//! - `account_miner`: CREATE2 contracts and auxiliary accounts deepening the account trie
+//! - `storage_layout`, `key_derivation`: Where the mined mapping lives and how its slots are hashed
+//! - `search`: Multi-target search machinery and nibble comparisons
+//! - `checkpoint`: Progress records that let an interrupted run resume
+//! - `progress`: Hashrate, expected work and ETAs of a running search
+//! - `plan`: Expected and 99th-percentile costs of a run, from calibrated hashrates
+//! - `contract`, `evm`: Init code of the generated contract, via `solc` or emitted natively
+//! - `keys`: Auxiliary secret keys and their keystore encryption
+//! - `mpt`: In-memory Merkle Patricia Trie for reports and proofs
+//! - `proof`: `eth_getProof`-style proofs and witness sizes of mined contracts
+//! - `genesis`: Devnet genesis `alloc` holding a mined state
+//! - `state_test`: ethereum/tests state test fillers attacking mined contracts
+//! - `transactions`: Signed transactions deploying a mined result on a live chain
+//! - `verify`: Re-checks mined result files
+//! - `error`: The library's error type
+//!
+//! The command-line parsing lives in the binary, so the library does not expose its clap
+//! argument structs.
+
+pub mod account_miner;
+pub mod checkpoint;
+pub mod contract;
+pub mod error;
+pub mod evm;
+pub mod genesis;
+pub mod key_derivation;
+pub mod keys;
+pub mod mpt;
+pub mod plan;
+pub mod progress;
+pub mod proof;
+pub mod rlp;
+pub mod search;
+pub mod state_test;
+pub mod storage_layout;
+pub mod storage_miner;
+pub mod transactions;
+pub mod verify;
+
+#[cfg(feature = "cuda")]
+pub mod cuda_miner;
+
+pub use account_miner::{
+    Create2Config, Create2ConfigBuilder, Create2MiningResult, SaltScheme, TargetConfig,
+    TargetConfigBuilder, TargetMiningResult, calculate_create2_address, mine_create2_accounts,
+    mine_target_accounts,
+};
+pub use error::Error;
+pub use progress::ProgressMonitor;
+pub use search::{Budget, MAX_DEPTH, SearchStatus, count_shared_nibbles};
+pub use storage_miner::{
+    KeyMode, MinedBranch, StorageMiningConfig, StorageMiningConfigBuilder, StorageMiningResult,
+    StorageSlot, has_nibble_prefix, mine_deep_branch,
+};
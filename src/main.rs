@@ -1,265 +1,695 @@
-use clap::Parser;
+mod cli;
+
+use clap::{CommandFactory, Parser};
 use log::info;
-use std::process::Command;
+use std::fs::OpenOptions;
+use std::io;
+use std::sync::Arc;
 use std::time::Instant;
 
-mod account_miner;
-mod storage_miner;
-
+use cli::{
+    Cli, CodegenArgs, Create2Args, DecryptKeysArgs, DeployTxsArgs, GenesisArgs, PlanArgs,
+    ProgressArgs, ProofArgs, StateTestArgs, StorageArgs, TargetsArgs, VerifyArgs,
+};
+use worst_case_miner::checkpoint::CheckpointFile;
+use worst_case_miner::contract::{CompiledContract, compile_solidity, load_init_code};
 #[cfg(feature = "cuda")]
-mod cuda_miner;
-
-/// A mining program to create deep branches in ERC20 contract storage and account trie
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Target depth for the storage/account branch
-    #[arg(short, long)]
-    depth: usize,
-
-    /// Number of threads to use for mining (default: number of CPU cores)
-    #[arg(short, long, default_value_t = num_cpus::get())]
-    threads: usize,
-
-    /// Use CUDA acceleration if available
-    #[arg(long)]
-    cuda: bool,
-
-    /// Deployer address for CREATE2 (hex string, default: 0x0000...)
-    #[arg(long)]
-    deployer: Option<String>,
-
-    /// Number of contracts to deploy via CREATE2
-    #[arg(long)]
-    num_contracts: Option<usize>,
-
-    /// Path to contract init code for CREATE2 hash calculation
-    #[arg(long)]
-    init_code: Option<String>,
-
-    /// Output file for CREATE2 accounts JSON
-    #[arg(long, default_value = "create2_accounts.json")]
-    accounts_output: String,
-}
+use worst_case_miner::cuda_miner;
+use worst_case_miner::evm::Codegen;
+use worst_case_miner::search::SearchStatus;
+use worst_case_miner::storage_miner::{SlotWrite, StorageMiningConfig, StorageSlot};
+use worst_case_miner::{
+    Create2Config, Error, ProgressMonitor, SaltScheme, TargetConfig, account_miner, genesis, keys,
+    mpt, plan, progress, proof, search, state_test, storage_layout, storage_miner, transactions,
+    verify,
+};
 
 fn main() {
     // Initialize logger
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let args = Args::parse();
+    let cli = Cli::parse();
+
+    let validation = match &cli.command {
+        cli::Command::Storage(args) => args.validate(),
+        cli::Command::Create2(args) => args.validate(),
+        cli::Command::Targets(args) => args.validate(),
+        cli::Command::DeployTxs(args) => args.validate(),
+        cli::Command::Plan(_)
+        | cli::Command::Proof(_)
+        | cli::Command::DecryptKeys(_)
+        | cli::Command::Verify(_)
+        | cli::Command::Genesis(_)
+        | cli::Command::StateTest(_) => Ok(()),
+    };
+    if let Err(msg) = validation {
+        Cli::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, msg)
+            .exit();
+    }
 
-    info!("Starting mining for depth: {}", args.depth);
+    let outcome = match cli.command {
+        cli::Command::Storage(args) => run_storage(args),
+        cli::Command::Create2(args) => run_create2(args),
+        cli::Command::Targets(args) => run_targets(args),
+        cli::Command::Plan(args) => run_plan(args),
+        cli::Command::Proof(args) => run_proof(args),
+        cli::Command::DecryptKeys(args) => run_decrypt_keys(args),
+        cli::Command::Verify(args) => run_verify(args),
+        cli::Command::Genesis(args) => run_genesis(args),
+        cli::Command::StateTest(args) => run_state_test(args),
+        cli::Command::DeployTxs(args) => run_deploy_txs(args),
+    };
+    if let Err(e) = outcome {
+        log::error!("{e}");
+        std::process::exit(e.exit_code());
+    }
+}
 
+/// Log which backend will be used for mining
+fn log_backend(threads: usize, #[allow(unused_variables)] cuda: bool) {
     #[cfg(feature = "cuda")]
     {
-        if args.cuda && cuda_miner::cuda_available() {
+        if cuda && cuda_miner::cuda_available() {
             info!("Using CUDA acceleration");
-        } else if args.cuda {
+        } else if cuda {
             info!("CUDA requested but not available, falling back to CPU");
-            info!("Using {} CPU threads", args.threads);
+            info!("Using {threads} CPU threads");
         } else {
-            info!("Using {} CPU threads", args.threads);
+            info!("Using {threads} CPU threads");
         }
     }
 
     #[cfg(not(feature = "cuda"))]
     {
-        if args.cuda {
+        if cuda {
             info!("CUDA support not compiled. Rebuild with --features cuda");
         }
-        info!("Using {} CPU threads", args.threads);
+        info!("Using {threads} CPU threads");
     }
+}
 
-    // Mine CREATE2 accounts if requested
-    if let Some(num_contracts) = args.num_contracts {
-        // Parse deployer address
-        let deployer = if let Some(deployer_str) = args.deployer {
-            parse_address(&deployer_str).expect("Invalid deployer address")
-        } else {
-            [0u8; 20] // Default to zero address
-        };
+/// Mine a deep storage branch and generate the contract seeding it
+fn run_storage(args: StorageArgs) -> Result<(), Error> {
+    info!("Starting mining for depth: {}", args.depth);
+    log_backend(args.threads, args.cuda);
+    search::stop_on_interrupt();
+    let budget = Arc::new(
+        args.budget
+            .to_budget()
+            .monitor(progress_monitor(&args.progress)?),
+    );
+    let checkpoint = open_checkpoint(
+        &args.resume,
+        &args.checkpoint,
+        args.overwrite_checkpoint,
+        args.seed,
+        args.depth,
+    )?;
+
+    let config = StorageMiningConfig::builder(args.depth)
+        .num_threads(args.threads)
+        .use_cuda(args.cuda)
+        .key_mode(args.key_mode)
+        .layout(args.layout.to_layout())
+        .key_scheme(args.layout.key_scheme)
+        .full_width(args.full_width)
+        .seed(checkpoint.seed())
+        .budget(budget)
+        .checkpoint(checkpoint)
+        .build()?;
 
-        // Load or generate init code, deploy code, and storage keys
-        let (compiled, storage_keys): (CompiledContract, Vec<[u8; 20]>) =
-            if let Some(init_code_path) = args.init_code {
-                // Check if it's a .sol file or a hex file
-                if init_code_path.ends_with(".sol") {
-                    // Compile the Solidity file to get bytecode
-                    info!("Compiling Solidity contract: {}", init_code_path);
-                    let compiled =
-                        compile_solidity(&init_code_path).expect("Failed to compile Solidity contract");
-                    // When loading from external .sol file, we don't have storage keys
-                    (compiled, Vec::new())
-                } else if init_code_path.ends_with(".hex") || init_code_path.ends_with(".bin") {
-                    // Read hex bytecode from file
-                    info!("Loading bytecode from: {}", init_code_path);
-                    let hex_content = std::fs::read_to_string(&init_code_path)
-                        .expect("Failed to read bytecode file");
-                    let hex_content = hex_content.trim();
-                    let hex_content = hex_content.strip_prefix("0x").unwrap_or(hex_content);
-                    let init_code = hex::decode(hex_content).expect("Invalid hex in bytecode file");
-                    // For raw bytecode, we don't have deploy_code or storage_keys
-                    (
-                        CompiledContract {
-                            init_code,
-                            deploy_code: Vec::new(),
-                        },
-                        Vec::new(),
-                    )
-                } else {
-                    // Assume it's raw bytecode
-                    let init_code = std::fs::read(&init_code_path).expect("Failed to read init code file");
-                    (
-                        CompiledContract {
-                            init_code,
-                            deploy_code: Vec::new(),
-                        },
-                        Vec::new(),
-                    )
-                }
-            } else if args.depth > 0 {
-                // No init code provided but depth specified - generate and compile a contract with the specified depth
-                info!(
-                    "No init code provided. Generating contract with depth {}...",
-                    args.depth
-                );
+    let start_time = Instant::now();
 
-                // First, mine storage slots for the contract
-                let branch = storage_miner::mine_deep_branch(args.depth, args.threads, false);
+    // Mine for the deep branch (storage)
+    let mined = storage_miner::mine_deep_branch(&config)?;
+    let branch = &mined.branch;
 
-                // Extract storage keys (addresses) from the mined branch
-                let storage_keys: Vec<[u8; 20]> = branch.iter().map(|slot| slot.address).collect();
+    let elapsed = start_time.elapsed();
 
-                // Generate the contract
-                storage_miner::generate_contract(&branch);
+    // Output results
+    storage_miner::print_results(branch, &config, elapsed.as_secs_f64());
+    // Check the resulting trie structurally, next to any existing storage
+    let existing_storage = match &args.existing_storage {
+        Some(path) => load_storage(path)?,
+        None => Vec::new(),
+    };
+    let storage_trie = storage_miner::storage_trie_report(branch, &existing_storage);
+    storage_trie.print("Storage Trie");
+
+    storage_miner::write_results(
+        &mined,
+        &config,
+        elapsed.as_secs_f64(),
+        &storage_trie,
+        &args.output,
+    )?;
 
-                // Compile the generated contract
-                let contract_path = "contracts/WorstCaseERC20.sol";
-                info!("Compiling generated contract: {}", contract_path);
-                let compiled =
-                    compile_solidity(contract_path).expect("Failed to compile generated contract");
+    // Generate contract with mined storage keys
+    match args.codegen.codegen {
+        Codegen::Solc => storage_miner::generate_contract(branch, args.slot_write)?,
+        Codegen::Native => {
+            storage_miner::generate_bytecode(branch, args.codegen.runtime)?;
+        }
+    }
+    check_status(
+        mined.status,
+        &format!("the shorter branch is saved to {}", args.output),
+    )
+}
 
-                (compiled, storage_keys)
-            } else {
-                panic!(
-                    "For CREATE2 mining, either provide --init-code or specify --depth to auto-generate a contract"
-                );
-            };
-
-        account_miner::mine_create2_accounts(
-            deployer,
-            num_contracts,
-            args.depth,
-            args.threads,
-            &compiled.init_code,
-            &compiled.deploy_code,
-            &storage_keys,
-            &args.accounts_output,
-        );
+/// Mine CREATE2 contracts and their auxiliary accounts
+fn run_create2(args: Create2Args) -> Result<(), Error> {
+    info!("Starting mining for depth: {}", args.depth);
+    log_backend(args.threads, false);
+    search::stop_on_interrupt();
+    let budget = Arc::new(
+        args.budget
+            .to_budget()
+            .monitor(progress_monitor(&args.progress)?),
+    );
+    let checkpoint = open_checkpoint(
+        &args.resume,
+        &args.checkpoint,
+        args.overwrite_checkpoint,
+        args.seed,
+        args.depth,
+    )?;
+    let seed = checkpoint.seed();
+
+    // Load or generate init code, deploy code, and storage keys
+    let (compiled, storage_branch) = match &args.init_code {
+        // When loading external code, we don't have storage keys
+        Some(init_code_path) => (load_init_code(init_code_path)?, Vec::new()),
+        None => {
+            let config = StorageMiningConfig::builder(args.depth)
+                .num_threads(args.threads)
+                .key_mode(args.key_mode)
+                .layout(args.layout.to_layout())
+                .key_scheme(args.layout.key_scheme)
+                .full_width(args.full_width)
+                .seed(seed)
+                .budget(Arc::clone(&budget))
+                .checkpoint(Arc::clone(&checkpoint))
+                .build()?;
+            generate_contract_for_depth(&config, args.slot_write, &args.codegen)?
+        }
+    };
+
+    let mut builder = Create2Config::builder(args.deployer, args.num_contracts, args.depth)
+        .salts(args.salt_scheme())
+        .num_threads(args.threads)
+        .seed(seed)
+        .budget(budget)
+        .checkpoint(checkpoint);
+    if args.aux_keys {
+        let password = args.key_password_file.as_deref().map(read_password);
+        builder = builder.aux_keys(password.transpose()?);
+    }
+    let config = builder.build()?;
+
+    let existing_accounts = match &args.existing_accounts {
+        Some(path) => load_existing_accounts(path)?,
+        None => Vec::new(),
+    };
+
+    let result = account_miner::mine_create2_accounts(
+        &config,
+        &compiled.init_code,
+        &compiled.deploy_code,
+        &storage_branch,
+        &existing_accounts,
+    )?;
+    account_miner::write_create2_result(&result, &args.accounts_output)?;
+    check_status(
+        result.status,
+        &format!(
+            "the contracts mined so far are saved to {}",
+            args.accounts_output
+        ),
+    )
+}
 
-        // Exit after CREATE2 mining - don't continue to storage mining
-        return;
+/// Estimate the time of a storage branch, and optionally a CREATE2 run, at the hashrates
+/// measured by mining their search paths for a moment
+fn run_plan(args: PlanArgs) -> Result<(), Error> {
+    let storage = StorageMiningConfig::builder(args.depth)
+        .num_threads(args.threads)
+        .key_mode(args.key_mode)
+        .layout(args.layout.to_layout())
+        .key_scheme(args.layout.key_scheme)
+        .full_width(args.full_width)
+        .build()?;
+    info!(
+        "Calibrating hashrates on {} threads, {} per search path",
+        args.threads,
+        progress::format_duration(args.calibration.as_secs_f64())
+    );
+    let storage_plan = plan::storage_plan(
+        &storage,
+        storage_miner::calibrate(&storage, args.calibration)?,
+    );
+    storage_plan.print();
+
+    let Some(num_contracts) = args.num_contracts else {
+        return Ok(());
+    };
+    let salts = SaltScheme {
+        cluster_nibbles: args.cluster_nibbles.unwrap_or(0),
+        ..SaltScheme::default()
+    };
+    let mut create2 = Create2Config::builder([0u8; 20], num_contracts, args.depth)
+        .num_threads(args.threads)
+        .salts(salts);
+    if args.aux_keys {
+        create2 = create2.aux_keys(None);
     }
+    let create2 = create2.build()?;
+    let create2_plan = plan::create2_plan(
+        &create2,
+        account_miner::calibrate(&create2, args.calibration)?,
+    );
+    create2_plan.print();
+
+    // Without --init-code, create2 mines the contract's storage branch first
+    let both = plan::Cost::sequence(&[(storage_plan.total(), 1), (create2_plan.total(), 1)]);
+    info!("");
+    info!(
+        "create2 without --init-code (storage branch, then contracts): {} expected, {} at p99",
+        progress::format_duration(both.expected),
+        progress::format_duration(both.p99)
+    );
+    Ok(())
+}
 
-    let start_time = Instant::now();
+/// Mine auxiliary accounts around existing accounts given on the command line or in a file
+fn run_targets(args: TargetsArgs) -> Result<(), Error> {
+    info!("Starting target mining for depth: {}", args.depth);
+    log_backend(args.threads, false);
+
+    let mut targets = args.targets.clone();
+    if let Some(path) = &args.targets_file {
+        info!("Loading targets from: {path}");
+        for entry in read_account_entries(path)? {
+            let key = mpt::parse_account_key(&entry)
+                .map_err(|e| Error::InvalidInput(format!("Invalid target {entry}: {e}")))?;
+            targets.push((entry, key));
+        }
+    }
+    if targets.is_empty() {
+        return Err(Error::InvalidInput("No targets given".to_string()));
+    }
 
-    // Mine for the deep branch (storage)
-    let branch = storage_miner::mine_deep_branch(args.depth, args.threads, args.cuda);
+    search::stop_on_interrupt();
+    let budget = Arc::new(
+        args.budget
+            .to_budget()
+            .monitor(progress_monitor(&args.progress)?),
+    );
+    let checkpoint = open_checkpoint(
+        &args.resume,
+        &args.checkpoint,
+        args.overwrite_checkpoint,
+        args.seed,
+        args.depth,
+    )?;
+    let config = TargetConfig::builder(args.depth)
+        .skip_nibbles(args.skip_nibbles)
+        .num_threads(args.threads)
+        .seed(checkpoint.seed())
+        .budget(budget)
+        .checkpoint(checkpoint)
+        .build()?;
+    let result = account_miner::mine_target_accounts(&config, &targets)?;
+    account_miner::write_target_result(&result, &args.output)?;
+    check_status(
+        result.status,
+        &format!("the targets mined so far are saved to {}", args.output),
+    )
+}
 
-    let elapsed = start_time.elapsed();
+/// Fail a run whose search ended early, once its partial results are saved (as `saved` says),
+/// so scripts can tell it from a complete run
+fn check_status(status: SearchStatus, saved: &str) -> Result<(), Error> {
+    let msg = format!("Search ended early ({status}); {saved}");
+    match status {
+        SearchStatus::Complete => Ok(()),
+        SearchStatus::Interrupted => Err(Error::Interrupted(msg)),
+        SearchStatus::AttemptsExhausted | SearchStatus::TimedOut => {
+            Err(Error::BudgetExhausted(msg))
+        }
+    }
+}
 
-    // Output results
-    storage_miner::print_results(&branch, elapsed.as_secs_f64());
+/// The progress monitor of a run, writing status lines where `--status-file` asks
+fn progress_monitor(args: &ProgressArgs) -> Result<ProgressMonitor, Error> {
+    let monitor = ProgressMonitor::new().interval(args.progress_interval);
+    Ok(match args.status_file.as_deref() {
+        None => monitor,
+        Some("-") => monitor.status_to(io::stdout()),
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| Error::io(path, e))?;
+            monitor.status_to(file)
+        }
+    })
+}
 
-    // Generate contract with mined storage keys
-    storage_miner::generate_contract(&branch);
+/// Open the checkpoint file of a run: the one being resumed, or a new one at `path`
+fn open_checkpoint(
+    resume: &Option<String>,
+    path: &str,
+    overwrite: bool,
+    seed: Option<u64>,
+    depth: usize,
+) -> Result<Arc<CheckpointFile>, Error> {
+    let file = match resume {
+        Some(resume) => CheckpointFile::resume(resume, depth)?,
+        None => CheckpointFile::create(
+            path,
+            seed.unwrap_or_else(search::random_seed),
+            depth,
+            overwrite,
+        )?,
+    };
+    Ok(Arc::new(file))
 }
 
-/// Result of compiling a Solidity contract
-struct CompiledContract {
-    /// Init code (constructor + runtime) - used for CREATE2 address calculation
-    init_code: Vec<u8>,
-    /// Deploy code (runtime only) - what ends up on chain
-    deploy_code: Vec<u8>,
+/// Load storage from a JSON object mapping slots to values (hex or decimal words)
+fn load_storage(path: &str) -> Result<Vec<account_miner::StorageEntry>, Error> {
+    info!("Loading storage from: {path}");
+    let content = read_file(path)?;
+    let entries: std::collections::BTreeMap<String, String> = serde_json::from_str(&content)
+        .map_err(|e| invalid(format!("Storage must be a JSON object: {e}")))?;
+    entries
+        .iter()
+        .map(|(slot, value)| {
+            let slot = storage_layout::parse_word(slot)
+                .map_err(|e| invalid(format!("Invalid slot in storage: {e}")))?;
+            let value = storage_layout::parse_word(value)
+                .map_err(|e| invalid(format!("Invalid value in storage: {e}")))?;
+            Ok((slot, value))
+        })
+        .collect()
 }
 
-/// Compile a Solidity file and return both init code and runtime code
-fn compile_solidity(sol_path: &str) -> Result<CompiledContract, String> {
-    // Run solc to compile the contract with both --bin and --bin-runtime
-    let output = Command::new("solc")
-        .args([
-            "--optimize",
-            "--optimize-runs",
-            "200",
-            "--bin",
-            "--bin-runtime",
-            "--metadata-hash",
-            "none",
-            sol_path,
-        ])
-        .output()
-        .map_err(|e| format!("Failed to run solc: {}. Make sure solc is installed.", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Solidity compilation failed: {}", stderr));
+/// Read the entries of an account list: a JSON array of strings, or one entry per line with
+/// blank lines and `#` comments skipped (the format of keys dumped from a node)
+fn read_account_entries(path: &str) -> Result<Vec<String>, Error> {
+    let content = read_file(path)?;
+    if content.trim_start().starts_with('[') {
+        return serde_json::from_str(&content)
+            .map_err(|e| invalid(format!("Accounts must be a JSON array of strings: {e}")));
     }
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Load existing accounts from a list of addresses (20 bytes) or account-trie keys (32 bytes,
+/// i.e. already hashed), and return their account-trie keys
+fn load_existing_accounts(path: &str) -> Result<Vec<[u8; 32]>, Error> {
+    info!("Loading existing accounts from: {path}");
+    read_account_entries(path)?
+        .iter()
+        .map(|entry| {
+            mpt::parse_account_key(entry)
+                .map_err(|e| invalid(format!("Invalid existing account {entry}: {e}")))
+        })
+        .collect()
+}
+
+/// Load a CREATE2 result file
+fn load_create2_result(path: &str) -> Result<account_miner::Create2MiningResult, Error> {
+    info!("Loading CREATE2 result from: {path}");
+    let content = read_file(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| invalid(format!("Invalid CREATE2 result JSON {path}: {e}")))
+}
+
+fn read_file(path: &str) -> Result<String, Error> {
+    std::fs::read_to_string(path).map_err(|e| Error::io(path, e))
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidInput(msg)
+}
+
+/// Prove every contract of a CREATE2 result and report witness sizes
+fn run_proof(args: ProofArgs) -> Result<(), Error> {
+    let result = load_create2_result(&args.input)?;
+
+    let existing_accounts = match &args.existing_accounts {
+        Some(path) => load_existing_accounts(path)?,
+        None => Vec::new(),
+    };
+
+    let storage = match &args.storage {
+        Some(path) => Some(load_storage(path)?),
+        None => None,
+    };
+
+    let report = proof::prove_create2_result(
+        &result,
+        &existing_accounts,
+        storage.as_deref(),
+        args.max_proofs,
+    )
+    .map_err(invalid)?;
+    proof::print_report(&report);
+    proof::write_report(&report, &args.output)
+}
+
+/// Write the genesis alloc of a CREATE2 result, into a given genesis file if any
+fn run_genesis(args: GenesisArgs) -> Result<(), Error> {
+    let result = load_create2_result(&args.input)?;
+
+    let base = match &args.genesis {
+        Some(path) => {
+            info!("Adding accounts to genesis: {path}");
+            let content = read_file(path)?;
+            let base = serde_json::from_str(&content)
+                .map_err(|e| invalid(format!("Invalid genesis JSON {path}: {e}")))?;
+            Some(base)
+        }
+        None => None,
+    };
+
+    let storage = match &args.storage {
+        Some(path) => Some(load_storage(path)?),
+        None => None,
+    };
+
+    let alloc =
+        genesis::genesis_alloc(&result, args.balance, storage.as_deref()).map_err(invalid)?;
+    let num_auxiliaries = alloc.len() - result.contracts.len();
+    info!(
+        "Placing {} contracts and {num_auxiliaries} auxiliary accounts ({} wei each)",
+        result.contracts.len(),
+        args.balance
+    );
+    let genesis = genesis::genesis_json(alloc, base).map_err(invalid)?;
+    genesis::write_genesis(&genesis, &args.output)
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let lines: Vec<&str> = stdout.lines().collect();
-
-    let mut init_code: Option<Vec<u8>> = None;
-    let mut deploy_code: Option<Vec<u8>> = None;
-    let mut next_is_binary = false;
-    let mut next_is_runtime = false;
-
-    for line in lines {
-        if next_is_binary {
-            let bytecode_hex = line.trim();
-            if !bytecode_hex.is_empty() {
-                init_code = Some(
-                    hex::decode(bytecode_hex)
-                        .map_err(|e| format!("Failed to decode init code hex: {}", e))?,
+/// Write state test fillers attacking the contracts of a CREATE2 result
+fn run_state_test(args: StateTestArgs) -> Result<(), Error> {
+    let result = load_create2_result(&args.input)?;
+
+    let fillers = state_test::state_test_fillers(&result, args.num_tests, args.value, &args.fork)
+        .map_err(invalid)?;
+    state_test::write_fillers(&fillers, &args.output)
+}
+
+/// Sign the deployment and funding transactions of a CREATE2 result
+fn run_deploy_txs(args: DeployTxsArgs) -> Result<(), Error> {
+    let result = load_create2_result(&args.input)?;
+
+    let key = read_password(&args.sender_key_file)?;
+    let key = hex::decode(key.trim().trim_start_matches("0x"))
+        .map_err(|e| invalid(format!("Invalid sender key hex: {e}")))?;
+    let sender = secp256k1::SecretKey::from_slice(&key)
+        .map_err(|e| invalid(format!("Invalid sender key: {e}")))?;
+    let sender_address = keys::address_of(&sender.public_key(&secp256k1::Secp256k1::new()));
+    info!("Sender: 0x{}", hex::encode(sender_address));
+    if result.deployer.to_lowercase() != transactions::NICKS_DEPLOYER {
+        log::warn!(
+            "Deployer {} is not Nick's deployer; it must CREATE2 `calldata[32..]` with salt \
+             `calldata[..32]`",
+            result.deployer
+        );
+    }
+
+    let config = transactions::BatchConfig {
+        sender,
+        chain_id: args.chain_id,
+        nonce_start: args.nonce,
+        max_fee_per_gas: args.max_fee_per_gas,
+        max_priority_fee_per_gas: args.max_priority_fee_per_gas,
+        deploy_gas_limit: args.deploy_gas_limit,
+        funding_value: args.fund_value,
+    };
+    let batch = transactions::deployment_batch(&result, &config).map_err(invalid)?;
+    let deployments = batch
+        .iter()
+        .filter(|tx| tx.kind == transactions::TxKind::Deploy)
+        .count();
+    info!(
+        "Signed {deployments} deployments and {} funding transfers (nonces {}..{}), costing \
+         the sender at most {} wei",
+        batch.len() - deployments,
+        args.nonce,
+        args.nonce + batch.len() as u64,
+        transactions::max_cost(&batch)
+    );
+    transactions::write_transactions(&batch, args.format, &args.output)
+}
+
+/// Write the plain auxiliary keys of a CREATE2 result
+fn run_decrypt_keys(args: DecryptKeysArgs) -> Result<(), Error> {
+    let result = load_create2_result(&args.input)?;
+
+    let password = args
+        .key_password_file
+        .as_deref()
+        .map(read_password)
+        .transpose()?;
+    let keys = result
+        .auxiliary_keys(password.as_deref())
+        .map_err(|e| invalid(format!("Failed to decrypt auxiliary keys: {e}")))?;
+    let keys: std::collections::BTreeMap<String, String> = keys
+        .iter()
+        .map(|(address, key)| {
+            (
+                format!("0x{}", hex::encode(address)),
+                format!("0x{}", hex::encode(key)),
+            )
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&keys)
+        .map_err(|e| invalid(format!("Failed to serialize to JSON: {e}")))?;
+    std::fs::write(&args.output, json).map_err(|e| Error::io(&args.output, e))?;
+    info!("Decrypted {} keys to: {}", keys.len(), args.output);
+    Ok(())
+}
+
+/// Mismatches listed per file before the rest are only counted
+const MAX_LISTED_MISMATCHES: usize = 20;
+
+/// Verify result files, failing if any cannot be verified or has mismatches
+fn run_verify(args: VerifyArgs) -> Result<(), Error> {
+    let init_code = match &args.init_code {
+        Some(path) => Some(load_init_code(path)?.init_code),
+        None => None,
+    };
+    let password = args
+        .key_password_file
+        .as_deref()
+        .map(read_password)
+        .transpose()?;
+
+    let (mut mismatched, mut failed) = (0, 0);
+    let verified = verify::verify_files(&args.inputs, init_code.as_deref(), password.as_deref());
+    for (path, verification) in args.inputs.iter().zip(verified) {
+        match verification {
+            Ok(verification) if verification.passed() => {
+                info!(
+                    "OK: {path} ({} result, {} checks)",
+                    verification.kind, verification.checks
                 );
             }
-            next_is_binary = false;
-        } else if next_is_runtime {
-            let bytecode_hex = line.trim();
-            if !bytecode_hex.is_empty() {
-                deploy_code = Some(
-                    hex::decode(bytecode_hex)
-                        .map_err(|e| format!("Failed to decode runtime code hex: {}", e))?,
+            Ok(verification) => {
+                mismatched += 1;
+                log::error!(
+                    "MISMATCH: {path} ({} result, {} of {} checks failed)",
+                    verification.kind,
+                    verification.mismatches.len(),
+                    verification.checks
                 );
+                for mismatch in verification.mismatches.iter().take(MAX_LISTED_MISMATCHES) {
+                    log::error!("  {mismatch}");
+                }
+                if verification.mismatches.len() > MAX_LISTED_MISMATCHES {
+                    log::error!(
+                        "  ... and {} more",
+                        verification.mismatches.len() - MAX_LISTED_MISMATCHES
+                    );
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                log::error!("FAILED: {e}");
             }
-            next_is_runtime = false;
-        }
-
-        if line.contains("Binary:") && !line.contains("Binary of the runtime") {
-            next_is_binary = true;
-        } else if line.contains("Binary of the runtime") {
-            next_is_runtime = true;
         }
     }
 
-    Ok(CompiledContract {
-        init_code: init_code.ok_or("Could not find init code in solc output")?,
-        deploy_code: deploy_code.ok_or("Could not find runtime code in solc output")?,
-    })
+    info!(
+        "Verified {} files: {} passed, {mismatched} with mismatches, {failed} unreadable",
+        args.inputs.len(),
+        args.inputs.len() - mismatched - failed
+    );
+    if failed > 0 {
+        return Err(Error::Unverifiable(format!(
+            "{failed} of {} files could not be verified",
+            args.inputs.len()
+        )));
+    } else if mismatched > 0 {
+        return Err(Error::VerifyMismatch(format!(
+            "{mismatched} of {} files have mismatches",
+            args.inputs.len()
+        )));
+    }
+    Ok(())
 }
 
-fn parse_address(hex_str: &str) -> Result<[u8; 20], String> {
-    let hex_str = hex_str.strip_prefix("0x").unwrap_or(hex_str);
+/// Read a password file, ignoring a trailing newline
+fn read_password(path: &str) -> Result<String, Error> {
+    let password = read_file(path)?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
 
-    if hex_str.len() != 40 {
-        return Err(format!(
-            "Address must be 40 hex characters, got {}",
-            hex_str.len()
-        ));
+/// Mine a storage branch of `depth`, then generate and compile a contract seeding it
+fn generate_contract_for_depth(
+    config: &StorageMiningConfig,
+    slot_write: SlotWrite,
+    codegen: &CodegenArgs,
+) -> Result<(CompiledContract, Vec<StorageSlot>), Error> {
+    info!(
+        "No init code provided. Generating contract with depth {}...",
+        config.target_depth
+    );
+
+    // First, mine storage slots for the contract. Accounts are only mined for a full branch
+    let mined = storage_miner::mine_deep_branch(config)?;
+    check_status(
+        mined.status,
+        "the storage branch mined so far is kept in the checkpoint",
+    )?;
+    let branch = mined.branch;
+
+    if codegen.codegen == Codegen::Native {
+        let bytecode = storage_miner::generate_bytecode(&branch, codegen.runtime)?;
+        let compiled = CompiledContract {
+            init_code: bytecode.init_code,
+            deploy_code: bytecode.runtime_code,
+        };
+        return Ok((compiled, branch));
     }
 
-    let bytes = hex::decode(hex_str).map_err(|e| format!("Invalid hex: {e}"))?;
+    // Generate the contract
+    storage_miner::generate_contract(&branch, slot_write)?;
+
+    // Compile the generated contract
+    let contract_path = "contracts/WorstCaseERC20.sol";
+    info!("Compiling generated contract: {}", contract_path);
+    let compiled = compile_solidity(contract_path)?;
 
-    let mut address = [0u8; 20];
-    address.copy_from_slice(&bytes);
-    Ok(address)
+    Ok((compiled, branch))
 }
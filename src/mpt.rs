@@ -0,0 +1,482 @@
+//! # Merkle Patricia Trie Module
+//!
+//! Builds Ethereum's Merkle Patricia Tries in memory, so the shape of a mined branch can be
+//! checked structurally instead of being inferred from shared-nibble counts. A trie is built
+//! from its full set of entries (mined keys plus any existing ones) and can then report the
+//! root hash and, for any key, the nodes on its path with their types and RLP sizes.
+//!
+//! ## Key Functions
+//! - `Trie::new`: Builds a trie from raw key/value entries
+//! - `storage_trie`: Builds a secure storage trie from slots and values
+//! - `account_trie`: Builds a secure account trie from addresses (or trie keys) and accounts
+//! - `Trie::path`: Lists the nodes on the path to a key
+//! - `TrieReport::new`: Summarizes the root and the paths of selected keys
+//! - `parse_account_key`: Parses an address or 32-byte account-trie key
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::rlp;
+
+/// Root hash of an empty trie: `keccak256(rlp(""))`
+pub const EMPTY_ROOT: [u8; 32] = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// Code hash of an account without code: `keccak256("")`
+pub const EMPTY_CODE_HASH: [u8; 32] = [
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+];
+
+/// Type of a trie node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Branch,
+    Extension,
+    Leaf,
+}
+
+/// A node of an in-memory trie with its RLP encoding, computed once when the node is built
+struct Node {
+    data: NodeData,
+    rlp: Vec<u8>,
+}
+
+/// What lookups need to walk a node. Paths are stored as nibbles
+enum NodeData {
+    Leaf,
+    Extension { path: Vec<u8>, child: Box<Node> },
+    Branch { children: Box<[Option<Node>; 16]> },
+}
+
+impl Node {
+    fn leaf(path: &[u8], value: &[u8]) -> Self {
+        Node {
+            rlp: rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, true)),
+                rlp::encode_bytes(value),
+            ]),
+            data: NodeData::Leaf,
+        }
+    }
+
+    fn extension(path: Vec<u8>, child: Node) -> Self {
+        Node {
+            rlp: rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(&path, false)),
+                child.reference(),
+            ]),
+            data: NodeData::Extension {
+                path,
+                child: Box::new(child),
+            },
+        }
+    }
+
+    fn branch(children: [Option<Node>; 16], value: Option<&[u8]>) -> Self {
+        let mut items: Vec<Vec<u8>> = children
+            .iter()
+            .map(|child| match child {
+                Some(child) => child.reference(),
+                None => rlp::encode_bytes(&[]),
+            })
+            .collect();
+        items.push(rlp::encode_bytes(value.unwrap_or_default()));
+        Node {
+            rlp: rlp::encode_list(&items),
+            data: NodeData::Branch {
+                children: Box::new(children),
+            },
+        }
+    }
+
+    fn kind(&self) -> NodeKind {
+        match self.data {
+            NodeData::Leaf => NodeKind::Leaf,
+            NodeData::Extension { .. } => NodeKind::Extension,
+            NodeData::Branch { .. } => NodeKind::Branch,
+        }
+    }
+
+    /// How a parent refers to this node: inline if its encoding is shorter than 32 bytes,
+    /// otherwise by hash
+    fn reference(&self) -> Vec<u8> {
+        if self.rlp.len() < 32 {
+            self.rlp.clone()
+        } else {
+            rlp::encode_bytes(&keccak256(&self.rlp))
+        }
+    }
+}
+
+/// A node on the path to a key
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PathNode {
+    pub kind: NodeKind,
+    /// Nibble offset of the node in the key
+    pub depth: usize,
+    /// Size of the node's RLP encoding in bytes
+    pub rlp_size: usize,
+    /// Whether the node is inlined in its parent (encodings under 32 bytes) instead of hashed
+    pub embedded: bool,
+    /// RLP encoding of the node
+    #[serde(skip)]
+    pub rlp: Vec<u8>,
+}
+
+/// An in-memory Merkle Patricia Trie
+pub struct Trie {
+    root: Option<Node>,
+    num_entries: usize,
+}
+
+impl Trie {
+    /// Build a trie from `(key, value)` entries. Values are stored as given (already
+    /// RLP-encoded where the trie expects it); later duplicates replace earlier ones
+    pub fn new(entries: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>) -> Self {
+        let entries: BTreeMap<Vec<u8>, Vec<u8>> = entries
+            .into_iter()
+            .map(|(key, value)| (to_nibbles(&key), value))
+            .collect();
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = entries.into_iter().collect();
+        Trie {
+            root: (!entries.is_empty()).then(|| build(&entries, 0)),
+            num_entries: entries.len(),
+        }
+    }
+
+    pub fn num_entries(&self) -> usize {
+        self.num_entries
+    }
+
+    /// Root hash: `keccak256` of the root node's encoding (never inlined)
+    pub fn root_hash(&self) -> [u8; 32] {
+        match &self.root {
+            Some(root) => keccak256(&root.rlp),
+            None => EMPTY_ROOT,
+        }
+    }
+
+    /// Nodes visited when looking up `key`, from the root down. The last node is the key's
+    /// leaf if the key is present
+    pub fn path(&self, key: &[u8]) -> Vec<PathNode> {
+        let nibbles = to_nibbles(key);
+        let mut nodes = Vec::new();
+        let mut depth = 0;
+        let mut current = self.root.as_ref();
+
+        while let Some(node) = current {
+            nodes.push(PathNode {
+                kind: node.kind(),
+                depth,
+                rlp_size: node.rlp.len(),
+                embedded: depth > 0 && node.rlp.len() < 32,
+                rlp: node.rlp.clone(),
+            });
+            current = match &node.data {
+                NodeData::Leaf => None,
+                NodeData::Extension { path, child } => {
+                    if !nibbles[depth..].starts_with(path) {
+                        break;
+                    }
+                    depth += path.len();
+                    Some(child.as_ref())
+                }
+                NodeData::Branch { children } => {
+                    let Some(&nibble) = nibbles.get(depth) else {
+                        break;
+                    };
+                    depth += 1;
+                    children[nibble as usize].as_ref()
+                }
+            };
+        }
+
+        nodes
+    }
+
+    /// Merkle proof of `key` as returned by `eth_getProof`: the RLP encodings of the nodes on
+    /// its path, root first, leaving out nodes embedded in their parent
+    pub fn proof(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        self.path(key)
+            .into_iter()
+            .filter(|node| !node.embedded)
+            .map(|node| node.rlp)
+            .collect()
+    }
+}
+
+/// Build the (sub)trie of `entries`, sorted by nibble path, all sharing the first `depth` nibbles
+fn build(entries: &[(Vec<u8>, Vec<u8>)], depth: usize) -> Node {
+    if let [(path, value)] = entries {
+        return Node::leaf(&path[depth..], value);
+    }
+
+    // Entries are sorted, so the first and last share the prefix common to all of them
+    let first = &entries[0].0[depth..];
+    let last = &entries[entries.len() - 1].0[depth..];
+    let common = first.iter().zip(last).take_while(|(a, b)| a == b).count();
+    if common > 0 {
+        return Node::extension(first[..common].to_vec(), build(entries, depth + common));
+    }
+
+    // A key ending here is the branch's own value; sorting puts it first
+    let (value, rest) = match entries.split_first() {
+        Some(((path, value), rest)) if path.len() == depth => (Some(value.as_slice()), rest),
+        _ => (None, entries),
+    };
+    let mut children: [Option<Node>; 16] = Default::default();
+    let mut start = 0;
+    while start < rest.len() {
+        let nibble = rest[start].0[depth];
+        let end = start
+            + rest[start..]
+                .iter()
+                .take_while(|(path, _)| path[depth] == nibble)
+                .count();
+        children[nibble as usize] = Some(build(&rest[start..end], depth + 1));
+        start = end;
+    }
+    Node::branch(children, value)
+}
+
+/// Split bytes into nibbles, high nibble first
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+/// Hex-prefix encoding of a nibble path, flagging leaves and odd lengths
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        out.push(((flag + 1) << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag << 4);
+        nibbles
+    };
+    out.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    out
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    output
+}
+
+/// Build a secure storage trie: keyed by `keccak256(slot)`, values RLP-encoded without
+/// leading zeros. Zero values are not stored, as in the EVM
+pub fn storage_trie(slots: impl IntoIterator<Item = ([u8; 32], [u8; 32])>) -> Trie {
+    Trie::new(
+        slots
+            .into_iter()
+            .filter(|(_, value)| value.iter().any(|&b| b != 0))
+            .map(|(slot, value)| {
+                (
+                    keccak256(&slot).to_vec(),
+                    rlp::encode_bytes(rlp::trim_leading_zeros(&value)),
+                )
+            }),
+    )
+}
+
+/// State of an account as stored in the account trie
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: u128,
+    pub storage_root: [u8; 32],
+    pub code_hash: [u8; 32],
+}
+
+impl Account {
+    /// An externally owned account holding `balance` wei
+    pub fn funded(balance: u128) -> Self {
+        Account {
+            nonce: 0,
+            balance,
+            storage_root: EMPTY_ROOT,
+            code_hash: EMPTY_CODE_HASH,
+        }
+    }
+
+    /// A contract with runtime `code` and storage trie root `storage_root`. Contracts start
+    /// at nonce 1 (EIP-161)
+    pub fn contract(code: &[u8], storage_root: [u8; 32]) -> Self {
+        Account {
+            nonce: 1,
+            balance: 0,
+            storage_root,
+            code_hash: keccak256(code),
+        }
+    }
+
+    /// `rlp([nonce, balance, storageRoot, codeHash])`
+    pub fn encode(&self) -> Vec<u8> {
+        rlp::encode_list(&[
+            rlp::encode_uint(self.nonce as u128),
+            rlp::encode_uint(self.balance),
+            rlp::encode_bytes(&self.storage_root),
+            rlp::encode_bytes(&self.code_hash),
+        ])
+    }
+}
+
+/// Build a secure account trie from accounts keyed by their account-trie key
+/// (`keccak256(address)`, see `account_key`)
+pub fn account_trie(accounts: impl IntoIterator<Item = ([u8; 32], Account)>) -> Trie {
+    Trie::new(
+        accounts
+            .into_iter()
+            .map(|(key, account)| (key.to_vec(), account.encode())),
+    )
+}
+
+/// Account-trie key of an address: `keccak256(address)`
+pub fn account_key(address: &[u8; 20]) -> [u8; 32] {
+    keccak256(address)
+}
+
+/// An account-trie key: `keccak256` of a 20-byte address, or a 32-byte key as is
+pub fn parse_account_key(s: &str) -> Result<[u8; 32], String> {
+    let bytes =
+        hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(|e| format!("Invalid hex: {e}"))?;
+    match bytes.len() {
+        20 => Ok(account_key(&bytes.try_into().unwrap())),
+        32 => Ok(bytes.try_into().unwrap()),
+        n => Err(format!(
+            "Accounts must be 20-byte addresses or 32-byte keys, got {n} bytes"
+        )),
+    }
+}
+
+/// Path of one key through a trie
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyPath {
+    /// Trie key (hashed slot or address)
+    pub trie_key: String,
+    /// Nibble offset of the last node on the path (the key's leaf)
+    pub leaf_depth: usize,
+    pub nodes: Vec<PathNode>,
+}
+
+/// Root and per-key paths of a built trie
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrieReport {
+    pub root: String,
+    pub num_entries: usize,
+    pub paths: Vec<KeyPath>,
+}
+
+impl TrieReport {
+    /// Report the root of `trie` and the paths of `keys` (trie keys)
+    pub fn new(trie: &Trie, keys: &[[u8; 32]]) -> Self {
+        TrieReport {
+            root: format!("0x{}", hex::encode(trie.root_hash())),
+            num_entries: trie.num_entries(),
+            paths: keys
+                .iter()
+                .map(|key| {
+                    let nodes = trie.path(key);
+                    KeyPath {
+                        trie_key: format!("0x{}", hex::encode(key)),
+                        leaf_depth: nodes.last().map_or(0, |node| node.depth),
+                        nodes,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Log the root and a one-line summary per path
+    pub fn print(&self, title: &str) {
+        info!("");
+        info!("═══ {title} ═══");
+        info!("Root: {}", self.root);
+        info!("Entries: {}", self.num_entries);
+        for path in &self.paths {
+            let kinds: Vec<String> = path
+                .nodes
+                .iter()
+                .map(|node| {
+                    let kind = match node.kind {
+                        NodeKind::Branch => "B",
+                        NodeKind::Extension => "E",
+                        NodeKind::Leaf => "L",
+                    };
+                    format!("{kind}{}", node.rlp_size)
+                })
+                .collect();
+            let total: usize = path.nodes.iter().map(|node| node.rlp_size).sum();
+            info!(
+                "  {}...: leaf at depth {}, {} nodes ({total} bytes): {}",
+                &path.trie_key[..10],
+                path.leaf_depth,
+                path.nodes.len(),
+                kinds.join(" ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_hash_vectors() {
+        assert_eq!(Trie::new(Vec::new()).root_hash(), EMPTY_ROOT);
+        assert_eq!(keccak256(&[]), EMPTY_CODE_HASH);
+
+        // Classic example from the Ethereum wiki (unhashed keys, values in branches)
+        let trie = Trie::new(
+            [
+                ("do", "verb"),
+                ("dog", "puppy"),
+                ("doge", "coin"),
+                ("horse", "stallion"),
+            ]
+            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec())),
+        );
+        assert_eq!(
+            hex::encode(trie.root_hash()),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+    }
+
+    #[test]
+    fn test_path_through_shared_prefix() {
+        // Two slots whose trie keys share no nibble, plus one sharing 3 nibbles with a key
+        let mut keys = [[0x12; 32], [0xf0; 32], [0x12; 32]];
+        keys[2][1] = 0x1f;
+        let trie = Trie::new(keys.iter().map(|k| (k.to_vec(), vec![0x01])));
+        assert_eq!(trie.num_entries(), 3);
+
+        let path = trie.path(&keys[2]);
+        let kinds: Vec<NodeKind> = path.iter().map(|node| node.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                NodeKind::Branch,
+                NodeKind::Extension,
+                NodeKind::Branch,
+                NodeKind::Leaf
+            ]
+        );
+        // root branch, extension "2 1" after nibble 1, branch at nibble 3, leaf below it
+        let depths: Vec<usize> = path.iter().map(|node| node.depth).collect();
+        assert_eq!(depths, vec![0, 1, 3, 4]);
+
+        // A missing key stops where the lookup diverges
+        assert_eq!(trie.path(&[0x55; 32]).len(), 1);
+    }
+}
@@ -0,0 +1,306 @@
+//! # Plan Module
+//!
+//! Cost estimates of mining runs before anything is mined. A plan combines the expected work
+//! of each search (see `progress`) with hashrates measured by briefly running the miners' own
+//! search paths, giving the expected and 99th-percentile time of every level, of each search
+//! and of the whole run.
+//!
+//! A run's searches follow one another, so its expected time is the sum of theirs. Its 99th
+//! percentile is approximated as the longer of two estimates. The first treats the total as
+//! normally distributed, which fits runs of many similar searches. The second takes the
+//! slowest search's own percentile plus the other searches' expected times, which fits runs
+//! where one search dominates.
+//!
+//! ## Key Functions
+//! - `Cost`: Expected time, variance and 99th percentile of a search or a sequence of them
+//! - `storage_plan`: Costs of a storage branch's levels and searches
+//! - `create2_plan`: Costs of a CREATE2 run's auxiliaries, salts and contracts
+//! - `RunPlan::print`: Logs a plan as a table
+
+use log::info;
+
+use crate::account_miner::Create2Config;
+use crate::progress::{
+    attempts_for_chance, attempts_variance, expected_attempts, format_count, format_duration,
+};
+use crate::storage_miner::StorageMiningConfig;
+
+/// Chance the percentile columns are for
+const PERCENTILE: f64 = 0.99;
+
+/// The standard normal distribution's 99th percentile
+const NORMAL_PERCENTILE: f64 = 2.326;
+
+/// What a search, or several in a row, costs at a given hashrate. Times are in seconds
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cost {
+    /// Expected candidates
+    pub attempts: f64,
+    /// Expected time
+    pub expected: f64,
+    /// Variance of the time
+    pub variance: f64,
+    /// Time within which the work is done with 99% chance
+    pub p99: f64,
+}
+
+impl Cost {
+    /// The cost of one search checking every candidate against all of `levels` (the nibbles
+    /// each needs) at `hashrate` candidates per second
+    pub fn search(levels: &[usize], hashrate: f64) -> Self {
+        let attempts = expected_attempts(levels);
+        Cost {
+            attempts,
+            expected: attempts / hashrate,
+            variance: attempts_variance(levels) / (hashrate * hashrate),
+            p99: attempts_for_chance(levels, PERCENTILE) / hashrate,
+        }
+    }
+
+    /// The cost of searches run one after another, each given with how many times it runs
+    pub fn sequence(searches: &[(Cost, usize)]) -> Self {
+        let total = |part: fn(&Cost) -> f64| -> f64 {
+            searches
+                .iter()
+                .map(|(cost, count)| part(cost) * *count as f64)
+                .sum()
+        };
+        let (attempts, expected, variance) = (
+            total(|cost| cost.attempts),
+            total(|cost| cost.expected),
+            total(|cost| cost.variance),
+        );
+        let normal = expected + NORMAL_PERCENTILE * variance.sqrt();
+        let dominant = searches
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(cost, _)| cost.p99 + expected - cost.expected)
+            .fold(0.0, f64::max);
+        Cost {
+            attempts,
+            expected,
+            variance,
+            p99: normal.max(dominant),
+        }
+    }
+}
+
+/// One row of a plan's table
+#[derive(Clone, Debug)]
+pub struct PlanRow {
+    pub label: String,
+    pub cost: Cost,
+}
+
+impl PlanRow {
+    fn new(label: String, cost: Cost) -> Self {
+        PlanRow { label, cost }
+    }
+}
+
+/// Estimated costs of one mining run
+#[derive(Clone, Debug)]
+pub struct RunPlan {
+    pub title: String,
+    /// Measured hashrates in candidates per second, by search path
+    pub hashrates: Vec<(String, f64)>,
+    /// Each level on its own, as if it were mined alone
+    pub levels: Vec<PlanRow>,
+    /// Whole searches, with their levels mined together, ending with the whole run
+    pub totals: Vec<PlanRow>,
+}
+
+impl RunPlan {
+    /// The cost of the whole run
+    pub fn total(&self) -> Cost {
+        self.totals.last().map(|row| row.cost).unwrap_or_default()
+    }
+
+    /// Log the plan as a table
+    pub fn print(&self) {
+        info!("");
+        info!("═══ {} ═══", self.title);
+        for (path, hashrate) in &self.hashrates {
+            info!("Hashrate ({path}): {} H/s", format_count(*hashrate));
+        }
+        info!(
+            "{:<44} {:>18} {:>14} {:>14}",
+            "", "Expected attempts", "Expected time", "p99 time"
+        );
+        for (index, row) in self.levels.iter().chain(&self.totals).enumerate() {
+            if index == self.levels.len() {
+                info!("{}", "─".repeat(93));
+            }
+            info!(
+                "{:<44} {:>18} {:>14} {:>14}",
+                row.label,
+                format_count(row.cost.attempts),
+                format_duration(row.cost.expected),
+                format_duration(row.cost.p99)
+            );
+        }
+    }
+}
+
+/// The plan of mining `config`'s storage branch at `hashrate` candidates per second
+pub fn storage_plan(config: &StorageMiningConfig, hashrate: f64) -> RunPlan {
+    let depth = config.target_depth;
+    let searches = config.searches();
+    let mut levels: Vec<PlanRow> = (1..depth)
+        .map(|nibbles| {
+            PlanRow::new(
+                format!("Level {nibbles} ({nibbles} nibbles)"),
+                Cost::search(&[nibbles], hashrate),
+            )
+        })
+        .collect();
+    levels.push(PlanRow::new(
+        format!("Level {depth} (anchor)"),
+        Cost::default(),
+    ));
+
+    let branch = Cost::search(&searches[0], hashrate);
+    let mut totals = vec![PlanRow::new(
+        format!("Branch (levels 1-{depth} together)"),
+        branch,
+    )];
+    let siblings: Vec<(Cost, usize)> = searches[1..]
+        .iter()
+        .map(|levels| (Cost::search(levels, hashrate), 1))
+        .collect();
+    if config.full_width {
+        levels.extend(searches[1..].iter().zip(&siblings).enumerate().map(
+            |(level, (keys, (cost, _)))| {
+                PlanRow::new(
+                    format!(
+                        "Siblings of level {} ({} x {} nibbles)",
+                        level + 1,
+                        keys.len(),
+                        level + 1
+                    ),
+                    *cost,
+                )
+            },
+        ));
+        totals.push(PlanRow::new(
+            "All siblings".to_string(),
+            Cost::sequence(&siblings),
+        ));
+    }
+    totals.push(PlanRow::new(
+        "Storage run".to_string(),
+        Cost::sequence(&[&[(branch, 1)], siblings.as_slice()].concat()),
+    ));
+
+    let width = if config.full_width {
+        ", full width"
+    } else {
+        ""
+    };
+    RunPlan {
+        title: format!("Storage Branch Plan (depth {depth}{width})"),
+        hashrates: vec![("key search".to_string(), hashrate)],
+        levels,
+        totals,
+    }
+}
+
+/// The plan of mining `config`'s contracts and auxiliaries at the hashrates of auxiliaries
+/// and salts (only needed with salt mining), in candidates per second
+pub fn create2_plan(
+    config: &Create2Config,
+    (auxiliary_rate, salt_rate): (f64, Option<f64>),
+) -> RunPlan {
+    let depth = config.target_depth;
+    let num_contracts = config.num_contracts;
+    let mut levels: Vec<PlanRow> = (1..=depth)
+        .map(|nibbles| {
+            PlanRow::new(
+                format!("Auxiliary {nibbles} ({nibbles} nibbles)"),
+                Cost::search(&[nibbles], auxiliary_rate),
+            )
+        })
+        .collect();
+    let mut hashrates = vec![("auxiliaries".to_string(), auxiliary_rate)];
+
+    let auxiliaries = Cost::search(&(1..=depth).collect::<Vec<_>>(), auxiliary_rate);
+    let mut totals = vec![PlanRow::new(
+        format!("Auxiliaries of one contract (1-{depth} together)"),
+        auxiliaries,
+    )];
+    let mut run = vec![(auxiliaries, num_contracts)];
+    if let Some(salt_rate) = salt_rate.filter(|_| config.salts.cluster_nibbles > 0) {
+        let nibbles = config.salts.cluster_nibbles;
+        let salt = Cost::search(&[nibbles], salt_rate);
+        hashrates.push(("salts".to_string(), salt_rate));
+        levels.push(PlanRow::new(format!("Salt ({nibbles} nibbles)"), salt));
+        totals.push(PlanRow::new(
+            "One clustered contract".to_string(),
+            Cost::sequence(&[(salt, 1), (auxiliaries, 1)]),
+        ));
+        // Every mined salt is a search of its own
+        run.push((salt, config.searches().len() - num_contracts));
+    }
+    totals.push(PlanRow::new(
+        format!("All {num_contracts} contracts"),
+        Cost::sequence(&run),
+    ));
+
+    RunPlan {
+        title: format!("CREATE2 Plan ({num_contracts} contracts x {depth} auxiliaries)"),
+        hashrates,
+        levels,
+        totals,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_miner::SaltScheme;
+    use crate::progress::level_attempts;
+
+    #[test]
+    fn test_sequence_percentiles() {
+        let one = Cost::search(&[4], 1.0);
+        assert!((one.expected - level_attempts(4)).abs() < 1e-6 * one.expected);
+        // Many similar searches even out: the total's p99 is close to its expected time
+        let many = Cost::sequence(&[(one, 10_000)]);
+        assert!((many.expected - 10_000.0 * one.expected).abs() < 1e-6 * many.expected);
+        assert!(many.p99 < 1.05 * many.expected);
+        // One dominant search keeps its own long tail
+        let tiny = Cost::search(&[1], 1.0);
+        let dominated = Cost::sequence(&[(one, 1), (tiny, 3)]);
+        assert!(dominated.p99 >= one.p99 + 3.0 * tiny.expected - 1e-6);
+    }
+
+    #[test]
+    fn test_plans_cover_every_level_and_search() {
+        let storage = StorageMiningConfig::builder(4)
+            .full_width(true)
+            .build()
+            .unwrap();
+        let plan = storage_plan(&storage, 1e6);
+        // Three mined levels and the anchor, then the siblings of all four levels
+        assert_eq!(plan.levels.len(), 4 + 4);
+        assert_eq!(plan.totals.len(), 3);
+        let siblings = plan.totals[1].cost.expected;
+        assert!((plan.total().expected - plan.totals[0].cost.expected - siblings).abs() < 1e-9);
+
+        let salts = SaltScheme {
+            cluster_nibbles: 3,
+            ..SaltScheme::default()
+        };
+        let create2 = Create2Config::builder([0u8; 20], 5, 4)
+            .salts(salts)
+            .build()
+            .unwrap();
+        let plan = create2_plan(&create2, (1e6, Some(5e5)));
+        assert_eq!(plan.levels.len(), 4 + 1);
+        // The first contract sets the cluster's target, so only four salts are mined
+        let auxiliaries = plan.totals[0].cost.expected;
+        let salt = Cost::search(&[3], 5e5).expected;
+        let total = plan.total().expected;
+        assert!((total - 5.0 * auxiliaries - 4.0 * salt).abs() < 1e-9 * total);
+    }
+}
@@ -0,0 +1,413 @@
+//! # Progress Module
+//!
+//! Live progress of a mining run. Every search reports to the run's `ProgressMonitor` as its
+//! workers go, which logs the aggregate hashrate with the expected work of the levels still
+//! open and optionally appends a JSON status line per report for dashboards to tail.
+//!
+//! A level needing `n` shared nibbles takes 16^n candidates on average. The levels of a search
+//! are checked against every candidate at once, so a search lasts until its slowest level is
+//! found; its expected length is that of the longest of several geometric waits, modelled as
+//! exponential ones. Misses do not bring a level closer: the expected work left is always the
+//! full 16^n, and an ETA is that work over the current hashrate.
+//!
+//! ## Key Functions
+//! - `level_attempts`: Expected candidates for one level
+//! - `expected_attempts`: Expected candidates for all levels of a search together
+//! - `attempts_for_chance`: Candidates that find all levels with a given chance (percentiles)
+//! - `ProgressMonitor`: Collects the workers' attempts and reports hashrate and ETAs
+//! - `format_count` / `format_duration`: Short human-readable counts and durations
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often a monitor reports unless told otherwise
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Steps per unit of log-attempts when integrating a search's expected length
+const STEPS_PER_LOG_UNIT: f64 = 100.0;
+
+/// Expected candidates to find one level needing `nibbles` shared nibbles: 16^nibbles
+pub fn level_attempts(nibbles: usize) -> f64 {
+    16f64.powi(nibbles as i32)
+}
+
+/// Chance that `attempts` candidates find every level of a search (given by the nibbles each
+/// needs), with each candidate checked against all of them
+pub fn chance_within(levels: &[usize], attempts: f64) -> f64 {
+    levels
+        .iter()
+        .map(|&nibbles| -(-attempts / level_attempts(nibbles)).exp_m1())
+        .product()
+}
+
+/// Expected candidates until every level of a search is found, with each candidate checked
+/// against all of them. About the hardest level's 16^n once the others are much easier
+pub fn expected_attempts(levels: &[usize]) -> f64 {
+    attempts_moment(levels, 1)
+}
+
+/// Variance of the candidates until every level of a search is found
+pub fn attempts_variance(levels: &[usize]) -> f64 {
+    let mean = attempts_moment(levels, 1);
+    (attempts_moment(levels, 2) - mean * mean).max(0.0)
+}
+
+/// Candidates that find every level of a search with the given chance, e.g. 0.99 for the
+/// 99th percentile
+pub fn attempts_for_chance(levels: &[usize], chance: f64) -> f64 {
+    let (Some(&easiest), Some(&hardest)) = (levels.iter().min(), levels.iter().max()) else {
+        return 0.0;
+    };
+    // Bisect over ln(attempts), between bounds well outside any useful chance
+    let (mut low, mut high) = (
+        (level_attempts(easiest) * 1e-9).ln(),
+        (level_attempts(hardest) * 1e3).ln(),
+    );
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if chance_within(levels, middle.exp()) < chance {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    high.exp()
+}
+
+/// The `k`-th moment of the candidates until every level is found:
+/// E[A^k] = ∫ k a^(k-1) P(not all found after a) da, integrated over ln(a)
+fn attempts_moment(levels: &[usize], k: i32) -> f64 {
+    let (Some(&easiest), Some(&hardest)) = (levels.iter().min(), levels.iter().max()) else {
+        return 0.0;
+    };
+    // Below `low` hardly any level can have been found, so that stretch contributes its full
+    // e^(k low)
+    let low = (level_attempts(easiest) * 1e-6).ln();
+    let high = (level_attempts(hardest) * 50.0).ln();
+    let steps = (((high - low) * STEPS_PER_LOG_UNIT).ceil() as usize).div_ceil(2) * 2;
+    let step = (high - low) / steps as f64;
+    let integrand = |u: f64| {
+        let attempts = u.exp();
+        (1.0 - chance_within(levels, attempts)) * k as f64 * attempts.powi(k)
+    };
+    // Simpson's rule
+    let inner: f64 = (1..steps)
+        .map(|i| {
+            let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+            weight * integrand(low + i as f64 * step)
+        })
+        .sum();
+    (k as f64 * low).exp() + step / 3.0 * (integrand(low) + inner + integrand(high))
+}
+
+/// A count with a metric suffix, e.g. `268.44M`
+pub fn format_count(count: f64) -> String {
+    const UNITS: [(f64, &str); 5] = [(1e15, "P"), (1e12, "T"), (1e9, "G"), (1e6, "M"), (1e3, "k")];
+    match UNITS.iter().find(|(scale, _)| count >= *scale) {
+        Some((scale, unit)) if count < 1e18 => format!("{:.2}{unit}", count / scale),
+        Some(_) => format!("{count:.2e}"),
+        None => format!("{count:.0}"),
+    }
+}
+
+/// A duration in its two largest units, e.g. `3h 12m` or `45s`
+pub fn format_duration(seconds: f64) -> String {
+    const UNITS: [(f64, &str); 4] = [(86400.0, "d"), (3600.0, "h"), (60.0, "m"), (1.0, "s")];
+    if !seconds.is_finite() || seconds >= 1e4 * 365.0 * 86400.0 {
+        return "centuries".to_string();
+    }
+    let Some(first) = UNITS.iter().position(|(scale, _)| seconds >= *scale) else {
+        return format!("{seconds:.1}s");
+    };
+    let (scale, unit) = UNITS[first];
+    let whole = (seconds / scale).floor();
+    match UNITS.get(first + 1) {
+        Some((next_scale, next_unit)) => {
+            let rest = ((seconds - whole * scale) / next_scale).floor();
+            format!("{whole}{unit} {rest}{next_unit}")
+        }
+        None => format!("{whole}{unit}"),
+    }
+}
+
+/// One progress report, as written to the status file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressStatus {
+    /// Unix time of the report
+    pub timestamp: u64,
+    /// Seconds since the run started
+    pub elapsed: f64,
+    /// Candidates tried by the run so far
+    pub attempts: u64,
+    /// Candidates per second over the last report interval, all threads together
+    pub hashrate: f64,
+    /// The running search, counting from 1, out of the run's searches
+    pub search: usize,
+    pub searches: usize,
+    /// Levels of the running search found so far, and still open
+    pub levels_found: usize,
+    pub levels_pending: usize,
+    /// Nibbles the easiest open level needs, and the candidates it takes on average
+    pub level_nibbles: Option<usize>,
+    pub level_expected_attempts: Option<f64>,
+    /// Expected seconds until the easiest open level, the running search and the run end
+    pub level_eta: Option<f64>,
+    pub search_eta: Option<f64>,
+    pub run_eta: Option<f64>,
+}
+
+impl fmt::Display for ProgressStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let eta = |seconds: Option<f64>| seconds.map_or("unknown".to_string(), format_duration);
+        write!(
+            f,
+            "{} H/s, {} tried in {}",
+            format_count(self.hashrate),
+            format_count(self.attempts as f64),
+            format_duration(self.elapsed)
+        )?;
+        if let (Some(nibbles), Some(expected)) = (self.level_nibbles, self.level_expected_attempts)
+        {
+            write!(
+                f,
+                " | next level: {nibbles} nibbles, {} expected, ETA {}",
+                format_count(expected),
+                eta(self.level_eta)
+            )?;
+        }
+        write!(
+            f,
+            " | search {}/{} ({}/{} levels), ETA {} | run ETA {}",
+            self.search,
+            self.searches,
+            self.levels_found,
+            self.levels_found + self.levels_pending,
+            eta(self.search_eta),
+            eta(self.run_eta)
+        )
+    }
+}
+
+/// Collects the attempts of every search of a run and reports hashrate and ETAs every
+/// interval: as a log line, and as a JSON line to the status writer if one is set. Miners
+/// announce the run's searches with `plan` and each one as it starts with `next_search`, so
+/// the run's ETA covers the searches still to come
+pub struct ProgressMonitor {
+    interval: Duration,
+    status: Option<Mutex<Box<dyn Write + Send>>>,
+    start: Instant,
+    state: Mutex<RunState>,
+}
+
+/// What a monitor knows about the run so far
+struct RunState {
+    /// Expected candidates of each search still to come
+    ahead: VecDeque<f64>,
+    /// Searches started so far, and in the plan
+    search: usize,
+    searches: usize,
+    /// Candidates tried by the searches that ended
+    done: u64,
+    /// When the last report was made, with the run's attempts then
+    last: (Instant, u64),
+}
+
+impl fmt::Debug for ProgressMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressMonitor")
+            .field("interval", &self.interval)
+            .field("status", &self.status.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ProgressMonitor {
+    fn default() -> Self {
+        ProgressMonitor::new()
+    }
+}
+
+impl ProgressMonitor {
+    /// A monitor logging every `DEFAULT_INTERVAL`, whose clock starts now
+    pub fn new() -> Self {
+        let start = Instant::now();
+        ProgressMonitor {
+            interval: DEFAULT_INTERVAL,
+            status: None,
+            start,
+            state: Mutex::new(RunState {
+                ahead: VecDeque::new(),
+                search: 0,
+                searches: 0,
+                done: 0,
+                last: (start, 0),
+            }),
+        }
+    }
+
+    /// Time between reports
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Also write each report to `writer` as one line of JSON
+    pub fn status_to(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.status = Some(Mutex::new(Box::new(writer)));
+        self
+    }
+
+    /// Announce the run's searches, each given by the nibbles its levels need
+    pub fn plan(&self, searches: &[Vec<usize>]) {
+        let mut state = self.state.lock().unwrap();
+        state.ahead = searches
+            .iter()
+            .map(|levels| expected_attempts(levels))
+            .collect();
+        state.search = 0;
+        state.searches = searches.len();
+    }
+
+    /// Announce that the next search of the plan starts (or is skipped, when resumed)
+    pub fn next_search(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ahead.pop_front();
+        state.search += 1;
+    }
+
+    /// Take the running search's candidates tried so far and the nibbles its open levels
+    /// need, and report if an interval has passed since the last report
+    pub(crate) fn observe(&self, tried: u64, found: usize, pending: &[usize]) {
+        let now = Instant::now();
+        let status = {
+            let mut state = self.state.lock().unwrap();
+            let (last_time, last_attempts) = state.last;
+            let interval = now.duration_since(last_time);
+            if interval < self.interval {
+                return;
+            }
+            let attempts = state.done + tried;
+            let hashrate =
+                attempts.saturating_sub(last_attempts) as f64 / interval.as_secs_f64().max(1e-9);
+            state.last = (now, attempts);
+
+            let eta = |work: f64| (hashrate > 0.0).then(|| work / hashrate);
+            let level = pending.iter().min().copied();
+            let search_work = expected_attempts(pending);
+            ProgressStatus {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs()),
+                elapsed: now.duration_since(self.start).as_secs_f64(),
+                attempts,
+                hashrate,
+                search: state.search,
+                searches: state.searches,
+                levels_found: found,
+                levels_pending: pending.len(),
+                level_nibbles: level,
+                level_expected_attempts: level.map(level_attempts),
+                level_eta: level.and_then(|nibbles| eta(level_attempts(nibbles))),
+                search_eta: eta(search_work),
+                run_eta: eta(search_work + state.ahead.iter().sum::<f64>()),
+            }
+        };
+        self.report(&status);
+    }
+
+    /// Count a search's candidates once its workers are done
+    pub(crate) fn finish_search(&self, tried: u64) {
+        self.state.lock().unwrap().done += tried;
+    }
+
+    fn report(&self, status: &ProgressStatus) {
+        info!("Progress: {status}");
+        if let Some(writer) = &self.status {
+            let mut writer = writer.lock().unwrap();
+            let written = serde_json::to_string(status)
+                .map_err(|e| e.to_string())
+                .and_then(|line| {
+                    writeln!(writer, "{line}")
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(e) = written {
+                log::warn!("Failed to write progress status: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_expected_attempts() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-3 * b;
+        assert_eq!(expected_attempts(&[]), 0.0);
+        assert!(close(expected_attempts(&[5]), level_attempts(5)));
+        // 15 equally hard levels take the harmonic number H(15) times one of them
+        let harmonic: f64 = (1..=15).map(|k| 1.0 / k as f64).sum();
+        assert!(close(
+            expected_attempts(&[3; 15]),
+            harmonic * level_attempts(3)
+        ));
+        // Easier levels barely add to the deepest one
+        let branch = expected_attempts(&[1, 2, 3, 4, 5, 6]);
+        assert!(branch > level_attempts(6) && branch < 1.1 * level_attempts(6));
+        assert!(chance_within(&[4], level_attempts(4)) > 0.63);
+        // One level's wait is exponential: variance 16^2n, 99th percentile ln(100) 16^n
+        assert!(close(attempts_variance(&[4]), level_attempts(8)));
+        assert!(close(
+            attempts_for_chance(&[4], 0.99),
+            100f64.ln() * level_attempts(4)
+        ));
+        let p99 = attempts_for_chance(&[1, 2, 3], 0.99);
+        assert!((chance_within(&[1, 2, 3], p99) - 0.99).abs() < 1e-9);
+
+        assert_eq!(format_count(268_435_456.0), "268.44M");
+        assert_eq!(format_duration(11_530.0), "3h 12m");
+        assert_eq!(format_duration(45.0), "45s");
+    }
+
+    #[test]
+    fn test_monitor_writes_status_lines() {
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Shared::default();
+        let monitor = ProgressMonitor::new()
+            .interval(Duration::ZERO)
+            .status_to(output.clone());
+        monitor.plan(&[vec![1, 2], vec![3]]);
+        monitor.next_search();
+        monitor.finish_search(0);
+        monitor.observe(1000, 1, &[2]);
+
+        let lines = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let status: ProgressStatus = serde_json::from_str(lines.trim()).unwrap();
+        assert_eq!((status.search, status.searches), (1, 2));
+        assert_eq!((status.levels_found, status.levels_pending), (1, 1));
+        assert_eq!(status.attempts, 1000);
+        assert_eq!(status.level_nibbles, Some(2));
+        assert_eq!(status.level_expected_attempts, Some(256.0));
+        // The run still has the second search's 16^3 candidates ahead
+        let hashrate = status.hashrate;
+        assert!(status.run_eta.unwrap() * hashrate > level_attempts(3));
+    }
+}
@@ -0,0 +1,365 @@
+//! # Proof Module
+//!
+//! Generates `eth_getProof`-style account and storage proofs for mined CREATE2 contracts and
+//! summarizes the witness size of attacking each one. For stateless clients and proof-size
+//! benchmarks the byte size of these proofs matters more than the nibble depth alone.
+//!
+//! The state is rebuilt from a `Create2MiningResult`: every contract and every auxiliary
+//! account, plus optional existing accounts. A contract holds what its constructor writes (the
+//! mined slots set to 1, plus the mint of a generated ERC20 constructor), or an explicitly given
+//! storage for init code whose constructor writes more. An access is the contract's account
+//! proof plus the storage proof of its deepest mined slot.
+//!
+//! ## Key Functions
+//! - `prove_create2_result`: Builds the state and proves every contract access
+//! - `write_report`: Saves a `ProofReport` JSON file
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::account_miner::{self, Create2MiningResult, StorageEntry};
+use crate::error::Error;
+use crate::mpt::{self, Account};
+use crate::storage_miner::calculate_trie_key;
+
+/// Proof of one account and some of its storage, in `eth_getProof` response format
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EthProof {
+    pub address: String,
+    pub account_proof: Vec<String>,
+    pub balance: String,
+    pub code_hash: String,
+    pub nonce: String,
+    pub storage_hash: String,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// Proof of one storage slot, in `eth_getProof` response format
+#[derive(Serialize, Deserialize)]
+pub struct StorageProof {
+    pub key: String,
+    pub value: String,
+    pub proof: Vec<String>,
+}
+
+/// Distribution of a size over all proven accesses
+#[derive(Serialize, Deserialize)]
+pub struct SizeStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl SizeStats {
+    fn from_sizes(sizes: &[usize]) -> Self {
+        SizeStats {
+            min: sizes.iter().copied().min().unwrap_or(0),
+            max: sizes.iter().copied().max().unwrap_or(0),
+            mean: if sizes.is_empty() {
+                0.0
+            } else {
+                sizes.iter().sum::<usize>() as f64 / sizes.len() as f64
+            },
+        }
+    }
+}
+
+/// Witness sizes per access (account proof + storage proof of the deepest slot)
+#[derive(Serialize, Deserialize)]
+pub struct WitnessSummary {
+    pub accesses: usize,
+    pub account_proof_nodes: SizeStats,
+    pub account_proof_bytes: SizeStats,
+    pub storage_proof_nodes: SizeStats,
+    pub storage_proof_bytes: SizeStats,
+    /// Account plus storage proof bytes
+    pub total_bytes: SizeStats,
+    /// Runtime code size, which a stateless witness carries on top of the proofs
+    pub code_bytes: usize,
+}
+
+/// Proofs and witness summary for a mined CREATE2 result
+#[derive(Serialize, Deserialize)]
+pub struct ProofReport {
+    pub state_root: String,
+    pub summary: WitnessSummary,
+    /// Proofs of the first `--max-proofs` contracts
+    pub proofs: Vec<EthProof>,
+}
+
+/// Rebuild the state of `result` next to `existing` account-trie keys and prove each contract's
+/// account and deepest storage slot. Each contract holds `storage` if given, otherwise what the
+/// recorded constructor writes. Full proofs are kept for the first `max_proofs` contracts
+pub fn prove_create2_result(
+    result: &Create2MiningResult,
+    existing: &[[u8; 32]],
+    storage: Option<&[StorageEntry]>,
+    max_proofs: usize,
+) -> Result<ProofReport, String> {
+    let deploy_code = result.deploy_code()?;
+    let storage_slots = result.contract_storage_slots()?;
+    let mined_accounts = result.mined_accounts()?;
+
+    let explicit = storage.is_some();
+    let storage = match storage {
+        Some(storage) => storage.to_vec(),
+        None => result.contract_storage()?,
+    };
+    let storage_trie = account_miner::contract_storage_trie(&storage);
+    let storage_root = storage_trie.root_hash();
+    let state =
+        account_miner::build_account_trie(&mined_accounts, &deploy_code, storage_root, existing);
+    let account = Account::contract(&deploy_code, storage_root);
+
+    // Every contract holds the same storage, so the deepest slot's proof is shared. Without
+    // recorded slots, the given storage's slot with the largest proof stands in for it
+    let slot_proof = |slot: &[u8; 32]| storage_trie.proof(&calculate_trie_key(slot));
+    let deepest_slot = match storage_slots.last() {
+        Some(slot) => Some(*slot),
+        None if explicit => storage
+            .iter()
+            .map(|(slot, _)| *slot)
+            .max_by_key(|slot| proof_size(&slot_proof(slot))),
+        None => None,
+    }
+    .ok_or_else(|| {
+        "Result records no storage slots and its init code is not a known template; pass \
+         --storage"
+            .to_string()
+    })?;
+    // The last write to a slot is the one that stays
+    let deepest_value = storage
+        .iter()
+        .rev()
+        .find(|(written, _)| *written == deepest_slot)
+        .map_or([0u8; 32], |(_, value)| *value);
+    let storage_proof = slot_proof(&deepest_slot);
+    let storage_sizes = proof_size(&storage_proof);
+
+    let mut account_nodes = Vec::new();
+    let mut account_bytes = Vec::new();
+    let mut proofs = Vec::new();
+    for (contract_address, _) in &mined_accounts {
+        let account_proof = state.proof(&mpt::account_key(contract_address));
+        let (nodes, bytes) = proof_size(&account_proof);
+        account_nodes.push(nodes);
+        account_bytes.push(bytes);
+
+        if proofs.len() < max_proofs {
+            proofs.push(EthProof {
+                address: format!("0x{}", hex::encode(contract_address)),
+                account_proof: hex_nodes(&account_proof),
+                balance: quantity(account.balance),
+                code_hash: format!("0x{}", hex::encode(account.code_hash)),
+                nonce: quantity(account.nonce as u128),
+                storage_hash: format!("0x{}", hex::encode(storage_root)),
+                storage_proof: vec![StorageProof {
+                    key: format!("0x{}", hex::encode(deepest_slot)),
+                    value: word_quantity(&deepest_value),
+                    proof: hex_nodes(&storage_proof),
+                }],
+            });
+        }
+    }
+
+    let accesses = mined_accounts.len();
+    let total_bytes: Vec<usize> = account_bytes
+        .iter()
+        .map(|bytes| bytes + storage_sizes.1)
+        .collect();
+
+    Ok(ProofReport {
+        state_root: format!("0x{}", hex::encode(state.root_hash())),
+        summary: WitnessSummary {
+            accesses,
+            account_proof_nodes: SizeStats::from_sizes(&account_nodes),
+            account_proof_bytes: SizeStats::from_sizes(&account_bytes),
+            storage_proof_nodes: SizeStats::from_sizes(&vec![storage_sizes.0; accesses]),
+            storage_proof_bytes: SizeStats::from_sizes(&vec![storage_sizes.1; accesses]),
+            total_bytes: SizeStats::from_sizes(&total_bytes),
+            code_bytes: deploy_code.len(),
+        },
+        proofs,
+    })
+}
+
+/// Number of nodes and total bytes of a proof
+fn proof_size(proof: &[Vec<u8>]) -> (usize, usize) {
+    (proof.len(), proof.iter().map(Vec::len).sum())
+}
+
+fn hex_nodes(proof: &[Vec<u8>]) -> Vec<String> {
+    proof
+        .iter()
+        .map(|node| format!("0x{}", hex::encode(node)))
+        .collect()
+}
+
+/// JSON-RPC quantity: minimal hex without leading zeros
+fn quantity(value: u128) -> String {
+    format!("0x{value:x}")
+}
+
+/// JSON-RPC quantity of a 32-byte word
+fn word_quantity(word: &[u8; 32]) -> String {
+    match hex::encode(word).trim_start_matches('0') {
+        "" => "0x0".to_string(),
+        digits => format!("0x{digits}"),
+    }
+}
+
+/// Log the witness summary
+pub fn print_report(report: &ProofReport) {
+    let summary = &report.summary;
+    info!("");
+    info!("╔════════════════════════════════════════════════════════════════════════╗");
+    info!("║                          WITNESS SIZE REPORT                           ║");
+    info!("╚════════════════════════════════════════════════════════════════════════╝");
+    info!("");
+    info!("State root: {}", report.state_root);
+    info!("Accesses: {}", summary.accesses);
+    for (name, nodes, bytes) in [
+        (
+            "Account proof",
+            &summary.account_proof_nodes,
+            &summary.account_proof_bytes,
+        ),
+        (
+            "Storage proof",
+            &summary.storage_proof_nodes,
+            &summary.storage_proof_bytes,
+        ),
+    ] {
+        info!(
+            "{name}: {:.1} nodes, {:.1} bytes on average (min {}, max {})",
+            nodes.mean, bytes.mean, bytes.min, bytes.max
+        );
+    }
+    info!(
+        "Total witness per access: {:.1} bytes on average (min {}, max {}) + {} bytes of code",
+        summary.total_bytes.mean,
+        summary.total_bytes.min,
+        summary.total_bytes.max,
+        summary.code_bytes
+    );
+}
+
+/// Write the proof report to a JSON file
+pub fn write_report(report: &ProofReport, output_path: &str) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(report)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize to JSON: {e}")))?;
+    fs::write(output_path, json).map_err(|e| Error::io(output_path, e))?;
+    info!("Proofs saved to: {output_path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_miner::ContractWithAuxiliaries;
+    use crate::evm::{Runtime, mint_writes, seeding_contract};
+    use crate::storage_layout::{address_key, slot_from_u64};
+    use tiny_keccak::{Hasher, Keccak};
+
+    fn single_contract_result(slot: [u8; 32]) -> Create2MiningResult {
+        Create2MiningResult {
+            deployer: format!("0x{}", hex::encode([0u8; 20])),
+            init_code_hash: String::new(),
+            init_code: String::new(),
+            deploy_code: "0x00".to_string(),
+            storage_keys: Vec::new(),
+            storage_slots: vec![format!("0x{}", hex::encode(slot))],
+            target_depth: 0,
+            num_contracts: 1,
+            seed: None,
+            total_time: 0.0,
+            status: Default::default(),
+            contracts: vec![ContractWithAuxiliaries {
+                salt: [0; 32],
+                contract_address: format!("0x{}", hex::encode([0x42; 20])),
+                auxiliary_accounts: Vec::new(),
+                auxiliary_keys: Vec::new(),
+            }],
+            account_trie: None,
+            key_encryption: None,
+            cluster: None,
+        }
+    }
+
+    #[test]
+    fn test_proof_of_single_account() {
+        let result = single_contract_result(slot_from_u64(7));
+        let report = prove_create2_result(&result, &[], None, 1).unwrap();
+
+        // A single-entry trie is one leaf, which is also the root
+        let proof = &report.proofs[0];
+        assert_eq!(proof.account_proof.len(), 1);
+        let leaf = hex::decode(&proof.account_proof[0][2..]).unwrap();
+        let mut hasher = Keccak::v256();
+        let mut root = [0u8; 32];
+        hasher.update(&leaf);
+        hasher.finalize(&mut root);
+        assert_eq!(report.state_root, format!("0x{}", hex::encode(root)));
+
+        assert_eq!(proof.nonce, "0x1");
+        assert_eq!(proof.storage_proof[0].value, "0x1");
+        assert_eq!(proof.storage_proof[0].proof.len(), 1);
+        assert_eq!(report.summary.account_proof_bytes.max, leaf.len());
+    }
+
+    #[test]
+    fn test_storage_includes_constructor_mint() {
+        let slot = slot_from_u64(7);
+        let deployer = [0x4e; 20];
+        let mut result = single_contract_result(slot);
+        result.deployer = format!("0x{}", hex::encode(deployer));
+        let bytecode = seeding_contract(&[slot], Runtime::Erc20);
+        result.init_code = format!("0x{}", hex::encode(&bytecode.init_code));
+
+        // totalSupply and the deployer's balance sit next to the mined slot
+        let storage = result.contract_storage().unwrap();
+        assert_eq!(storage.len(), 3);
+        assert_eq!(
+            storage[..2],
+            mint_writes(&bytecode.init_code, &deployer)[..]
+        );
+        let report = prove_create2_result(&result, &[], None, 1).unwrap();
+        let root = mpt::storage_trie(storage.iter().copied()).root_hash();
+        assert_eq!(
+            report.proofs[0].storage_hash,
+            format!("0x{}", hex::encode(root))
+        );
+        assert_eq!(report.proofs[0].storage_proof[0].value, "0x1");
+
+        // Explicit storage replaces what the constructor is assumed to write
+        let explicit = [(slot, slot_from_u64(0x2a))];
+        let report = prove_create2_result(&result, &[], Some(&explicit), 1).unwrap();
+        assert_eq!(report.proofs[0].storage_proof[0].value, "0x2a");
+        assert_eq!(report.summary.storage_proof_nodes.max, 1);
+    }
+
+    #[test]
+    fn test_slots_of_results_without_recorded_slots() {
+        let mut result = single_contract_result([0; 32]);
+        result.storage_slots.clear();
+        // Nothing tells which slot the init code writes
+        assert!(prove_create2_result(&result, &[], None, 1).is_err());
+
+        // ...unless it is an older template's, which sstores to the raw addresses
+        let address = [0xc5; 20];
+        result.init_code = format!("0x6001 73{} 55", hex::encode(address)).replace(' ', "");
+        let report = prove_create2_result(&result, &[], None, 1).unwrap();
+        assert_eq!(
+            report.proofs[0].storage_proof[0].key,
+            format!("0x{}", hex::encode(address_key(&address)))
+        );
+
+        // ...or the storage is given, whose slot with the largest proof is attacked
+        result.init_code = String::new();
+        let explicit = [(slot_from_u64(1), slot_from_u64(1))];
+        let report = prove_create2_result(&result, &[], Some(&explicit), 1).unwrap();
+        assert_eq!(report.summary.storage_proof_nodes.max, 1);
+    }
+}
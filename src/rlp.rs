@@ -0,0 +1,76 @@
+//! # RLP Module
+//!
+//! Recursive Length Prefix encoding, as used by trie nodes, accounts and transactions.
+//! Only encoding is needed: items are encoded bottom-up and lists take already-encoded items.
+//!
+//! ## Key Functions
+//! - `encode_bytes`: Encodes a byte string
+//! - `encode_list`: Wraps already-encoded items in a list
+//! - `encode_uint`: Encodes an unsigned integer as a minimal big-endian byte string
+
+/// Encode a byte string
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = length_prefix(0x80, bytes.len());
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Encode a list of items that are already RLP-encoded
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_len = items.iter().map(Vec::len).sum();
+    let mut out = length_prefix(0xc0, payload_len);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+/// Encode an unsigned integer: big-endian without leading zeros (zero is the empty string)
+pub fn encode_uint(value: u128) -> Vec<u8> {
+    encode_bytes(trim_leading_zeros(&value.to_be_bytes()))
+}
+
+/// Strip leading zero bytes, as RLP integers and storage values require
+pub fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// Header for a string (`offset` 0x80) or list (`offset` 0xc0) payload of `len` bytes
+fn length_prefix(offset: u8, len: usize) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes = trim_leading_zeros(&len.to_be_bytes()).to_vec();
+    let mut out = vec![offset + 55 + len_bytes.len() as u8];
+    out.extend_from_slice(&len_bytes);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rlp_examples() {
+        // Examples from the Ethereum yellow paper / wiki
+        assert_eq!(encode_bytes(b"dog"), vec![0x83, b'd', b'o', b'g']);
+        assert_eq!(encode_bytes(&[]), vec![0x80]);
+        assert_eq!(encode_bytes(&[0x0f]), vec![0x0f]);
+        assert_eq!(encode_uint(0), vec![0x80]);
+        assert_eq!(encode_uint(1024), vec![0x82, 0x04, 0x00]);
+        assert_eq!(
+            encode_list(&[encode_bytes(b"cat"), encode_bytes(b"dog")]),
+            vec![0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']
+        );
+        assert_eq!(encode_list(&[]), vec![0xc0]);
+
+        let long = [b'a'; 56];
+        let encoded = encode_bytes(&long);
+        assert_eq!(&encoded[..2], &[0xb8, 56]);
+        assert_eq!(encoded.len(), 58);
+    }
+}
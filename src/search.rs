@@ -0,0 +1,755 @@
+//! # Search Module
+//!
+//! Shared machinery of the multi-target searches run by the storage and account miners.
+//! Candidates are counter-based: a prefix derived from the run's seed followed by a 64-bit
+//! nonce, where thread `t` of `n` tries nonces `t, t + n, t + 2n, ...`. Each slot of a search
+//! keeps the matching candidate with the lowest nonce, and workers only stop once no lower
+//! nonce can turn up, so a given seed produces the same result for any thread count.
+//!
+//! A `Budget` bounds how many candidates and how much time each search and the whole run may
+//! take. Attempt limits are nonce limits, so a search cut short by one still ends with the same
+//! slots for any thread count. Time limits and Ctrl-C stop the workers wherever they are; the
+//! slots filled so far are kept either way, and the search reports why it ended. The budget
+//! also carries the run's `ProgressMonitor`, which `run_workers` feeds while it polls the
+//! workers.
+//!
+//! ## Key Functions
+//! - `NonceKeys`: Deterministic candidate keys for one search stream of a seed
+//! - `LevelSearch`: Slots filled concurrently by the lowest-nonce matching candidate
+//! - `Budget`: Attempt and wall-clock limits of each search and of a whole run
+//! - `run_workers`: Runs a search's workers within a budget, reporting its progress for
+//!   checkpoints
+//! - `stop_on_interrupt`: Ends running searches on Ctrl-C instead of killing the process
+//! - `measure_hashrate`: Times a search path for a moment, for planning runs
+//! - `count_shared_nibbles`: Counts the leading nibbles two keys share
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::error::Error;
+use crate::progress::ProgressMonitor;
+
+/// Nonce reserved for a search's anchor; worker nonces never get this far
+pub const ANCHOR_NONCE: u64 = u64::MAX;
+
+/// How often `run_workers` checks on the workers
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest time `run_workers` goes without reporting progress, even if no slot was filled
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of nibbles in a 32-byte trie key
+pub const MAX_DEPTH: usize = 64;
+
+/// Check the depth and thread count every mining config has
+pub fn check_search_params(depth: usize, num_threads: usize) -> Result<(), Error> {
+    if depth == 0 || depth > MAX_DEPTH {
+        return Err(Error::InvalidInput(format!(
+            "Depth must be between 1 and {MAX_DEPTH}, got {depth}"
+        )));
+    }
+    if num_threads == 0 {
+        return Err(Error::InvalidInput(
+            "Thread count must be at least 1".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Set by the Ctrl-C handler of `stop_on_interrupt`
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// End running (and later) searches on the first Ctrl-C instead of killing the process, so the
+/// caller can write out what was mined. A second Ctrl-C kills the process as usual
+#[cfg(unix)]
+pub fn stop_on_interrupt() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        // Storing an atomic and resetting the handler are both async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
+    let handler = on_interrupt as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+/// Ctrl-C keeps killing the process where no handler is installed
+#[cfg(not(unix))]
+pub fn stop_on_interrupt() {}
+
+/// How a search, or a whole run, ended
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchStatus {
+    /// Every slot was filled
+    #[default]
+    Complete,
+    /// The attempt budget ran out first
+    AttemptsExhausted,
+    /// The time budget ran out first
+    TimedOut,
+    /// Stopped by Ctrl-C
+    Interrupted,
+}
+
+impl SearchStatus {
+    pub fn is_complete(&self) -> bool {
+        *self == SearchStatus::Complete
+    }
+}
+
+impl fmt::Display for SearchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SearchStatus::Complete => "complete",
+            SearchStatus::AttemptsExhausted => "attempt budget exhausted",
+            SearchStatus::TimedOut => "time budget exhausted",
+            SearchStatus::Interrupted => "interrupted",
+        })
+    }
+}
+
+/// Limits on the work of a run. Level limits apply to each search on its own: the levels of a
+/// branch (or the auxiliaries of one account) are searched together, so a level not found
+/// within them is given up. Run limits apply to all searches of the run together. The run's
+/// clock starts when the budget is created
+#[derive(Debug)]
+pub struct Budget {
+    level_attempts: Option<u64>,
+    level_timeout: Option<Duration>,
+    max_attempts: Option<u64>,
+    timeout: Option<Duration>,
+    start: Instant,
+    spent: AtomicU64,
+    monitor: ProgressMonitor,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::new()
+    }
+}
+
+impl Budget {
+    /// A budget without limits, whose clock starts now
+    pub fn new() -> Self {
+        Budget {
+            level_attempts: None,
+            level_timeout: None,
+            max_attempts: None,
+            timeout: None,
+            start: Instant::now(),
+            spent: AtomicU64::new(0),
+            monitor: ProgressMonitor::new(),
+        }
+    }
+
+    /// Candidates each search may try, counted across resumed runs
+    pub fn level_attempts(mut self, attempts: u64) -> Self {
+        self.level_attempts = Some(attempts);
+        self
+    }
+
+    /// Time each search may take, counted across resumed runs
+    pub fn level_timeout(mut self, timeout: Duration) -> Self {
+        self.level_timeout = Some(timeout);
+        self
+    }
+
+    /// Candidates the searches of this run may try together
+    pub fn max_attempts(mut self, attempts: u64) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Time this run may take
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Report the run's progress to `monitor`
+    pub fn monitor(mut self, monitor: ProgressMonitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// The monitor the run's searches report to
+    pub fn progress_monitor(&self) -> &ProgressMonitor {
+        &self.monitor
+    }
+
+    /// Nonces a search whose first nonce is `origin` may still try from `next` on
+    pub fn nonces(&self, origin: u64, next: u64) -> Range<u64> {
+        let level_end = self
+            .level_attempts
+            .map_or(u64::MAX, |attempts| origin.saturating_add(attempts));
+        let run_end = self.max_attempts.map_or(u64::MAX, |attempts| {
+            let left = attempts.saturating_sub(self.spent.load(Ordering::Relaxed));
+            next.saturating_add(left)
+        });
+        next..level_end.min(run_end).max(next)
+    }
+
+    /// Whether a search that ran for `elapsed` seconds, or the run, is out of time
+    fn expired(&self, elapsed: f64) -> bool {
+        self.level_timeout
+            .is_some_and(|timeout| elapsed >= timeout.as_secs_f64())
+            || self
+                .timeout
+                .is_some_and(|timeout| self.start.elapsed() >= timeout)
+    }
+
+    fn spend(&self, attempts: u64) {
+        self.spent.fetch_add(attempts, Ordering::Relaxed);
+    }
+}
+
+/// Candidates per second of the search `search` runs on the budget it is given, which stops it
+/// after about `duration` without reporting progress. For calibrating a search path without
+/// mining anything kept
+pub fn measure_hashrate(
+    duration: Duration,
+    search: impl FnOnce(&Budget) -> Result<(), Error>,
+) -> Result<f64, Error> {
+    let budget = Budget::new()
+        .level_timeout(duration)
+        .monitor(ProgressMonitor::new().interval(Duration::MAX));
+    let start = Instant::now();
+    search(&budget)?;
+    Ok(budget.spent.load(Ordering::Relaxed) as f64 / start.elapsed().as_secs_f64())
+}
+
+/// Pick a seed for a run that was not given one
+pub fn random_seed() -> u64 {
+    fastrand::u64(..)
+}
+
+/// Deterministic candidates of one search stream: a prefix derived from the seed and stream
+/// name, followed by the nonce in the last 8 bytes
+#[derive(Clone, Copy, Debug)]
+pub struct NonceKeys {
+    prefix: [u8; 32],
+}
+
+impl NonceKeys {
+    pub fn new(seed: u64, stream: &str) -> Self {
+        let mut hasher = Keccak::v256();
+        let mut prefix = [0u8; 32];
+        hasher.update(&seed.to_be_bytes());
+        hasher.update(stream.as_bytes());
+        hasher.finalize(&mut prefix);
+        NonceKeys { prefix }
+    }
+
+    /// Candidate `nonce` as a key of `width` bytes (at least 8), right-aligned in a word
+    pub fn key(&self, width: usize, nonce: u64) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[32 - width..24].copy_from_slice(&self.prefix[..width - 8]);
+        key[24..].copy_from_slice(&nonce.to_be_bytes());
+        key
+    }
+
+    /// Candidate `nonce` as an address
+    pub fn address(&self, nonce: u64) -> [u8; 20] {
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&self.key(20, nonce)[12..]);
+        address
+    }
+}
+
+/// Slots of a multi-target search, shared by the workers. Each slot keeps the matching
+/// candidate with the lowest nonce; for the levels of a branch, slot `i` takes candidates
+/// sharing `i + 1` nibbles with the target (or more, for the last slot)
+pub struct LevelSearch<T> {
+    slots: Mutex<Vec<Option<(T, f64)>>>,
+    /// Shared nibbles a candidate needs for each slot, for progress reports
+    nibbles: Vec<usize>,
+    /// Lowest nonce found per slot (`u64::MAX` while empty), readable without locking
+    best: Vec<AtomicU64>,
+    /// Number of slots filled so far, to notice new results without locking
+    fills: AtomicU64,
+    /// Why the search was ended early; workers then see every goal as settled
+    stopped: OnceLock<SearchStatus>,
+    /// First nonce past the search's attempt budget, which no slot takes
+    limit: AtomicU64,
+    start: Instant,
+}
+
+impl<T> LevelSearch<T> {
+    pub fn new(num_slots: usize) -> Self {
+        Self::resumed(num_slots, 0.0)
+    }
+
+    /// A search that already ran for `elapsed` seconds, so the times of new finds continue
+    /// from there
+    pub fn resumed(num_slots: usize, elapsed: f64) -> Self {
+        let now = Instant::now();
+        LevelSearch {
+            slots: Mutex::new((0..num_slots).map(|_| None).collect()),
+            nibbles: (1..=num_slots).collect(),
+            best: (0..num_slots).map(|_| AtomicU64::new(u64::MAX)).collect(),
+            fills: AtomicU64::new(0),
+            stopped: OnceLock::new(),
+            limit: AtomicU64::new(u64::MAX),
+            start: now
+                .checked_sub(Duration::from_secs_f64(elapsed))
+                .unwrap_or(now),
+        }
+    }
+
+    /// The shared nibbles each slot needs, if not `i + 1` for slot `i` as for a branch
+    pub fn requiring(mut self, nibbles: Vec<usize>) -> Self {
+        assert_eq!(nibbles.len(), self.best.len(), "one requirement per slot");
+        self.nibbles = nibbles;
+        self
+    }
+
+    /// Shared nibbles needed by the slots in `goal` that are still empty
+    pub fn pending_nibbles(&self, goal: u64) -> Vec<usize> {
+        self.slots_in(goal)
+            .filter(|&slot| self.best[slot].load(Ordering::Relaxed) == u64::MAX)
+            .map(|slot| self.nibbles[slot])
+            .collect()
+    }
+
+    /// Seconds since the search started
+    pub fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    /// Mask of the level slots sharing fewer than `nibbles` nibbles
+    pub fn levels_below(&self, nibbles: usize) -> u64 {
+        let count = nibbles.saturating_sub(1).min(self.best.len());
+        if count == 0 {
+            0
+        } else {
+            u64::MAX >> (64 - count)
+        }
+    }
+
+    /// Mask of every slot
+    pub fn all_slots(&self) -> u64 {
+        self.levels_below(self.best.len() + 1)
+    }
+
+    /// The level slot a candidate sharing `shared` nibbles belongs to
+    pub fn level_for(&self, shared: usize) -> Option<usize> {
+        if shared == 0 || self.best.is_empty() {
+            return None;
+        }
+        Some((shared - 1).min(self.best.len() - 1))
+    }
+
+    /// Whether every slot in `goal` is filled
+    pub fn reached(&self, goal: u64) -> bool {
+        self.slots_in(goal)
+            .all(|slot| self.best[slot].load(Ordering::Relaxed) != u64::MAX)
+    }
+
+    /// Whether every slot in `goal` holds a candidate below `nonce`, so a worker at `nonce`
+    /// can no longer improve on them, or the search was stopped or has spent its attempts
+    pub fn settled(&self, goal: u64, nonce: u64) -> bool {
+        self.stopped()
+            || nonce >= self.limit.load(Ordering::Relaxed)
+            || self
+                .slots_in(goal)
+                .all(|slot| self.best[slot].load(Ordering::Relaxed) < nonce)
+    }
+
+    /// End the search for `reason`: workers stop at their next check, keeping the slots
+    /// filled so far. The first reason given sticks
+    pub fn stop(&self, reason: SearchStatus) {
+        let _ = self.stopped.set(reason);
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.stopped.get().is_some()
+    }
+
+    /// How the search ended, once its workers have: complete if every slot in `goal` is
+    /// filled, otherwise why it was stopped
+    pub fn status(&self, goal: u64) -> SearchStatus {
+        if self.reached(goal) {
+            return SearchStatus::Complete;
+        }
+        // Workers only end without a reason once they pass the attempt limit
+        self.stopped
+            .get()
+            .copied()
+            .unwrap_or(SearchStatus::AttemptsExhausted)
+    }
+
+    /// Whether a candidate at `nonce` would improve `slot`
+    pub fn improves(&self, slot: usize, nonce: u64) -> bool {
+        nonce < self.best[slot].load(Ordering::Relaxed)
+            && nonce < self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Put `value` in `slot` unless it holds a lower nonce already. Returns whether it did
+    pub fn fill(&self, slot: usize, nonce: u64, value: T) -> bool {
+        let mut slots = self.slots.lock().unwrap();
+        if !self.improves(slot, nonce) {
+            return false;
+        }
+        let found_after = self.elapsed();
+        slots[slot] = Some((value, found_after));
+        self.best[slot].store(nonce, Ordering::Relaxed);
+        self.fills.fetch_add(1, Ordering::Relaxed);
+        debug!("Slot {slot} filled at nonce {nonce} after {found_after:.2} seconds");
+        true
+    }
+
+    /// Put back a candidate found earlier, e.g. by an interrupted run
+    pub fn restore(&self, slot: usize, nonce: u64, value: T, found_after: f64) {
+        self.slots.lock().unwrap()[slot] = Some((value, found_after));
+        self.best[slot].store(nonce, Ordering::Relaxed);
+    }
+
+    /// Number of slots filled so far
+    pub fn fills(&self) -> u64 {
+        self.fills.load(Ordering::Relaxed)
+    }
+
+    /// The slots as they stand, each with its nonce and time, with values mapped by `f`
+    pub fn snapshot<S>(&self, f: impl Fn(&T) -> S) -> Vec<Option<(S, u64, f64)>> {
+        let slots = self.slots.lock().unwrap();
+        slots
+            .iter()
+            .zip(&self.best)
+            .map(|(slot, best)| {
+                let (value, found_after) = slot.as_ref()?;
+                Some((f(value), best.load(Ordering::Relaxed), *found_after))
+            })
+            .collect()
+    }
+
+    /// The filled slots up to the first empty one, with the seconds into the search each was
+    /// found at. For the levels of a branch, the levels that still form one
+    pub fn into_leading_slots(self) -> Vec<(T, f64)> {
+        self.slots
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map_while(|slot| slot)
+            .collect()
+    }
+
+    /// The filled slots in order, with the seconds into the search each was found at
+    pub fn into_slots(self) -> Vec<(T, f64)> {
+        self.slots
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn slots_in(&self, goal: u64) -> impl Iterator<Item = usize> {
+        (0..self.best.len()).filter(move |slot| goal & (1 << slot) != 0)
+    }
+}
+
+/// Nonces reached by the workers of a search. Thread `t` of `n` walks `start + t`,
+/// `start + t + n`, ..., so every nonce below the lowest position has been tried
+pub struct Frontier {
+    start: u64,
+    positions: Vec<AtomicU64>,
+}
+
+impl Frontier {
+    fn new(start: u64, num_threads: usize) -> Self {
+        Frontier {
+            start,
+            positions: (0..num_threads as u64)
+                .map(|t| AtomicU64::new(start + t))
+                .collect(),
+        }
+    }
+
+    /// The first nonce of a worker
+    pub fn first(&self, thread_id: usize) -> u64 {
+        self.positions[thread_id].load(Ordering::Relaxed)
+    }
+
+    /// Record that a worker tried every one of its nonces below `nonce`
+    pub fn record(&self, thread_id: usize, nonce: u64) {
+        // Release, so a snapshot after reading the position sees the fills below it
+        self.positions[thread_id].store(nonce, Ordering::Release);
+    }
+
+    /// Every nonce below this one has been tried
+    pub fn scanned(&self) -> u64 {
+        self.positions
+            .iter()
+            .map(|position| position.load(Ordering::Acquire))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Candidates tried by all workers together, as far as they recorded
+    pub fn tried(&self) -> u64 {
+        let num_threads = self.positions.len() as u64;
+        (self.positions.iter().zip(self.start..))
+            .map(|(position, first)| {
+                position.load(Ordering::Relaxed).saturating_sub(first) / num_threads
+            })
+            .sum()
+    }
+}
+
+/// Run `worker` on `num_threads` threads over `nonces` (usually `budget.nonces(...)`) until
+/// all return. Meanwhile `report` is called from this thread with the nonce every candidate
+/// below has been tried, whenever a slot was filled (and at least every minute) and once the
+/// workers are done, so progress can be saved, and the budget's monitor hears of the attempts
+/// and the slots in `goal` still open. Running out of time or a Ctrl-C stops the workers; a
+/// worker that panics stops the others and fails the search
+pub fn run_workers<T: Send>(
+    progress: &LevelSearch<T>,
+    goal: u64,
+    nonces: Range<u64>,
+    num_threads: usize,
+    budget: &Budget,
+    worker: impl Fn(usize, &Frontier) + Sync,
+    mut report: impl FnMut(u64),
+) -> Result<(), Error> {
+    progress.limit.store(nonces.end, Ordering::Relaxed);
+    let frontier = Frontier::new(nonces.start, num_threads);
+    // Workers may run past the limit within a batch, but nothing past it is taken
+    let scanned = || frontier.scanned().min(nonces.end);
+    let finished = AtomicUsize::new(0);
+    let main = thread::current();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..num_threads)
+            .map(|thread_id| {
+                let (worker, frontier, finished) = (&worker, &frontier, &finished);
+                let main = main.clone();
+                scope.spawn(move || {
+                    let outcome =
+                        panic::catch_unwind(AssertUnwindSafe(|| worker(thread_id, frontier)));
+                    if outcome.is_err() {
+                        progress.stop(SearchStatus::Interrupted);
+                    }
+                    finished.fetch_add(1, Ordering::Relaxed);
+                    main.unpark();
+                    outcome
+                })
+            })
+            .collect();
+
+        let mut reported = (progress.fills(), Instant::now());
+        while finished.load(Ordering::Relaxed) < num_threads {
+            if INTERRUPTED.load(Ordering::Relaxed) {
+                progress.stop(SearchStatus::Interrupted);
+            } else if budget.expired(progress.elapsed()) {
+                progress.stop(SearchStatus::TimedOut);
+            }
+            thread::park_timeout(POLL_INTERVAL);
+            if progress.fills() != reported.0 || reported.1.elapsed() >= CHECKPOINT_INTERVAL {
+                reported = (progress.fills(), Instant::now());
+                report(scanned());
+            }
+            let pending = progress.pending_nibbles(goal);
+            let found = goal.count_ones() as usize - pending.len();
+            budget.monitor.observe(frontier.tried(), found, &pending);
+        }
+        budget.spend(scanned() - nonces.start);
+        budget.monitor.finish_search(frontier.tried());
+        report(scanned());
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(Err))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .try_for_each(|outcome| outcome.map_err(|payload| Error::worker_panic(&*payload)))
+    })
+}
+
+/// Count how many leading nibbles two keys share
+pub fn count_shared_nibbles(a: &[u8; 32], b: &[u8; 32]) -> usize {
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        let diff = x ^ y;
+        if diff != 0 {
+            // The high nibble of the first differing byte may still match
+            return 2 * i + usize::from(diff & 0xF0 == 0);
+        }
+    }
+    64
+}
+
+/// Nibble `index` of a key, most significant first
+pub fn nibble_at(key: &[u8; 32], index: usize) -> usize {
+    let byte = key[index / 2];
+    if index.is_multiple_of(2) {
+        (byte >> 4) as usize
+    } else {
+        (byte & 0x0F) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_search_keeps_lowest_nonce() {
+        let mut a = [0u8; 32];
+        let mut b = [0u8; 32];
+        a[1] = 0x12;
+        b[1] = 0x13;
+        assert_eq!(count_shared_nibbles(&a, &b), 3);
+        assert_eq!(count_shared_nibbles(&a, &a), 64);
+
+        let progress = LevelSearch::new(3);
+        assert_eq!(progress.level_for(0), None);
+        assert_eq!(progress.level_for(2), Some(1));
+        // Anything deeper than the last level belongs to the last level
+        assert_eq!(progress.level_for(9), Some(2));
+
+        assert!(progress.fill(1, 20, "late"));
+        assert!(progress.fill(1, 10, "b"));
+        assert!(!progress.fill(1, 15, "other"));
+        assert!(!progress.reached(progress.all_slots()));
+        assert!(progress.fill(0, 5, "a") && progress.fill(2, 30, "c"));
+        assert!(progress.reached(progress.all_slots()));
+        assert!(!progress.settled(progress.all_slots(), 25));
+        assert!(progress.settled(progress.all_slots(), 31));
+
+        let slots: Vec<_> = progress.into_slots().into_iter().map(|(v, _)| v).collect();
+        assert_eq!(slots, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_run_workers_reports_scanned_nonces() {
+        // Slot 0 takes even nonces from 10 on; restoring slot 1 leaves only slot 0 to find
+        let progress = LevelSearch::resumed(2, 5.0);
+        progress.restore(1, 3, 3, 1.0);
+        let goal = progress.all_slots();
+        let budget = Budget::new();
+        let mut reports = Vec::new();
+        run_workers(
+            &progress,
+            goal,
+            budget.nonces(0, 10),
+            3,
+            &budget,
+            |thread_id, frontier| {
+                let mut nonce = frontier.first(thread_id);
+                while !progress.settled(goal, nonce) {
+                    if nonce.is_multiple_of(2) && progress.improves(0, nonce) {
+                        progress.fill(0, nonce, nonce);
+                    }
+                    nonce += 3;
+                    frontier.record(thread_id, nonce);
+                }
+            },
+            |scanned| reports.push(scanned),
+        )
+        .unwrap();
+
+        assert!(reports.iter().all(|&scanned| scanned >= 10));
+        let snapshot = progress.snapshot(|value| *value);
+        assert_eq!(
+            snapshot[0].map(|(value, nonce, _)| (value, nonce)),
+            Some((10, 10))
+        );
+        assert_eq!(snapshot[1], Some((3, 3, 1.0)));
+        assert!(progress.elapsed() >= 5.0);
+    }
+
+    #[test]
+    fn test_nonce_keys_are_deterministic() {
+        let keys = NonceKeys::new(7, "storage");
+        assert_eq!(keys.key(20, 1), NonceKeys::new(7, "storage").key(20, 1));
+        assert_ne!(keys.key(20, 1), keys.key(20, 2));
+        assert_ne!(keys.key(20, 1), NonceKeys::new(8, "storage").key(20, 1));
+        assert_eq!(keys.key(20, 1)[..12], [0u8; 12]);
+        assert_eq!(&keys.address(1)[..], &keys.key(20, 1)[12..]);
+    }
+
+    #[test]
+    fn test_run_workers_reports_worker_panic() {
+        let progress: LevelSearch<u64> = LevelSearch::new(1);
+        let result = run_workers(
+            &progress,
+            progress.all_slots(),
+            0..u64::MAX,
+            2,
+            &Budget::new(),
+            |thread_id, _| {
+                if thread_id == 0 {
+                    panic!("worker failed");
+                }
+                // The other worker stops once the panic stops the search
+                while !progress.stopped() {
+                    thread::yield_now();
+                }
+            },
+            |_| {},
+        );
+        assert_eq!(result, Err(Error::WorkerPanic("worker failed".to_string())));
+        assert!(progress.stopped());
+    }
+
+    #[test]
+    fn test_attempt_budget_keeps_partial_slots() {
+        // Slot 0 takes nonce 7 and slot 1 nonce 40, past the budget of 20 attempts
+        let progress = LevelSearch::new(2);
+        let goal = progress.all_slots();
+        let budget = Budget::new().level_attempts(20);
+        let mut reports = Vec::new();
+        run_workers(
+            &progress,
+            goal,
+            budget.nonces(0, 0),
+            4,
+            &budget,
+            |thread_id, frontier| {
+                let mut nonce = frontier.first(thread_id);
+                while !progress.settled(goal, nonce) {
+                    for (slot, target) in [(0, 7), (1, 40)] {
+                        if nonce == target && progress.improves(slot, nonce) {
+                            progress.fill(slot, nonce, nonce);
+                        }
+                    }
+                    nonce += 4;
+                    frontier.record(thread_id, nonce);
+                }
+            },
+            |scanned| reports.push(scanned),
+        )
+        .unwrap();
+
+        assert_eq!(progress.status(goal), SearchStatus::AttemptsExhausted);
+        assert_eq!(reports.last(), Some(&20));
+        assert_eq!(progress.pending_nibbles(goal), [2]);
+        // A resumed run with a larger budget continues past the first one
+        assert_eq!(Budget::new().level_attempts(50).nonces(0, 20), 20..50);
+        assert_eq!(
+            Budget::new()
+                .max_attempts(5)
+                .level_attempts(50)
+                .nonces(0, 20),
+            20..25
+        );
+        let slots: Vec<_> = progress
+            .into_leading_slots()
+            .into_iter()
+            .map(|(v, _)| v)
+            .collect();
+        assert_eq!(slots, [7]);
+    }
+}
@@ -0,0 +1,337 @@
+//! # State Test Module
+//!
+//! Turns a mined CREATE2 result into state test fillers in the `GeneralStateTests` format of
+//! ethereum/tests, so client teams get reproducible worst-case tests without writing fixtures
+//! by hand. Each test attacks one contract: the pre-state holds the contract with all mined
+//! slots set to 1 next to its auxiliary accounts, the transaction calls `attack(uint256)`, and
+//! the expected post-state has the deepest slot overwritten.
+//!
+//! Fillers state the expected storage rather than the post-state root, which takes executing
+//! the transaction. Filling them (retesteth, or `fill` of execution-spec-tests) produces the
+//! `StateTest` fixtures and, from the same filler, `BlockchainTest` fixtures.
+//!
+//! ## Key Functions
+//! - `state_test_fillers`: Builds one filler per attacked contract
+//! - `write_fillers`: Saves the fillers to a JSON file
+
+use log::info;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::account_miner::{AUXILIARY_BALANCE, Create2MiningResult};
+use crate::error::Error;
+use crate::evm::{op, selector};
+use crate::keys::address_of;
+use crate::storage_layout::slot_from_u64;
+
+/// Secret key of the sender used throughout ethereum/tests (address `0xa94f...6ebf0b`)
+const SENDER_SECRET_KEY: &str = "45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8";
+
+/// Balance of the sender: 1000 ether
+const SENDER_BALANCE: u128 = 1000 * 10u128.pow(18);
+
+/// Gas limit of the attack transaction, well above a cold `SSTORE` plus the call overhead
+const ATTACK_GAS_LIMIT: u64 = 1_000_000;
+
+/// Gas price of the attack transaction, equal to the block base fee
+const GAS_PRICE: u64 = 10;
+
+/// A state test filler
+#[derive(Serialize, Deserialize)]
+pub struct StateTestFiller {
+    #[serde(rename = "_info")]
+    pub info: TestInfo,
+    pub env: Env,
+    pub pre: BTreeMap<String, PreAccount>,
+    pub transaction: Transaction,
+    pub expect: Vec<Expectation>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TestInfo {
+    pub comment: String,
+}
+
+/// Block environment the transaction runs in
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Env {
+    pub current_coinbase: String,
+    pub current_difficulty: String,
+    pub current_gas_limit: String,
+    pub current_number: String,
+    pub current_timestamp: String,
+    pub current_base_fee: String,
+    pub current_random: String,
+}
+
+/// An account of the pre-state
+#[derive(Serialize, Deserialize)]
+pub struct PreAccount {
+    pub balance: String,
+    pub code: String,
+    pub nonce: String,
+    pub storage: BTreeMap<String, String>,
+}
+
+/// The attack transaction. `data`, `gasLimit` and `value` are lists the expectations index into
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    pub data: Vec<String>,
+    pub gas_limit: Vec<String>,
+    pub gas_price: String,
+    pub nonce: String,
+    pub secret_key: String,
+    pub to: String,
+    pub value: Vec<String>,
+}
+
+/// Expected post-state for the forks in `network`
+#[derive(Serialize, Deserialize)]
+pub struct Expectation {
+    pub indexes: ExpectIndexes,
+    pub network: Vec<String>,
+    pub result: BTreeMap<String, ExpectedAccount>,
+}
+
+/// Transaction variants an expectation applies to (-1 for all)
+#[derive(Serialize, Deserialize)]
+pub struct ExpectIndexes {
+    pub data: i64,
+    pub gas: i64,
+    pub value: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExpectedAccount {
+    pub storage: BTreeMap<String, String>,
+}
+
+/// One filler per contract for the first `num_tests` contracts of `result`, each calling
+/// `attack(value)` from `fork` on. Tests are named after the number of mined slots, the account
+/// depth and the contract
+pub fn state_test_fillers(
+    result: &Create2MiningResult,
+    num_tests: usize,
+    value: u64,
+    fork: &str,
+) -> Result<BTreeMap<String, StateTestFiller>, String> {
+    let deploy_code = result.deploy_code()?;
+    let attack = selector("attack(uint256)");
+    let dispatches_attack = deploy_code
+        .windows(5)
+        .any(|w| w[0] == op::PUSH1 + 3 && w[1..] == attack);
+    if !dispatches_attack {
+        return Err("deploy_code has no attack(uint256) function".to_string());
+    }
+    let storage_slots = result.contract_storage_slots()?;
+    let Some(deepest) = storage_slots.last() else {
+        return Err("Result records no storage slots to attack".to_string());
+    };
+
+    let word = |w: &[u8; 32]| format!("0x{}", hex::encode(w));
+    let one = word(&slot_from_u64(1));
+    let value_word = slot_from_u64(value);
+    let pre_storage: BTreeMap<String, String> = storage_slots
+        .iter()
+        .map(|slot| (word(slot), one.clone()))
+        .collect();
+    let mut post_storage = pre_storage.clone();
+    post_storage.insert(word(deepest), word(&value_word));
+
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(&hex::decode(SENDER_SECRET_KEY).unwrap()).unwrap();
+    let sender = address_of(&PublicKey::from_secret_key(&secp, &secret_key));
+    let code = format!("0x{}", hex::encode(&deploy_code));
+    let data = format!("0x{}{}", hex::encode(attack), hex::encode(value_word));
+
+    let mut fillers = BTreeMap::new();
+    for (index, (contract_address, auxiliaries)) in result
+        .mined_accounts()?
+        .into_iter()
+        .take(num_tests)
+        .enumerate()
+    {
+        let contract = format!("0x{}", hex::encode(contract_address));
+        let mut pre = BTreeMap::new();
+        pre.insert(
+            format!("0x{}", hex::encode(sender)),
+            PreAccount {
+                balance: quantity(SENDER_BALANCE),
+                code: "0x".to_string(),
+                nonce: "0x00".to_string(),
+                storage: BTreeMap::new(),
+            },
+        );
+        for auxiliary in &auxiliaries {
+            pre.insert(
+                format!("0x{}", hex::encode(auxiliary)),
+                PreAccount {
+                    balance: quantity(AUXILIARY_BALANCE),
+                    code: "0x".to_string(),
+                    nonce: "0x00".to_string(),
+                    storage: BTreeMap::new(),
+                },
+            );
+        }
+        pre.insert(
+            contract.clone(),
+            PreAccount {
+                balance: "0x00".to_string(),
+                code: code.clone(),
+                nonce: "0x01".to_string(),
+                storage: pre_storage.clone(),
+            },
+        );
+
+        let filler = StateTestFiller {
+            info: TestInfo {
+                comment: format!(
+                    "attack(uint256) on contract {contract}: deepest of {} mined slots {}, \
+                     {} auxiliary accounts for account depth {}",
+                    storage_slots.len(),
+                    word(deepest),
+                    auxiliaries.len(),
+                    result.target_depth
+                ),
+            },
+            env: Env {
+                current_coinbase: "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba".to_string(),
+                current_difficulty: "0x020000".to_string(),
+                current_gas_limit: "0x05f5e100".to_string(),
+                current_number: "0x01".to_string(),
+                current_timestamp: "0x03e8".to_string(),
+                current_base_fee: quantity(GAS_PRICE as u128),
+                current_random: word(&slot_from_u64(0x020000)),
+            },
+            pre,
+            transaction: Transaction {
+                data: vec![data.clone()],
+                gas_limit: vec![quantity(ATTACK_GAS_LIMIT as u128)],
+                gas_price: quantity(GAS_PRICE as u128),
+                nonce: "0x00".to_string(),
+                secret_key: format!("0x{SENDER_SECRET_KEY}"),
+                to: contract.clone(),
+                value: vec!["0x00".to_string()],
+            },
+            expect: vec![Expectation {
+                indexes: ExpectIndexes {
+                    data: -1,
+                    gas: -1,
+                    value: -1,
+                },
+                network: vec![format!(">={fork}")],
+                result: BTreeMap::from([(
+                    contract,
+                    ExpectedAccount {
+                        storage: post_storage.clone(),
+                    },
+                )]),
+            }],
+        };
+        fillers.insert(
+            format!(
+                "worstCaseSlots{}Depth{}_{index}",
+                storage_slots.len(),
+                result.target_depth
+            ),
+            filler,
+        );
+    }
+    Ok(fillers)
+}
+
+/// Filler quantity: hex with at least two digits
+fn quantity(value: u128) -> String {
+    format!("0x{value:02x}")
+}
+
+/// Write the fillers to a JSON file
+pub fn write_fillers(
+    fillers: &BTreeMap<String, StateTestFiller>,
+    output_path: &str,
+) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(fillers)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize to JSON: {e}")))?;
+    fs::write(output_path, json).map_err(|e| Error::io(output_path, e))?;
+    info!(
+        "{} state test fillers saved to: {output_path}",
+        fillers.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_miner::ContractWithAuxiliaries;
+    use crate::evm::{Runtime, seeding_contract};
+
+    fn result_with_runtime(runtime: Runtime) -> Create2MiningResult {
+        let slots = [slot_from_u64(7), slot_from_u64(9)];
+        let bytecode = seeding_contract(&slots, runtime);
+        Create2MiningResult {
+            deployer: format!("0x{}", hex::encode([0u8; 20])),
+            init_code_hash: String::new(),
+            init_code: format!("0x{}", hex::encode(&bytecode.init_code)),
+            deploy_code: format!("0x{}", hex::encode(&bytecode.runtime_code)),
+            storage_keys: Vec::new(),
+            storage_slots: slots
+                .iter()
+                .map(|s| format!("0x{}", hex::encode(s)))
+                .collect(),
+            target_depth: 2,
+            num_contracts: 2,
+            seed: None,
+            total_time: 0.0,
+            status: Default::default(),
+            contracts: (0..2)
+                .map(|index| ContractWithAuxiliaries {
+                    salt: slot_from_u64(index),
+                    contract_address: format!("0x{}", hex::encode([0x40 + index as u8; 20])),
+                    auxiliary_accounts: vec![format!("0x{}", hex::encode([0x50; 20]))],
+                    auxiliary_keys: Vec::new(),
+                })
+                .collect(),
+            account_trie: None,
+            key_encryption: None,
+            cluster: None,
+        }
+    }
+
+    #[test]
+    fn test_filler_attacks_deepest_slot() {
+        let result = result_with_runtime(Runtime::Attack);
+        let fillers = state_test_fillers(&result, 1, 5, "Cancun").unwrap();
+        assert_eq!(fillers.len(), 1);
+
+        let filler = &fillers["worstCaseSlots2Depth2_0"];
+        let contract = format!("0x{}", hex::encode([0x40; 20]));
+        assert_eq!(filler.transaction.to, contract);
+        assert_eq!(
+            filler.transaction.data[0],
+            format!("0x{}{:064x}", hex::encode(selector("attack(uint256)")), 5)
+        );
+        // Sender, auxiliary and contract
+        assert_eq!(filler.pre.len(), 3);
+        assert!(
+            filler
+                .pre
+                .contains_key("0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b")
+        );
+
+        let storage = &filler.expect[0].result[&contract].storage;
+        let slot = |n| format!("0x{}", hex::encode(slot_from_u64(n)));
+        assert_eq!(storage[&slot(7)], slot(1));
+        assert_eq!(storage[&slot(9)], slot(5));
+    }
+
+    #[test]
+    fn test_filler_needs_attack_function() {
+        let result = result_with_runtime(Runtime::Minimal);
+        assert!(state_test_fillers(&result, 1, 5, "Cancun").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! # Storage Layout Module
+//!
+//! Describes where the mined mapping lives in a contract's storage, so branches can be mined
+//! for real token layouts instead of only `mapping(address => uint256)` at slot 0.
+//!
+//! A layout is a root slot (a declared slot or an ERC-7201 namespaced root), an optional
+//! chain of fixed outer mapping keys (e.g. the owner in `allowance[owner][spender]`), and the
+//! type of the mined inner key. How keys and slots are hashed together is up to the
+//! `KeyDerivation` passed alongside the layout.
+//!
+//! ## Key Functions
+//! - `StorageLayout::storage_slot`: Computes the slot of a mined key under this layout
+//! - `erc7201_root`: Computes an ERC-7201 namespaced storage root
+//! - `address_key`: Left-pads an address to a 32-byte mapping key
+//! - `parse_address`, `parse_word`: Parse addresses and 32-byte words from hex
+
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::key_derivation::KeyDerivation;
+
+/// Solidity type of the mined mapping key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    /// `address` keys (20 bytes, left-padded to 32)
+    #[default]
+    Address,
+    /// `uint256` keys (full 32-byte word)
+    Uint256,
+    /// `bytes32` keys (full 32-byte word)
+    Bytes32,
+}
+
+impl KeyType {
+    /// Number of bytes of the 32-byte key word that are free to mine
+    pub fn width(self) -> usize {
+        match self {
+            KeyType::Address => 20,
+            KeyType::Uint256 | KeyType::Bytes32 => 32,
+        }
+    }
+}
+
+/// Location of the mapping whose keys are mined
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageLayout {
+    /// Slot of the outermost mapping
+    pub root_slot: [u8; 32],
+    /// Fixed keys of the enclosing mappings, outermost first. The mined key indexes the
+    /// mapping reached after applying all of them
+    pub outer_keys: Vec<[u8; 32]>,
+    /// Type of the mined (innermost) key
+    pub key_type: KeyType,
+}
+
+impl Default for StorageLayout {
+    fn default() -> Self {
+        StorageLayout::mapping(crate::storage_miner::ERC20_BALANCES_SLOT)
+    }
+}
+
+impl StorageLayout {
+    /// A plain `mapping(address => ...)` declared at `slot`
+    pub fn mapping(slot: u64) -> Self {
+        StorageLayout {
+            root_slot: slot_from_u64(slot),
+            outer_keys: Vec::new(),
+            key_type: KeyType::Address,
+        }
+    }
+
+    /// A mapping stored at `offset` slots into the ERC-7201 namespace `namespace`
+    pub fn erc7201(namespace: &str, offset: u64) -> Self {
+        StorageLayout {
+            root_slot: add_to_slot(&erc7201_root(namespace), offset),
+            outer_keys: Vec::new(),
+            key_type: KeyType::Address,
+        }
+    }
+
+    /// Slot of the innermost mapping, after applying all fixed outer keys.
+    /// Outer keys are treated as full 32-byte words
+    pub fn mapping_slot(&self, derivation: &dyn KeyDerivation) -> [u8; 32] {
+        self.outer_keys
+            .iter()
+            .fold(self.root_slot, |slot, outer_key| {
+                derivation.derive(outer_key, KeyType::Bytes32, &slot)
+            })
+    }
+
+    /// Storage slot of `key` (a 32-byte ABI-encoded key) under this layout
+    pub fn storage_slot(&self, key: &[u8; 32], derivation: &dyn KeyDerivation) -> [u8; 32] {
+        derivation.derive(key, self.key_type, &self.mapping_slot(derivation))
+    }
+
+    /// The innermost mapping slot as a `u64`, if it fits (as the CUDA kernel requires)
+    pub fn mapping_slot_u64(&self, derivation: &dyn KeyDerivation) -> Option<u64> {
+        let slot = self.mapping_slot(derivation);
+        if slot[..24].iter().any(|&b| b != 0) {
+            return None;
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&slot[24..]);
+        Some(u64::from_be_bytes(bytes))
+    }
+}
+
+/// ERC-7201 root: `keccak256(abi.encode(uint256(keccak256(namespace)) - 1)) & ~bytes32(uint256(0xff))`
+pub fn erc7201_root(namespace: &str) -> [u8; 32] {
+    let mut inner = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(namespace.as_bytes());
+    hasher.finalize(&mut inner);
+
+    // Subtract one with borrow
+    for byte in inner.iter_mut().rev() {
+        let (value, borrow) = byte.overflowing_sub(1);
+        *byte = value;
+        if !borrow {
+            break;
+        }
+    }
+
+    let mut root = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(&inner);
+    hasher.finalize(&mut root);
+    root[31] = 0;
+    root
+}
+
+/// Left-pad an address to a 32-byte mapping key
+pub fn address_key(address: &[u8; 20]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[12..].copy_from_slice(address);
+    key
+}
+
+/// Parse a 20-byte address from hex, with or without `0x`
+pub fn parse_address(hex_str: &str) -> Result<[u8; 20], String> {
+    let hex_str = hex_str.strip_prefix("0x").unwrap_or(hex_str);
+
+    if hex_str.len() != 40 {
+        return Err(format!(
+            "Address must be 40 hex characters, got {}",
+            hex_str.len()
+        ));
+    }
+
+    let bytes = hex::decode(hex_str).map_err(|e| format!("Invalid hex: {e}"))?;
+
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&bytes);
+    Ok(address)
+}
+
+/// Encode a slot number as a 32-byte big-endian word
+pub fn slot_from_u64(slot: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&slot.to_be_bytes());
+    bytes
+}
+
+/// Add `offset` to a 32-byte big-endian slot (wrapping, like EVM arithmetic)
+fn add_to_slot(slot: &[u8; 32], offset: u64) -> [u8; 32] {
+    let mut result = *slot;
+    let mut carry = offset as u128;
+    for byte in result.iter_mut().rev() {
+        if carry == 0 {
+            break;
+        }
+        let sum = *byte as u128 + (carry & 0xFF);
+        *byte = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+    result
+}
+
+/// Parse a 32-byte word from hex (`0x`-prefixed, left-padded if shorter) or a decimal number
+pub fn parse_word(s: &str) -> Result<[u8; 32], String> {
+    if let Some(hex_str) = s.strip_prefix("0x") {
+        if hex_str.is_empty() || hex_str.len() > 64 {
+            return Err(format!(
+                "Expected 1 to 64 hex characters, got {}",
+                hex_str.len()
+            ));
+        }
+        let padded = format!("{hex_str:0>64}");
+        let bytes = hex::decode(padded).map_err(|e| format!("Invalid hex: {e}"))?;
+        let mut word = [0u8; 32];
+        word.copy_from_slice(&bytes);
+        Ok(word)
+    } else {
+        let value: u64 = s.parse().map_err(|e| {
+            format!("Invalid number '{s}': {e} (use 0x-prefixed hex for large values)")
+        })?;
+        Ok(slot_from_u64(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_derivation::{SolidityDerivation, VyperDerivation};
+
+    #[test]
+    fn test_erc7201_root() {
+        // Reference value from EIP-7201 for the "example.main" namespace
+        assert_eq!(
+            hex::encode(erc7201_root("example.main")),
+            "183a6125c38840424c4a85fa12bab2ab606c4b6d0e7cc73c0c06ba5300eab500"
+        );
+    }
+
+    #[test]
+    fn test_nested_mapping_slot() {
+        // allowance[owner][spender] with `allowance` at slot 1
+        let owner = address_key(&[0x11; 20]);
+        let spender = address_key(&[0x22; 20]);
+        let layout = StorageLayout {
+            root_slot: slot_from_u64(1),
+            outer_keys: vec![owner],
+            key_type: KeyType::Address,
+        };
+
+        let solidity = &SolidityDerivation;
+        let inner = solidity.derive(&owner, KeyType::Address, &slot_from_u64(1));
+        let expected = solidity.derive(&spender, KeyType::Address, &inner);
+        assert_eq!(layout.storage_slot(&spender, solidity), expected);
+        assert_eq!(layout.mapping_slot_u64(solidity), None);
+        assert_eq!(
+            StorageLayout::mapping(7).mapping_slot_u64(solidity),
+            Some(7)
+        );
+
+        // Vyper reverses the operands at every nesting level
+        let vyper = &VyperDerivation;
+        let inner = vyper.derive(&owner, KeyType::Bytes32, &slot_from_u64(1));
+        let expected = vyper.derive(&spender, KeyType::Address, &inner);
+        assert_eq!(layout.storage_slot(&spender, vyper), expected);
+    }
+
+    #[test]
+    fn test_parse_word() {
+        assert_eq!(parse_word("5").unwrap(), slot_from_u64(5));
+        assert_eq!(parse_word("0x05").unwrap(), slot_from_u64(5));
+        assert!(parse_word("0x").is_err());
+        assert!(parse_word(&format!("0x{}", "0".repeat(65))).is_err());
+    }
+}
@@ -4,262 +4,905 @@
 //! scenarios in ERC20 contract storage tries. It finds addresses whose storage keys share
 //! increasingly long prefixes, forcing deep branches in the Modified Patricia Trie structure.
 //!
+//! In full-width mode the branch is followed by siblings for every level, one for each nibble
+//! at that position not yet taken by the path or by the level branching off above it, so each
+//! branch node on the path has all 16 children populated.
+//!
+//! A search that exhausts its `Budget` or is interrupted returns the branch it has so far: the
+//! levels up to the first one it did not find, closed by the anchor, with a status saying why
+//! it ended. The levels it found beyond that stay in the checkpoint for a resumed run.
+//!
 //! ## Key Functions
 //! - `mine_deep_branch`: Mines a sequence of addresses creating a deep storage trie branch
-//! - `calculate_storage_slot`: Computes the storage slot for an address in an ERC20 balance mapping
+//! - `calibrate`: Measures the hashrate of a config's key search without keeping anything
+//! - `calculate_storage_slot`: Computes the storage slot of a mapping key under a `StorageLayout`
+//! - `calculate_trie_key`: Computes the secure storage trie key (`keccak(slot)`) for a slot
 //! - `generate_contract`: Creates a Solidity contract with the mined storage slots
+//! - `generate_bytecode`: Emits the same contract's bytecode natively, without `solc`
+//! - `storage_trie_report`: Builds the resulting storage trie and reports each mined slot's path
+//! - `write_results`: Saves the mined branch as a `StorageMiningResult` JSON file
 
 use askama::Template;
 use log::{debug, info};
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::Duration;
 use tiny_keccak::{Hasher, Keccak};
 
+use crate::checkpoint::{Candidate, CheckpointFile, SearchRecord};
 #[cfg(feature = "cuda")]
 use crate::cuda_miner;
+use crate::error::Error;
+use crate::evm::{self, Bytecode, Runtime};
+use crate::key_derivation::{KeyDerivation, KeyScheme};
+use crate::mpt::{self, TrieReport};
+use crate::search::{
+    ANCHOR_NONCE, Budget, Frontier, LevelSearch, MAX_DEPTH, NonceKeys, SearchStatus,
+    check_search_params, count_shared_nibbles, measure_hashrate, nibble_at, random_seed,
+    run_workers,
+};
+#[cfg(feature = "cuda")]
+use crate::storage_layout::address_key;
+use crate::storage_layout::{KeyType, StorageLayout, parse_word};
+
+/// A mined mapping key together with the storage slot it resolves to
+pub struct TemplateSlot {
+    /// EIP-55 checksummed address, usable as a Solidity address literal
+    pub address: String,
+    /// Precomputed storage slot (hex, without `0x`)
+    pub storage_key: String,
+}
 
-/// Template for generating Solidity contract
+/// Template for generating Solidity contract that writes the precomputed storage slots
 #[derive(Template)]
 #[template(path = "WorstCaseERC20.sol.j2")]
 pub struct ContractTemplate {
-    addresses: Vec<String>,
+    slots: Vec<TemplateSlot>,
+    deepest: TemplateSlot,
+}
+
+/// Template for generating Solidity contract that writes through `balanceOf[addr]`
+#[derive(Template)]
+#[template(path = "WorstCaseERC20Mapping.sol.j2")]
+pub struct MappingContractTemplate {
+    slots: Vec<TemplateSlot>,
+    deepest: TemplateSlot,
+}
+
+/// How the generated contract writes the mined storage slots
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SlotWrite {
+    /// `sstore` to the precomputed storage slot (works for any storage layout)
+    #[default]
+    Key,
+    /// Assign `balanceOf[addr]` so Solidity derives the slot itself (default layout only)
+    Mapping,
 }
 
 /// Standard ERC20 balance mapping storage slot
 /// In OpenZeppelin's ERC20 implementation, _balances is the first state variable (slot 0)
 pub const ERC20_BALANCES_SLOT: u64 = 0;
 
+/// Which key the mined prefixes are matched against
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyMode {
+    /// Match `keccak(slot)`, the key Geth/Reth use in the secure storage trie
+    #[default]
+    TrieKey,
+    /// Match the Solidity storage slot itself (legacy behaviour of the `mined_assets`)
+    Slot,
+}
+
 #[derive(Clone, Debug)]
 pub struct StorageSlot {
-    pub address: [u8; 20],
+    /// Mined mapping key, ABI-encoded to 32 bytes (addresses are left-padded)
+    pub key: [u8; 32],
+    /// Type of the mined key, which decides how much of `key` is written out
+    pub key_type: KeyType,
     pub storage_key: [u8; 32],
+    pub trie_key: [u8; 32],
+    pub depth: usize,
+    /// Seconds into its search (the branch, or its level's siblings) when the key was found
+    pub found_after_secs: f64,
+    /// Full-width sibling branching off the path at nibble `depth`, rather than a level of the
+    /// branch itself
+    pub sibling: bool,
+}
+
+impl StorageSlot {
+    /// The mined key interpreted as an address (its low 20 bytes)
+    pub fn address(&self) -> [u8; 20] {
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&self.key[12..]);
+        address
+    }
+
+    /// The mined key as hex: the address of an address key, the full word otherwise
+    pub fn key_hex(&self) -> String {
+        format!("0x{}", hex::encode(&self.key[32 - self.key_type.width()..]))
+    }
+
+    /// The key whose prefix was mined under the given mode
+    pub fn mined_key(&self, key_mode: KeyMode) -> &[u8; 32] {
+        match key_mode {
+            KeyMode::TrieKey => &self.trie_key,
+            KeyMode::Slot => &self.storage_key,
+        }
+    }
+}
+
+/// Result structure for storage-branch mining
+#[derive(Serialize, Deserialize)]
+pub struct StorageMiningResult {
+    pub depth: usize,
+    /// Slot of the outermost mapping (32-byte hex)
+    pub base_slot: String,
+    /// Fixed keys of enclosing mappings, outermost first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outer_keys: Vec<String>,
+    #[serde(default)]
+    pub key_type: KeyType,
+    /// How mapping keys and slots are hashed together (`solidity`, `vyper` or `packed`)
+    #[serde(default = "default_key_derivation")]
+    pub key_derivation: String,
+    pub key_mode: KeyMode,
+    /// Seed of the run, which reproduces `accounts` (absent in older files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub total_time: f64,
+    /// How the search ended; unless complete, `accounts` is the shorter branch mined before it
+    /// stopped
+    #[serde(default)]
+    pub status: SearchStatus,
+    pub accounts: Vec<MinedStorageAccount>,
+    /// Storage trie built from the mined slots (plus any existing storage)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_trie: Option<TrieReport>,
+}
+
+/// A single mined level of the storage branch
+#[derive(Serialize, Deserialize)]
+pub struct MinedStorageAccount {
+    /// Mined key, for address-keyed mappings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Mined key (32-byte hex), for `uint256`/`bytes32`-keyed mappings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub storage_slot: String,
+    pub trie_key: String,
     pub depth: usize,
-    pub time_taken: f64, // Time taken to mine this level in seconds
+    /// Nibbles of the mined key shared with the deepest level (for the deepest level itself:
+    /// with the level before it, 0 if there is none)
+    pub shared_nibbles: usize,
+    /// Seconds spent on this key: since the key found before it in the same search (the
+    /// branch's levels, or one level's siblings). All levels are searched at once, so these add
+    /// up to the search's time
+    pub time_taken: f64,
+    /// Seconds into its search when the key was found (absent in older files)
+    #[serde(default)]
+    pub found_after_secs: f64,
+    /// Full-width sibling branching off the path at nibble `depth`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sibling: bool,
 }
 
-/// Calculate the storage slot for a given address in the balances mapping
-pub fn calculate_storage_slot(address: &[u8; 20], base_slot: u64) -> [u8; 32] {
+fn default_key_derivation() -> String {
+    "solidity".to_string()
+}
+
+impl StorageMiningResult {
+    /// Build the serializable result from a mined branch
+    pub fn from_branch(mined: &MinedBranch, config: &StorageMiningConfig, total_time: f64) -> Self {
+        let branch = &mined.branch;
+        let StorageMiningConfig {
+            key_mode,
+            ref layout,
+            ref derivation,
+            seed,
+            ..
+        } = *config;
+        let is_address = layout.key_type == KeyType::Address;
+        let levels = branch_levels(branch);
+        let accounts = branch
+            .iter()
+            .zip(search_durations(branch))
+            .map(|(slot, time_taken)| MinedStorageAccount {
+                address: is_address.then(|| format!("0x{}", hex::encode(slot.address()))),
+                key: (!is_address).then(|| format!("0x{}", hex::encode(slot.key))),
+                storage_slot: format!("0x{}", hex::encode(slot.storage_key)),
+                trie_key: format!("0x{}", hex::encode(slot.trie_key)),
+                depth: slot.depth,
+                shared_nibbles: shared_with_branch(slot, &levels, key_mode),
+                time_taken,
+                found_after_secs: slot.found_after_secs,
+                sibling: slot.sibling,
+            })
+            .collect();
+
+        StorageMiningResult {
+            depth: levels.len(),
+            base_slot: format!("0x{}", hex::encode(layout.root_slot)),
+            outer_keys: layout
+                .outer_keys
+                .iter()
+                .map(|k| format!("0x{}", hex::encode(k)))
+                .collect(),
+            key_type: layout.key_type,
+            key_derivation: derivation.name().to_string(),
+            key_mode,
+            seed: Some(seed),
+            total_time,
+            status: mined.status,
+            accounts,
+            storage_trie: None,
+        }
+    }
+}
+
+/// A mined branch, with how its search ended
+pub struct MinedBranch {
+    /// Levels from the shallowest to the anchor, after any full-width siblings
+    pub branch: Vec<StorageSlot>,
+    pub status: SearchStatus,
+}
+
+/// Calculate the storage slot of a mined mapping key under the given layout
+pub fn calculate_storage_slot(
+    key: &[u8; 32],
+    layout: &StorageLayout,
+    derivation: &dyn KeyDerivation,
+) -> [u8; 32] {
+    // The derivation is applied once per nesting level, e.g. keccak256(key || slot) in Solidity
+    layout.storage_slot(key, derivation)
+}
+
+/// Calculate the secure storage trie key for a storage slot: `keccak256(slot)`
+pub fn calculate_trie_key(storage_key: &[u8; 32]) -> [u8; 32] {
     let mut hasher = Keccak::v256();
-    let mut storage_key = [0u8; 32];
+    let mut trie_key = [0u8; 32];
+    hasher.update(storage_key);
+    hasher.finalize(&mut trie_key);
+    trie_key
+}
 
-    // For mappings in Solidity: keccak256(key || slot)
-    // Key is the address (padded to 32 bytes)
-    let mut padded_address = [0u8; 32];
-    padded_address[12..32].copy_from_slice(address);
+/// Parameters for mining a deep storage branch
+#[derive(Clone, Debug)]
+pub struct StorageMiningConfig {
+    /// Number of levels in the branch
+    pub target_depth: usize,
+    /// Number of mining threads
+    pub num_threads: usize,
+    /// Use CUDA for the expensive levels when available
+    pub use_cuda: bool,
+    /// Key whose prefix is matched
+    pub key_mode: KeyMode,
+    /// Mapping whose keys are mined
+    pub layout: StorageLayout,
+    /// How the mapping's keys are hashed into storage slots
+    pub derivation: Arc<dyn KeyDerivation>,
+    /// Also mine siblings for every level so every branch node on the path is full
+    pub full_width: bool,
+    /// Seed of the candidate keys, which determines the branch
+    pub seed: u64,
+    /// Limits on the attempts and time of the run
+    pub budget: Arc<Budget>,
+    /// File recording the progress of every search, if any
+    pub checkpoint: Option<Arc<CheckpointFile>>,
+}
 
-    // Slot index (padded to 32 bytes)
-    let mut slot_bytes = [0u8; 32];
-    slot_bytes[24..32].copy_from_slice(&base_slot.to_be_bytes());
+impl StorageMiningConfig {
+    /// Builder of a config mining `target_depth` levels of an ERC20 balance mapping, with the
+    /// CLI's defaults for everything else and a random seed
+    pub fn builder(target_depth: usize) -> StorageMiningConfigBuilder {
+        StorageMiningConfigBuilder {
+            config: StorageMiningConfig {
+                target_depth,
+                num_threads: num_cpus::get(),
+                use_cuda: false,
+                key_mode: KeyMode::default(),
+                layout: StorageLayout::default(),
+                derivation: KeyScheme::default().derivation(),
+                full_width: false,
+                seed: random_seed(),
+                budget: Arc::new(Budget::new()),
+                checkpoint: None,
+            },
+        }
+    }
 
-    // Hash the concatenated data
-    hasher.update(&padded_address);
-    hasher.update(&slot_bytes);
-    hasher.finalize(&mut storage_key);
+    /// The searches of a run, each given by the shared nibbles its levels need: the levels
+    /// below the anchor, then for a full-width branch the siblings of each level
+    pub fn searches(&self) -> Vec<Vec<usize>> {
+        let mut searches = vec![(1..self.target_depth).collect()];
+        if self.full_width {
+            searches.extend(
+                (0..self.target_depth).map(|level| vec![level + 1; siblings_at_level(level)]),
+            );
+        }
+        searches
+    }
+}
 
-    storage_key
+/// Siblings a full-width branch mines at `level`: all nibbles but the path's at the root, and
+/// also not the one of the level branching off just above it further down
+fn siblings_at_level(level: usize) -> usize {
+    if level == 0 { 15 } else { 14 }
 }
 
-/// Mine for a deep branch by finding addresses sequentially, one depth at a time
-pub fn mine_deep_branch(
-    target_depth: usize,
-    num_threads: usize,
-    use_cuda: bool,
-) -> Vec<StorageSlot> {
-    let mut branch = Vec::new();
+/// Builds a `StorageMiningConfig`, checking it before any mining starts
+#[derive(Clone, Debug)]
+pub struct StorageMiningConfigBuilder {
+    config: StorageMiningConfig,
+}
 
-    info!("Starting sequential mining for {target_depth} levels");
+impl StorageMiningConfigBuilder {
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.config.num_threads = num_threads;
+        self
+    }
 
-    // For each depth level, find an address that creates the right prefix collision
-    for current_depth in 0..target_depth {
-        let level_start = Instant::now();
+    pub fn use_cuda(mut self, use_cuda: bool) -> Self {
+        self.config.use_cuda = use_cuda;
+        self
+    }
 
-        // Each level should share an increasing number of nibbles:
-        // Level 1: any address (0 shared nibbles required)
-        // Level 2: 1 shared nibble with level 1
-        // Level 3: 2 shared nibbles with levels 1 & 2
-        // Level N: N-1 shared nibbles with all previous levels
-        let required_prefix_nibbles = current_depth;
+    pub fn key_mode(mut self, key_mode: KeyMode) -> Self {
+        self.config.key_mode = key_mode;
+        self
+    }
+
+    pub fn layout(mut self, layout: StorageLayout) -> Self {
+        self.config.layout = layout;
+        self
+    }
+
+    /// Hash mapping keys the way `scheme`'s compiler does
+    pub fn key_scheme(self, scheme: KeyScheme) -> Self {
+        self.derivation(scheme.derivation())
+    }
+
+    pub fn derivation(mut self, derivation: Arc<dyn KeyDerivation>) -> Self {
+        self.config.derivation = derivation;
+        self
+    }
+
+    pub fn full_width(mut self, full_width: bool) -> Self {
+        self.config.full_width = full_width;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn budget(mut self, budget: Arc<Budget>) -> Self {
+        self.config.budget = budget;
+        self
+    }
+
+    pub fn checkpoint(mut self, checkpoint: Arc<CheckpointFile>) -> Self {
+        self.config.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub fn build(self) -> Result<StorageMiningConfig, Error> {
+        check_search_params(self.config.target_depth, self.config.num_threads)?;
+        Ok(self.config)
+    }
+}
 
+/// What the workers hash and compare while mining one level
+#[derive(Clone)]
+struct PrefixSearch {
+    target: [u8; 32],
+    key_mode: KeyMode,
+    key_type: KeyType,
+    /// Slot of the innermost mapping, precomputed once per search
+    mapping_slot: [u8; 32],
+    derivation: Arc<dyn KeyDerivation>,
+    /// Name of the candidate stream, which a checkpoint records the search under
+    stream: String,
+    /// Candidate keys of this search
+    keys: NonceKeys,
+}
+
+impl PrefixSearch {
+    /// The search of `config`'s mapping keys in candidate stream `stream`, matched against
+    /// `target`
+    fn new(config: &StorageMiningConfig, stream: String, target: [u8; 32]) -> Self {
+        PrefixSearch {
+            target,
+            key_mode: config.key_mode,
+            key_type: config.layout.key_type,
+            mapping_slot: config.layout.mapping_slot(config.derivation.as_ref()),
+            derivation: Arc::clone(&config.derivation),
+            keys: NonceKeys::new(config.seed, &stream),
+            stream,
+        }
+    }
+
+    /// The key whose prefix is matched for a candidate mapping key
+    fn mined_key(&self, key: &[u8; 32]) -> [u8; 32] {
+        // Derive the storage slot against the precomputed innermost mapping slot
+        let storage_key = self
+            .derivation
+            .derive(key, self.key_type, &self.mapping_slot);
+
+        // The secure trie is keyed by keccak(slot), so hash once more in trie-key mode
+        match self.key_mode {
+            KeyMode::TrieKey => calculate_trie_key(&storage_key),
+            KeyMode::Slot => storage_key,
+        }
+    }
+
+    /// Candidate key for `nonce`
+    fn key(&self, nonce: u64) -> [u8; 32] {
+        self.keys.key(self.key_type.width(), nonce)
+    }
+}
+
+/// Mine for a deep branch. An anchor key is the deepest level, and level `i` below it is a key
+/// sharing exactly `i + 1` nibbles with the anchor (the last one may share more), so each level
+/// branches off the anchor's path at its own nibble. All levels are searched at once: every
+/// candidate is checked against each outstanding level, so the whole branch costs about as much
+/// as its deepest level alone. Without CUDA the branch is determined by the seed alone
+pub fn mine_deep_branch(config: &StorageMiningConfig) -> Result<MinedBranch, Error> {
+    let StorageMiningConfig {
+        target_depth,
+        num_threads,
+        use_cuda,
+        key_mode,
+        ref layout,
+        ref derivation,
+        full_width,
+        seed,
+        ref budget,
+        ref checkpoint,
+    } = *config;
+
+    info!("Starting multi-target mining for {target_depth} levels");
+    info!("Matching prefixes of: {key_mode:?}");
+    info!("Key derivation: {}", derivation.name());
+    info!("Seed: {seed}");
+
+    if target_depth == 0 {
+        return Ok(MinedBranch {
+            branch: Vec::new(),
+            status: SearchStatus::Complete,
+        });
+    }
+
+    // The anchor can be anything - every other level is mined against it
+    let mut search = PrefixSearch::new(config, "storage".to_string(), [0u8; 32]);
+    let anchor = search.key(ANCHOR_NONCE);
+    search.target = search.mined_key(&anchor);
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.bind_storage_target(&search.target)?;
+    }
+    budget.progress_monitor().plan(&config.searches());
+    budget.progress_monitor().next_search();
+
+    // CUDA is only used for derivations the kernel implements
+    let (levels, elapsed, mut status) = mine_levels(
+        &search,
+        target_depth - 1,
+        layout,
+        num_threads,
+        use_cuda && derivation.cuda_compatible(),
+        budget,
+        checkpoint.as_deref(),
+    )?;
+
+    let mut branch: Vec<StorageSlot> = levels
+        .into_iter()
+        .chain(std::iter::once((anchor, elapsed)))
+        .enumerate()
+        .map(|(depth, (key, found_after_secs))| {
+            let storage_key = calculate_storage_slot(&key, layout, derivation.as_ref());
+            StorageSlot {
+                key,
+                key_type: layout.key_type,
+                storage_key,
+                trie_key: calculate_trie_key(&storage_key),
+                depth,
+                found_after_secs,
+                sibling: false,
+            }
+        })
+        .collect();
+
+    for slot in &branch {
         info!(
-            "Mining level {}/{} (requires {} matching nibbles)",
-            current_depth + 1,
-            target_depth,
-            required_prefix_nibbles
+            "Level {} found after {:.2} seconds - Key: 0x{}, Storage: 0x{}..., Trie key: 0x{}...",
+            slot.depth + 1,
+            slot.found_after_secs,
+            hex::encode(&slot.key[32 - layout.key_type.width()..][..4]),
+            hex::encode(&slot.storage_key[..4]),
+            hex::encode(&slot.trie_key[..4])
         );
+    }
 
-        // Mine for an address at this depth level
-        let address = if current_depth == 0 {
-            // First address can be anything - just generate a random one
-            let mut addr = [0u8; 20];
-            fastrand::fill(&mut addr);
-            addr
-        } else {
-            // Need to find an address that shares the required prefix with the PREVIOUS level
-            // (not all previous addresses, just the immediately preceding one)
-            let previous_slot: &StorageSlot = &branch[branch.len() - 1];
-            // Only use CUDA for depth 8+ where the computational cost justifies the overhead
-            let use_cuda_for_level = use_cuda && current_depth >= 8;
-            match mine_address_for_prefix(
-                &previous_slot.storage_key,
-                required_prefix_nibbles,
-                num_threads,
-                use_cuda_for_level,
-            ) {
-                Some(addr) => addr,
-                None => {
-                    info!(
-                        "Failed to find address for level {} - stopping",
-                        current_depth + 1
-                    );
-                    break;
-                }
+    if !status.is_complete() {
+        log::warn!(
+            "Mining stopped ({status}) with {} of {target_depth} levels",
+            branch.len()
+        );
+    } else if full_width {
+        let (mut siblings, siblings_status) = mine_siblings(&branch, config)?;
+        status = siblings_status;
+        // Siblings go first so the deepest level stays last, as contract generation expects
+        siblings.append(&mut branch);
+        branch = siblings;
+    }
+
+    Ok(MinedBranch { branch, status })
+}
+
+/// Mine the siblings of every level of `branch`: keys sharing exactly `level` nibbles with
+/// the deepest key and covering every nibble value at position `level` the branch leaves
+/// free. A search that ends early keeps the siblings found up to then
+fn mine_siblings(
+    branch: &[StorageSlot],
+    config: &StorageMiningConfig,
+) -> Result<(Vec<StorageSlot>, SearchStatus), Error> {
+    let StorageMiningConfig {
+        num_threads,
+        key_mode,
+        ref layout,
+        ref derivation,
+        ref budget,
+        ref checkpoint,
+        ..
+    } = *config;
+    let deepest = &branch[branch.len() - 1];
+    let target = *deepest.mined_key(key_mode);
+    // Below the root, the level branching off above already holds one more nibble
+    let taken: Vec<u64> = (0..branch.len())
+        .map(|level| {
+            let path = 1 << nibble_at(&target, level);
+            match level.checked_sub(1) {
+                Some(above) => path | 1 << nibble_at(branch[above].mined_key(key_mode), level),
+                None => path,
             }
-        };
+        })
+        .collect();
 
-        let storage_key = calculate_storage_slot(&address, ERC20_BALANCES_SLOT);
+    info!("");
+    info!(
+        "Mining full-width siblings for {} levels ({} keys)",
+        branch.len(),
+        taken
+            .iter()
+            .map(|taken| 16 - taken.count_ones() as usize)
+            .sum::<usize>()
+    );
 
-        let level_time = level_start.elapsed();
+    let mut siblings = Vec::new();
+    for (level, &taken) in taken.iter().enumerate() {
+        budget.progress_monitor().next_search();
+        // Each level gets its own candidates, so siblings never repeat a level's key
+        let search = PrefixSearch::new(config, format!("siblings-{level}"), target);
+        let (keys, elapsed, status) = mine_siblings_at_level(
+            &search,
+            (level, taken),
+            num_threads,
+            budget,
+            checkpoint.as_deref(),
+        )?;
+        let found = keys.len();
 
-        branch.push(StorageSlot {
-            address,
-            storage_key,
-            depth: current_depth,
-            time_taken: level_time.as_secs_f64(),
-        });
+        for (key, found_after_secs) in keys {
+            let storage_key = calculate_storage_slot(&key, layout, derivation.as_ref());
+            siblings.push(StorageSlot {
+                key,
+                key_type: layout.key_type,
+                storage_key,
+                trie_key: calculate_trie_key(&storage_key),
+                depth: level,
+                found_after_secs,
+                sibling: true,
+            });
+        }
 
+        if !status.is_complete() {
+            log::warn!(
+                "Mining stopped ({status}) with {} siblings of level {}/{}",
+                found,
+                level + 1,
+                branch.len()
+            );
+            return Ok((siblings, status));
+        }
         info!(
-            "Level {} found in {:.2} seconds - Address: 0x{}, Storage: 0x{}...",
-            current_depth + 1,
-            level_time.as_secs_f64(),
-            hex::encode(&address[..4]),
-            hex::encode(&storage_key[..4])
+            "Siblings of level {}/{} found in {:.2} seconds",
+            level + 1,
+            branch.len(),
+            elapsed
         );
     }
 
-    branch
+    Ok((siblings, SearchStatus::Complete))
+}
+
+/// Find one key per nibble value at `position` outside the `taken` mask, whose mined key
+/// shares exactly `position` nibbles with the target, each with the seconds into the search
+/// it was found at, the seconds the search took and how it ended. CPU only
+fn mine_siblings_at_level(
+    search: &PrefixSearch,
+    (position, taken): (usize, u64),
+    num_threads: usize,
+    budget: &Budget,
+    checkpoint: Option<&CheckpointFile>,
+) -> Result<(Vec<MinedKey>, f64, SearchStatus), Error> {
+    // One slot per nibble value; the taken nibbles are never filled
+    let record = SearchRecord::new(checkpoint, &search.stream);
+    let (progress, next) = record.resume(16, candidate_key)?;
+    let progress = progress.requiring(vec![position + 1; 16]);
+    let goal = progress.all_slots() & !taken;
+
+    let mut status = SearchStatus::Complete;
+    if let Some(start) = next {
+        run_workers(
+            &progress,
+            goal,
+            budget.nonces(0, start),
+            num_threads,
+            budget,
+            |thread_id, frontier| {
+                mine_sibling_worker(
+                    thread_id,
+                    num_threads,
+                    search,
+                    position,
+                    &progress,
+                    frontier,
+                    goal,
+                );
+            },
+            |scanned| record.save(&progress, scanned, key_candidate),
+        )?;
+        status = progress.status(goal);
+        if status.is_complete() {
+            record.complete(&progress, key_candidate);
+        }
+    }
+
+    let elapsed = progress.elapsed();
+    Ok((progress.into_slots(), elapsed, status))
 }
 
-/// Mine for a single address that shares a prefix with the target storage key
-fn mine_address_for_prefix(
-    target_storage_key: &[u8; 32],
-    required_prefix_nibbles: usize,
+fn mine_sibling_worker(
+    thread_id: usize,
+    num_threads: usize,
+    search: &PrefixSearch,
+    position: usize,
+    progress: &LevelSearch<[u8; 32]>,
+    frontier: &Frontier,
+    goal: u64,
+) {
+    let mut nonce = frontier.first(thread_id);
+    let mut attempts = 0u64;
+
+    const BATCH_SIZE: u64 = 1000;
+
+    loop {
+        if attempts.is_multiple_of(BATCH_SIZE) {
+            frontier.record(thread_id, nonce);
+            if progress.settled(goal, nonce) {
+                break;
+            }
+        }
+
+        attempts += 1;
+
+        let key = search.key(nonce);
+        let mined_key = search.mined_key(&key);
+
+        if has_nibble_prefix(&mined_key, &search.target, position) {
+            // Taken nibbles are outside the goal, so they are never filled
+            let nibble = nibble_at(&mined_key, position);
+            if goal & (1 << nibble) != 0 && progress.improves(nibble, nonce) {
+                progress.fill(nibble, nonce, key);
+            }
+        }
+
+        nonce += num_threads as u64;
+    }
+}
+
+/// Measure the CPU hashrate of `config`'s key search: candidates per second on its threads,
+/// mining a throwaway branch too deep to finish for about `duration`
+pub fn calibrate(config: &StorageMiningConfig, duration: Duration) -> Result<f64, Error> {
+    let search = PrefixSearch::new(config, "calibration".to_string(), [0u8; 32]);
+    measure_hashrate(duration, |budget| {
+        mine_levels(
+            &search,
+            MAX_DEPTH - 1,
+            &config.layout,
+            config.num_threads,
+            false,
+            budget,
+            None,
+        )
+        .map(|_| ())
+    })
+}
+
+/// A mined key with the seconds into the search it was found at
+type MinedKey = ([u8; 32], f64);
+
+/// Minimum shared nibbles for a level to be worth handing to the CUDA kernel
+#[cfg(feature = "cuda")]
+const CUDA_MIN_NIBBLES: usize = 8;
+
+/// Mine `num_levels` keys whose mined keys share 1, 2, ... nibbles with the search target,
+/// checking every candidate against all outstanding levels at once. Also returns the seconds
+/// the search took and how it ended; a search that ends early returns the levels up to the
+/// first one it did not find
+fn mine_levels(
+    search: &PrefixSearch,
+    num_levels: usize,
+    #[allow(unused_variables)] layout: &StorageLayout,
     num_threads: usize,
     #[allow(unused_variables)] use_cuda: bool,
-) -> Option<[u8; 20]> {
+    budget: &Budget,
+    checkpoint: Option<&CheckpointFile>,
+) -> Result<(Vec<MinedKey>, f64, SearchStatus), Error> {
+    let record = SearchRecord::new(checkpoint, &search.stream);
+    let (progress, next) = record.resume(num_levels, candidate_key)?;
+    // A complete search has nothing left to mine
+    let Some(start) = next else {
+        let elapsed = progress.elapsed();
+        return Ok((
+            progress.into_leading_slots(),
+            elapsed,
+            SearchStatus::Complete,
+        ));
+    };
+
     #[cfg(feature = "cuda")]
     {
-        if use_cuda && cuda_miner::cuda_available() {
-            info!(
-                "Using CUDA acceleration for level with {} required nibbles",
-                required_prefix_nibbles
-            );
-            // Try CUDA mining first
-            if let Some((address, _storage_key)) = cuda_miner::mine_with_cuda(
-                target_storage_key,
-                required_prefix_nibbles,
-                ERC20_BALANCES_SLOT,
-            ) {
-                return Some(address);
+        // The kernel only handles address keys with a 64-bit mapping slot
+        let cuda_slot = layout
+            .mapping_slot_u64(search.derivation.as_ref())
+            .filter(|_| search.key_type == KeyType::Address);
+        if let (true, Some(base_slot)) = (use_cuda && cuda_miner::cuda_available(), cuda_slot) {
+            // Only levels of 8+ nibbles justify the overhead; the CPU fills the cheap ones
+            let cheap = progress.levels_below(CUDA_MIN_NIBBLES);
+            run_level_workers(
+                search,
+                &progress,
+                cheap,
+                start,
+                num_threads,
+                budget,
+                &record,
+            )?;
+
+            // The kernel does not take a budget, so a search stopped on the cheap levels stays so
+            let cheap_done = progress.status(cheap).is_complete();
+            while cheap_done
+                && let Some(level) = (0..num_levels).find(|&i| !progress.reached(1 << i))
+            {
+                info!(
+                    "Using CUDA acceleration for level with {} required nibbles",
+                    level + 1
+                );
+                let Some((address, _storage_key)) = cuda_miner::mine_with_cuda(
+                    &search.target,
+                    level + 1,
+                    base_slot,
+                    search.key_mode == KeyMode::TrieKey,
+                ) else {
+                    info!("CUDA mining failed, falling back to CPU");
+                    break;
+                };
+                // The kernel matches a prefix, so the key may fill a deeper level instead.
+                // Its results are not reproducible; nonce 0 keeps the CPU from replacing them
+                let key = address_key(&address);
+                let shared = count_shared_nibbles(&search.mined_key(&key), &search.target);
+                if let Some(level) = progress.level_for(shared) {
+                    progress.fill(level, 0, key);
+                    record.save(&progress, start, key_candidate);
+                }
             }
-            info!("CUDA mining failed, falling back to CPU");
         }
     }
-    let result = Arc::new(Mutex::new(None));
-    let found = Arc::new(AtomicBool::new(false));
 
-    let handles: Vec<_> = (0..num_threads)
-        .map(|thread_id| {
-            let result_clone = Arc::clone(&result);
-            let found_clone = Arc::clone(&found);
-            let target = *target_storage_key;
+    let goal = progress.all_slots();
+    run_level_workers(search, &progress, goal, start, num_threads, budget, &record)?;
+    let status = progress.status(goal);
+    if status.is_complete() {
+        record.complete(&progress, key_candidate);
+    }
 
-            thread::spawn(move || {
-                mine_worker_for_prefix(
-                    thread_id,
-                    &target,
-                    required_prefix_nibbles,
-                    result_clone,
-                    found_clone,
-                );
-            })
-        })
-        .collect();
+    let elapsed = progress.elapsed();
+    Ok((progress.into_leading_slots(), elapsed, status))
+}
 
-    for handle in handles {
-        handle.join().unwrap();
+/// Run CPU workers from nonce `start` until every level in `goal` holds a candidate no worker
+/// can improve on or the budget runs out, saving their progress along the way
+fn run_level_workers(
+    search: &PrefixSearch,
+    progress: &LevelSearch<[u8; 32]>,
+    goal: u64,
+    start: u64,
+    num_threads: usize,
+    budget: &Budget,
+    record: &SearchRecord,
+) -> Result<(), Error> {
+    run_workers(
+        progress,
+        goal,
+        budget.nonces(0, start),
+        num_threads,
+        budget,
+        |thread_id, frontier| {
+            mine_levels_worker(thread_id, num_threads, search, progress, frontier, goal);
+        },
+        |scanned| record.save(progress, scanned, key_candidate),
+    )
+}
+
+/// A mined key as written to a checkpoint
+fn key_candidate(key: &[u8; 32]) -> Candidate {
+    Candidate {
+        value: format!("0x{}", hex::encode(key)),
+        secret_key: None,
     }
+}
 
-    *result.lock().unwrap()
+/// A mined key read back from a checkpoint
+fn candidate_key(candidate: &Candidate) -> Result<[u8; 32], String> {
+    parse_word(&candidate.value)
 }
 
-fn mine_worker_for_prefix(
+fn mine_levels_worker(
     thread_id: usize,
-    target_prefix: &[u8; 32],
-    required_nibbles: usize,
-    result: Arc<Mutex<Option<[u8; 20]>>>,
-    found: Arc<AtomicBool>,
+    num_threads: usize,
+    search: &PrefixSearch,
+    progress: &LevelSearch<[u8; 32]>,
+    frontier: &Frontier,
+    goal: u64,
 ) {
-    // Use fastrand for faster non-crypto PRNG
-    let mut rng = fastrand::Rng::new();
+    // Thread `t` walks nonces `start + t, start + t + n, ...`, disjoint from the other threads
+    let mut nonce = frontier.first(thread_id);
     let mut attempts = 0u64;
 
-    // Pre-compute the slot bytes since they don't change
-    let mut slot_bytes = [0u8; 32];
-    slot_bytes[24..32].copy_from_slice(&ERC20_BALANCES_SLOT.to_be_bytes());
-
-    // Batch size for checking - check found flag less often
+    // Batch size for checking - check the filled levels less often
     const BATCH_SIZE: u64 = 1000;
 
     loop {
-        // Check if another thread found a result (but only every BATCH_SIZE attempts)
-        // Using Relaxed ordering - sufficient for a stop flag, no synchronization needed
-        if attempts % BATCH_SIZE == 0 && found.load(Ordering::Relaxed) {
-            break;
+        // Stop once no goal level can improve (but only check every BATCH_SIZE attempts)
+        if attempts.is_multiple_of(BATCH_SIZE) {
+            frontier.record(thread_id, nonce);
+            if progress.settled(goal, nonce) {
+                break;
+            }
         }
 
         attempts += 1;
-        if attempts % 1000000 == 0 {
+
+        let key = search.key(nonce);
+        let mined_key = search.mined_key(&key);
+
+        // One comparison covers every outstanding level: the shared length picks the level
+        let shared = count_shared_nibbles(&mined_key, &search.target);
+        if let Some(level) = progress.level_for(shared)
+            && progress.improves(level, nonce)
+            && progress.fill(level, nonce, key)
+        {
             debug!(
-                "Thread {}: {} million attempts",
-                thread_id,
-                attempts / 1000000
+                "Thread {thread_id} filled level {} at nonce {nonce}",
+                level + 1
             );
         }
 
-        // Generate a random address using fastrand
-        let mut address = [0u8; 20];
-        rng.fill(&mut address);
-
-        // Calculate storage key inline for better performance
-        use tiny_keccak::{Hasher, Keccak};
-        let mut hasher = Keccak::v256();
-        let mut storage_key = [0u8; 32];
-
-        // Prepare padded address
-        let mut padded_address = [0u8; 32];
-        padded_address[12..32].copy_from_slice(&address);
-
-        // Hash in one go
-        hasher.update(&padded_address);
-        hasher.update(&slot_bytes);
-        hasher.finalize(&mut storage_key);
-
-        // Check if it matches the required prefix
-        if has_nibble_prefix(&storage_key, target_prefix, required_nibbles) {
-            // Use compare_exchange for atomic flag setting
-            if found
-                .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
-                .is_ok()
-            {
-                let mut result_lock = result.lock().unwrap();
-                *result_lock = Some(address);
-                info!("Thread {thread_id} found matching address after {attempts} attempts");
-            }
-            break;
-        }
+        nonce += num_threads as u64;
     }
 }
 
@@ -290,119 +933,545 @@ pub fn has_nibble_prefix(a: &[u8; 32], b: &[u8; 32], nibbles: usize) -> bool {
     true
 }
 
-pub fn print_results(branch: &[StorageSlot], elapsed_seconds: f64) {
+pub fn print_results(branch: &[StorageSlot], config: &StorageMiningConfig, elapsed_seconds: f64) {
+    let StorageMiningConfig {
+        key_mode,
+        ref layout,
+        ref derivation,
+        ..
+    } = *config;
+
     info!("");
     info!("╔════════════════════════════════════════════════════════════════════════╗");
     info!("║                          MINING RESULTS                                ║");
     info!("╚════════════════════════════════════════════════════════════════════════╝");
     info!("");
-    info!("Total depth achieved: {}", branch.len());
+    let levels = branch_levels(branch);
+    info!("Total depth achieved: {}", levels.len());
     info!("Total time taken: {elapsed_seconds:.2} seconds");
-    info!("ERC20 balance mapping slot: {ERC20_BALANCES_SLOT}");
+    info!("Mapping root slot: 0x{}", hex::encode(layout.root_slot));
+    for (i, outer_key) in layout.outer_keys.iter().enumerate() {
+        info!("Outer key {}: 0x{}", i + 1, hex::encode(outer_key));
+    }
+    if let Some(slot) = layout.mapping_slot_u64(derivation.as_ref()) {
+        info!("Mined mapping slot: {slot}");
+    }
+    info!("Mined key type: {:?}", layout.key_type);
+    info!("Key derivation: {}", derivation.name());
+    info!("Mined key: {key_mode:?}");
     info!("");
     info!("═══ Branch Structure (Sequential Addresses) ═══");
     info!("");
 
-    // Show the common prefix that all addresses share
-    if branch.len() > 1 {
-        let common_nibbles = branch.len() - 1;
-        let common_prefix = get_common_prefix(branch);
-        info!("Common prefix ({common_nibbles} nibbles): 0x{common_prefix}");
+    // Show the path every level branches off
+    if levels.len() > 1 {
+        let path_nibbles = levels.len() - 1;
+        let path_prefix = get_path_prefix(&levels, key_mode);
+        info!("Deepest path ({path_nibbles} nibbles): 0x{path_prefix}");
         info!("");
     }
 
     // Print each address in the branch
-    for (i, slot) in branch.iter().enumerate() {
+    for (i, slot) in levels.iter().enumerate() {
         info!("Level {} (Depth {}):", i + 1, slot.depth);
-        info!("  Address:     0x{}", hex::encode(slot.address));
+        match layout.key_type {
+            KeyType::Address => info!("  Address:     0x{}", hex::encode(slot.address())),
+            _ => info!("  Key:         0x{}", hex::encode(slot.key)),
+        }
         info!("  Storage Key: 0x{}", hex::encode(slot.storage_key));
+        info!("  Trie Key:    0x{}", hex::encode(slot.trie_key));
 
-        if i > 0 {
-            // Show how many nibbles this shares with the previous level
-            let shared = count_shared_nibbles(&branch[i - 1].storage_key, &slot.storage_key);
-            info!("  Shares {shared} nibbles with previous level");
+        let shared = shared_with_branch(slot, &levels, key_mode);
+        if i + 1 < levels.len() {
+            info!("  Shares {shared} nibbles with the deepest level");
+        } else if i > 0 {
+            info!("  Shares {shared} nibbles with the previous level");
         }
         info!("");
     }
 
     info!("═══ Statistics ═══");
-    info!("Total addresses mined: {}", branch.len());
+    info!("Total keys mined: {}", branch.len());
+    if branch.len() > levels.len() {
+        info!("Full-width siblings: {}", branch.len() - levels.len());
+    }
     info!("");
-    info!("Time per depth level:");
-    for (i, slot) in branch.iter().enumerate() {
+    info!("Levels found after:");
+    for (i, slot) in levels.iter().enumerate() {
         info!(
             "  Level {} (depth {}): {:.2} seconds",
             i + 1,
             slot.depth,
-            slot.time_taken
+            slot.found_after_secs
         );
     }
     info!("");
 }
 
-/// Get the common prefix shared by all addresses in the branch
-fn get_common_prefix(branch: &[StorageSlot]) -> String {
-    if branch.is_empty() {
-        return String::new();
-    }
+/// Seconds spent on each key of `branch`: since the key found before it in the same search,
+/// where the levels form one search and each level's siblings another
+fn search_durations(branch: &[StorageSlot]) -> Vec<f64> {
+    let search = |slot: &StorageSlot| slot.sibling.then_some(slot.depth);
+    branch
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            let previous = branch
+                .iter()
+                .enumerate()
+                .filter(|(j, other)| {
+                    search(other) == search(slot)
+                        && (other.found_after_secs, *j) < (slot.found_after_secs, i)
+                })
+                .map(|(_, other)| other.found_after_secs)
+                .fold(0.0, f64::max);
+            slot.found_after_secs - previous
+        })
+        .collect()
+}
 
-    let first_key = &branch[0].storage_key;
-    let min_shared = branch.len() - 1;
+/// The levels of a mined branch, without full-width siblings
+pub fn branch_levels(branch: &[StorageSlot]) -> Vec<&StorageSlot> {
+    branch.iter().filter(|slot| !slot.sibling).collect()
+}
+
+/// Nibbles a slot shares with the deepest level, or for the deepest level itself with the
+/// level before it (0 if there is none)
+pub fn shared_with_branch(slot: &StorageSlot, levels: &[&StorageSlot], key_mode: KeyMode) -> usize {
+    let is_deepest = !slot.sibling && slot.depth + 1 == levels.len();
+    let reference = if is_deepest {
+        slot.depth.checked_sub(1).and_then(|i| levels.get(i))
+    } else {
+        levels.last()
+    };
+    reference.map_or(0, |other| {
+        count_shared_nibbles(other.mined_key(key_mode), slot.mined_key(key_mode))
+    })
+}
+
+/// Get the prefix of the deepest key that the other levels branch off
+fn get_path_prefix(branch: &[&StorageSlot], key_mode: KeyMode) -> String {
+    let Some(deepest) = branch.last() else {
+        return String::new();
+    };
 
     // Convert to hex and take the appropriate number of nibbles
-    let hex_str = hex::encode(first_key);
-    hex_str.chars().take(min_shared).collect()
+    let hex_str = hex::encode(deepest.mined_key(key_mode));
+    hex_str.chars().take(branch.len() - 1).collect()
+}
+
+/// Build the storage trie the generated contract ends up with (every mined slot set to 1,
+/// next to `existing` slot/value pairs) and report the path of each mined slot
+pub fn storage_trie_report(
+    branch: &[StorageSlot],
+    existing: &[([u8; 32], [u8; 32])],
+) -> TrieReport {
+    let mined = branch
+        .iter()
+        .map(|slot| (slot.storage_key, crate::storage_layout::slot_from_u64(1)));
+    let trie = mpt::storage_trie(existing.iter().copied().chain(mined));
+    let keys: Vec<[u8; 32]> = branch.iter().map(|slot| slot.trie_key).collect();
+    TrieReport::new(&trie, &keys)
 }
 
-/// Count how many nibbles two storage keys share
-fn count_shared_nibbles(a: &[u8; 32], b: &[u8; 32]) -> usize {
-    let hex_a = hex::encode(a);
-    let hex_b = hex::encode(b);
+/// Write the mined branch to a JSON file
+pub fn write_results(
+    mined: &MinedBranch,
+    config: &StorageMiningConfig,
+    elapsed_seconds: f64,
+    storage_trie: &TrieReport,
+    output_path: &str,
+) -> Result<(), Error> {
+    let mut result = StorageMiningResult::from_branch(mined, config, elapsed_seconds);
+    result.storage_trie = Some(storage_trie.clone());
 
-    hex_a
+    let json = serde_json::to_string_pretty(&result)
+        .map_err(|e| Error::InvalidInput(format!("Failed to serialize to JSON: {e}")))?;
+    fs::write(output_path, json).map_err(|e| Error::io(output_path, e))?;
+    info!("Results saved to: {output_path}");
+    Ok(())
+}
+
+/// Format an address with its EIP-55 mixed-case checksum
+pub fn to_checksum_address(address: &[u8; 20]) -> String {
+    let hex_addr = hex::encode(address);
+
+    let mut hasher = Keccak::v256();
+    let mut hash = [0u8; 32];
+    hasher.update(hex_addr.as_bytes());
+    hasher.finalize(&mut hash);
+
+    let checksummed: String = hex_addr
         .chars()
-        .zip(hex_b.chars())
-        .take_while(|(ca, cb)| ca == cb)
-        .count()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = if i % 2 == 0 {
+                hash[i / 2] >> 4
+            } else {
+                hash[i / 2] & 0x0F
+            };
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    format!("0x{checksummed}")
+}
+
+fn template_slot(slot: &StorageSlot) -> TemplateSlot {
+    TemplateSlot {
+        address: to_checksum_address(&slot.address()),
+        storage_key: hex::encode(slot.storage_key),
+    }
 }
 
 /// Generate and compile the Solidity contract with hardcoded storage keys
-pub fn generate_contract(branch: &[StorageSlot]) {
+pub fn generate_contract(branch: &[StorageSlot], slot_write: SlotWrite) -> Result<(), Error> {
     info!("");
     info!("╔════════════════════════════════════════════════════════════════════════╗");
     info!("║                     CONTRACT GENERATION & COMPILATION                  ║");
     info!("╚════════════════════════════════════════════════════════════════════════╝");
     info!("");
 
+    let Some(deepest) = branch.last() else {
+        return Err(Error::InvalidInput(
+            "Cannot generate a contract from an empty branch".to_string(),
+        ));
+    };
+
     // Step 1: Generate the contract using Askama template
-    let addresses: Vec<String> = branch
-        .iter()
-        .map(|slot| hex::encode(slot.address))
-        .collect();
+    let slots: Vec<TemplateSlot> = branch.iter().map(template_slot).collect();
+    let deepest = template_slot(deepest);
 
-    let template = ContractTemplate {
-        addresses: addresses.clone(),
+    let rendered = match slot_write {
+        SlotWrite::Key => ContractTemplate { slots, deepest }.render(),
+        SlotWrite::Mapping => MappingContractTemplate { slots, deepest }.render(),
     };
 
-    let contract_source = match template.render() {
-        Ok(source) => source,
-        Err(e) => {
-            log::error!("Failed to render contract template: {e}");
-            return;
-        }
-    };
+    let contract_source = rendered
+        .map_err(|e| Error::Compiler(format!("Failed to render contract template: {e}")))?;
 
     // Ensure contracts directory exists
-    if let Err(e) = fs::create_dir_all("contracts") {
-        log::error!("Failed to create contracts directory: {e}");
-        return;
-    }
+    fs::create_dir_all("contracts").map_err(|e| Error::io("contracts", e))?;
 
     // Save the generated contract
     let contract_path = "contracts/WorstCaseERC20.sol";
-    if let Err(e) = fs::write(contract_path, &contract_source) {
-        log::error!("Failed to write contract: {e}");
-        return;
-    }
+    fs::write(contract_path, &contract_source).map_err(|e| Error::io(contract_path, e))?;
     info!("Generated contract saved to: {contract_path}");
+    Ok(())
+}
+
+/// Emit the contract's bytecode natively (no `solc`) and save its init code as hex
+pub fn generate_bytecode(branch: &[StorageSlot], runtime: Runtime) -> Result<Bytecode, Error> {
+    info!("");
+    info!("╔════════════════════════════════════════════════════════════════════════╗");
+    info!("║                        NATIVE BYTECODE GENERATION                      ║");
+    info!("╚════════════════════════════════════════════════════════════════════════╝");
+    info!("");
+
+    if branch.is_empty() {
+        return Err(Error::InvalidInput(
+            "Cannot generate a contract from an empty branch".to_string(),
+        ));
+    }
+
+    let slots: Vec<[u8; 32]> = branch.iter().map(|slot| slot.storage_key).collect();
+    let bytecode = evm::seeding_contract(&slots, runtime);
+
+    let mut hasher = Keccak::v256();
+    let mut init_code_hash = [0u8; 32];
+    hasher.update(&bytecode.init_code);
+    hasher.finalize(&mut init_code_hash);
+    info!(
+        "Runtime: {runtime:?}, init code: {} bytes, runtime code: {} bytes",
+        bytecode.init_code.len(),
+        bytecode.runtime_code.len()
+    );
+    info!("Init code hash: 0x{}", hex::encode(init_code_hash));
+
+    fs::create_dir_all("contracts").map_err(|e| Error::io("contracts", e))?;
+
+    // Same format `create2 --init-code` accepts
+    let bytecode_path = "contracts/WorstCaseERC20.hex";
+    fs::write(bytecode_path, hex::encode(&bytecode.init_code))
+        .map_err(|e| Error::io(bytecode_path, e))?;
+    info!("Generated init code saved to: {bytecode_path}");
+    Ok(bytecode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_derivation::{KeyScheme, VyperDerivation};
+    use crate::mpt::{NodeKind, storage_trie};
+
+    #[test]
+    fn test_trie_key_is_keccak_of_slot() {
+        // keccak256 of slot 0 - the well-known key of storage slot 0 in the secure trie
+        let trie_key = calculate_trie_key(&[0u8; 32]);
+        assert_eq!(
+            hex::encode(trie_key),
+            "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"
+        );
+    }
+
+    #[test]
+    fn test_checksum_address() {
+        // Test vector from EIP-55
+        let address: [u8; 20] = hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            to_checksum_address(&address),
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+    }
+
+    #[test]
+    fn test_mine_deep_branch_trie_key_mode() {
+        let branch = mine_deep_branch(&StorageMiningConfig {
+            target_depth: 3,
+            num_threads: 2,
+            use_cuda: false,
+            key_mode: KeyMode::TrieKey,
+            layout: StorageLayout::default(),
+            derivation: KeyScheme::Solidity.derivation(),
+            full_width: false,
+            seed: 1,
+            budget: Arc::new(Budget::new()),
+            checkpoint: None,
+        })
+        .unwrap()
+        .branch;
+        assert_eq!(branch.len(), 3);
+        let deepest = &branch[2].trie_key;
+        for slot in &branch {
+            assert_eq!(slot.trie_key, calculate_trie_key(&slot.storage_key));
+        }
+        // Each level branches off the deepest key's path at its own nibble
+        assert_eq!(count_shared_nibbles(&branch[0].trie_key, deepest), 1);
+        assert!(count_shared_nibbles(&branch[1].trie_key, deepest) >= 2);
+    }
+
+    #[test]
+    fn test_key_hex_keeps_full_word_keys() {
+        let mine = |key_type| {
+            mine_deep_branch(&StorageMiningConfig {
+                target_depth: 2,
+                num_threads: 2,
+                use_cuda: false,
+                key_mode: KeyMode::TrieKey,
+                layout: StorageLayout {
+                    key_type,
+                    ..StorageLayout::default()
+                },
+                derivation: KeyScheme::Solidity.derivation(),
+                full_width: false,
+                seed: 1,
+                budget: Arc::new(Budget::new()),
+                checkpoint: None,
+            })
+            .unwrap()
+            .branch
+        };
+        for slot in mine(KeyType::Address) {
+            assert_eq!(slot.key_hex(), format!("0x{}", hex::encode(slot.address())));
+        }
+        // uint256 keys use all 32 bytes, so writing them as addresses would lose the top 12
+        for slot in mine(KeyType::Uint256) {
+            assert_eq!(slot.key_hex().len(), 2 + 64);
+            assert_eq!(parse_word(&slot.key_hex()).unwrap(), slot.key);
+        }
+    }
+
+    #[test]
+    fn test_level_attempts_keep_partial_branch() {
+        let mined = mine_deep_branch(&StorageMiningConfig {
+            target_depth: 6,
+            num_threads: 2,
+            use_cuda: false,
+            key_mode: KeyMode::TrieKey,
+            layout: StorageLayout::default(),
+            derivation: KeyScheme::Solidity.derivation(),
+            full_width: true,
+            seed: 1,
+            budget: Arc::new(Budget::new().level_attempts(64)),
+            checkpoint: None,
+        })
+        .unwrap();
+        assert_eq!(mined.status, SearchStatus::AttemptsExhausted);
+        // The leading levels found within the budget still end at the anchor
+        let branch = &mined.branch;
+        assert!(!branch.is_empty() && branch.len() < 6);
+        let deepest = &branch[branch.len() - 1].trie_key;
+        for (level, slot) in branch[..branch.len() - 1].iter().enumerate() {
+            assert_eq!(count_shared_nibbles(&slot.trie_key, deepest), level + 1);
+        }
+    }
+
+    #[test]
+    fn test_mine_deep_branch_full_width() {
+        let config = StorageMiningConfig {
+            target_depth: 3,
+            num_threads: 2,
+            use_cuda: false,
+            key_mode: KeyMode::TrieKey,
+            layout: StorageLayout::default(),
+            derivation: KeyScheme::Solidity.derivation(),
+            full_width: true,
+            seed: 1,
+            budget: Arc::new(Budget::new()),
+            checkpoint: None,
+        };
+        let mined = mine_deep_branch(&config).unwrap();
+        let branch = &mined.branch;
+        // 15 siblings at the root, 14 below it where the level branching off above sits
+        assert_eq!(branch.len(), 3 + 15 + 2 * 14);
+        let deepest = &branch[branch.len() - 1];
+        assert!(!deepest.sibling && deepest.depth == 2);
+
+        // Each level's siblings diverge from the deepest key at that nibble, all differently
+        for level in 0..3 {
+            let mut nibbles: Vec<usize> = branch
+                .iter()
+                .filter(|slot| slot.sibling && slot.depth == level)
+                .map(|slot| {
+                    assert_eq!(
+                        count_shared_nibbles(&slot.trie_key, &deepest.trie_key),
+                        level
+                    );
+                    nibble_at(&slot.trie_key, level)
+                })
+                .collect();
+            nibbles.sort();
+            nibbles.dedup();
+            assert_eq!(nibbles.len(), if level == 0 { 15 } else { 14 });
+        }
+
+        // Every branch node on the deepest key's path is full, and each key's leaf hangs right
+        // off the path, so the branch is no deeper than mined
+        let mut value = [0u8; 32];
+        value[31] = 1;
+        let trie = storage_trie(branch.iter().map(|slot| (slot.storage_key, value)));
+        let path = trie.path(&deepest.trie_key);
+        assert_eq!(path.len(), 4);
+        for (depth, node) in path[..3].iter().enumerate() {
+            assert_eq!((node.kind, node.depth), (NodeKind::Branch, depth));
+            assert_eq!(branch_children(&node.rlp), 16);
+        }
+        assert_eq!((path[3].kind, path[3].depth), (NodeKind::Leaf, 3));
+        for slot in branch {
+            let path = trie.path(&slot.trie_key);
+            let leaf = path.last().unwrap();
+            assert_eq!(leaf.kind, NodeKind::Leaf);
+            assert!(
+                path[..path.len() - 1]
+                    .iter()
+                    .all(|node| node.kind == NodeKind::Branch)
+            );
+        }
+
+        let result = StorageMiningResult::from_branch(&mined, &config, 0.0);
+        assert_eq!(result.depth, 3);
+        // Per-key times add up to each search's time
+        for search in [None, Some(0), Some(1), Some(2)] {
+            let accounts: Vec<&MinedStorageAccount> = result
+                .accounts
+                .iter()
+                .filter(|account| account.sibling.then_some(account.depth) == search)
+                .collect();
+            let total: f64 = accounts.iter().map(|account| account.time_taken).sum();
+            let last = accounts
+                .iter()
+                .map(|account| account.found_after_secs)
+                .fold(0.0, f64::max);
+            assert!(accounts.iter().all(|account| account.time_taken >= 0.0));
+            assert!((total - last).abs() < 1e-9);
+        }
+    }
+
+    /// Non-empty children of a branch node's RLP encoding
+    fn branch_children(rlp: &[u8]) -> usize {
+        let (mut offset, end) = match rlp[0] {
+            prefix @ 0xc0..=0xf7 => (1, 1 + (prefix - 0xc0) as usize),
+            prefix => {
+                let size = (prefix - 0xf7) as usize;
+                let len = rlp[1..=size]
+                    .iter()
+                    .fold(0, |len, &byte| len << 8 | byte as usize);
+                (1 + size, 1 + size + len)
+            }
+        };
+        let mut children = 0;
+        for _ in 0..16 {
+            let (header, len) = match rlp[offset] {
+                0x80 => (1, 0),
+                prefix @ 0x81..=0xb7 => (1, (prefix - 0x80) as usize),
+                prefix @ 0xc0..=0xf7 => (1, (prefix - 0xc0) as usize),
+                prefix => panic!("Unexpected child prefix {prefix:#x}"),
+            };
+            children += usize::from(len > 0);
+            offset += header + len;
+        }
+        assert!(offset < end);
+        children
+    }
+
+    #[test]
+    fn test_mine_deep_branch_vyper_derivation() {
+        let config = StorageMiningConfig {
+            target_depth: 2,
+            num_threads: 2,
+            use_cuda: false,
+            key_mode: KeyMode::Slot,
+            layout: StorageLayout::mapping(3),
+            derivation: KeyScheme::Vyper.derivation(),
+            full_width: false,
+            seed: 1,
+            budget: Arc::new(Budget::new()),
+            checkpoint: None,
+        };
+        let mined = mine_deep_branch(&config).unwrap();
+        let branch = &mined.branch;
+        assert_eq!(branch.len(), 2);
+        for slot in branch {
+            let expected =
+                VyperDerivation.derive(&slot.key, KeyType::Address, &config.layout.root_slot);
+            assert_eq!(slot.storage_key, expected);
+        }
+        assert!(has_nibble_prefix(
+            &branch[0].storage_key,
+            &branch[1].storage_key,
+            1
+        ));
+
+        let result = StorageMiningResult::from_branch(&mined, &config, 0.0);
+        assert_eq!(result.key_derivation, "vyper");
+    }
+
+    #[test]
+    fn test_builder_checks_config() {
+        let config = StorageMiningConfig::builder(3)
+            .num_threads(2)
+            .key_scheme(KeyScheme::Vyper)
+            .seed(9)
+            .build()
+            .unwrap();
+        assert_eq!((config.target_depth, config.num_threads), (3, 2));
+        assert_eq!(config.derivation.name(), "vyper");
+        assert_eq!(config.layout, StorageLayout::default());
+
+        assert!(StorageMiningConfig::builder(0).build().is_err());
+        assert!(StorageMiningConfig::builder(65).build().is_err());
+        assert!(
+            StorageMiningConfig::builder(3)
+                .num_threads(0)
+                .build()
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,375 @@
+//! # Transactions Module
+//!
+//! Builds the signed transactions that put a mined CREATE2 result on a live chain: one call to
+//! the deployer per contract with `salt ++ init_code` as calldata (the interface of Nick's
+//! deterministic deployer), and one transfer to each auxiliary account so it exists in the
+//! account trie. Transactions are EIP-1559 (type 2), signed with a single sender key at
+//! consecutive nonces.
+//!
+//! ## Key Functions
+//! - `Eip1559Transaction::sign`: RLP-encodes and signs a type-2 transaction
+//! - `deployment_batch`: Builds the deployments and funding transfers of a CREATE2 result
+//! - `write_transactions`: Saves them as raw hex lines or `eth_sendRawTransaction` payloads
+
+use log::info;
+use secp256k1::{Message, Secp256k1, SecretKey};
+use serde::Serialize;
+use std::fs;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::account_miner::Create2MiningResult;
+use crate::error::Error;
+use crate::rlp::{encode_bytes, encode_list, encode_uint, trim_leading_zeros};
+use crate::storage_layout::parse_address;
+
+/// Nick's deterministic deployer, which CREATE2-deploys `calldata[32..]` with salt `calldata[..32]`
+pub const NICKS_DEPLOYER: &str = "0x4e59b44847b379578588920ca78fbf26c0b4956c";
+
+/// Gas of a plain value transfer
+pub const TRANSFER_GAS: u64 = 21_000;
+
+/// EIP-2718 type of EIP-1559 transactions
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+/// How the signed transactions are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TxFormat {
+    /// One `0x`-prefixed raw transaction per line
+    #[default]
+    Raw,
+    /// A JSON-RPC batch of `eth_sendRawTransaction` requests
+    Rpc,
+}
+
+/// An EIP-1559 transaction without access list
+#[derive(Clone, Debug)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    pub to: [u8; 20],
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+impl Eip1559Transaction {
+    /// The RLP fields up to the (empty) access list
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            encode_uint(self.chain_id as u128),
+            encode_uint(self.nonce as u128),
+            encode_uint(self.max_priority_fee_per_gas),
+            encode_uint(self.max_fee_per_gas),
+            encode_uint(self.gas_limit as u128),
+            encode_bytes(&self.to),
+            encode_uint(self.value),
+            encode_bytes(&self.data),
+            encode_list(&[]),
+        ]
+    }
+
+    /// Hash the sender signs: `keccak256(0x02 || rlp(fields))`
+    pub fn signing_hash(&self) -> [u8; 32] {
+        keccak256(&typed(&encode_list(&self.fields())))
+    }
+
+    /// Signed raw transaction: `0x02 || rlp(fields ++ [y_parity, r, s])`
+    pub fn sign(&self, secret_key: &SecretKey) -> Vec<u8> {
+        let secp = Secp256k1::signing_only();
+        let message = Message::from_digest(self.signing_hash());
+        let (recovery_id, signature) = secp
+            .sign_ecdsa_recoverable(&message, secret_key)
+            .serialize_compact();
+
+        let mut fields = self.fields();
+        fields.push(encode_uint(recovery_id.to_i32() as u128));
+        fields.push(encode_bytes(trim_leading_zeros(&signature[..32])));
+        fields.push(encode_bytes(trim_leading_zeros(&signature[32..])));
+        typed(&encode_list(&fields))
+    }
+}
+
+/// Prefix an RLP payload with the EIP-1559 transaction type
+fn typed(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 1);
+    out.push(EIP1559_TX_TYPE);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// What a transaction of the batch does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxKind {
+    Deploy,
+    Fund,
+}
+
+/// A signed transaction of the batch
+pub struct SignedTransaction {
+    pub kind: TxKind,
+    pub transaction: Eip1559Transaction,
+    pub raw: Vec<u8>,
+}
+
+/// Parameters of a transaction batch
+pub struct BatchConfig {
+    pub sender: SecretKey,
+    pub chain_id: u64,
+    pub nonce_start: u64,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+    /// Gas limit of each deployment (default: `deployment_gas`)
+    pub deploy_gas_limit: Option<u64>,
+    /// Wei sent to each auxiliary account
+    pub funding_value: u128,
+}
+
+/// Upper estimate of the gas to deploy through a CREATE2 factory with `calldata`: intrinsic
+/// gas, CREATE2 with init-code hashing and the EIP-3860 charge, the factory's calldata copy, a
+/// fresh `SSTORE` for each constructor write and the code deposit, plus 25% for the factory
+/// and compiler overhead. Unused gas is refunded, so erring high only costs sender balance
+pub fn deployment_gas(calldata: &[u8], runtime_len: usize, constructor_writes: usize) -> u64 {
+    let calldata_gas: u64 = calldata.iter().map(|&b| if b == 0 { 4 } else { 16 }).sum();
+    let words = calldata.len().div_ceil(32) as u64;
+    let create2 = 32_000 + 6 * words + 2 * words;
+    let memory = 3 * words + words * words / 512 + 3 * words;
+    let stores = 22_100 * constructor_writes as u64;
+    let deposit = 200 * runtime_len as u64;
+    let total = TRANSFER_GAS + calldata_gas + create2 + memory + stores + deposit;
+    total + total / 4
+}
+
+/// Deploy every contract of `result` through its deployer and fund its auxiliary accounts,
+/// contract by contract at consecutive nonces
+pub fn deployment_batch(
+    result: &Create2MiningResult,
+    config: &BatchConfig,
+) -> Result<Vec<SignedTransaction>, String> {
+    let deployer = parse_address(&result.deployer)?;
+    let init_code = result.init_code()?;
+    if init_code.is_empty() {
+        return Err("Result has no init_code".to_string());
+    }
+    let recorded_hash = format!("0x{}", hex::encode(keccak256(&init_code)));
+    if recorded_hash != result.init_code_hash.to_lowercase() {
+        return Err(format!(
+            "init_code hashes to {recorded_hash}, not the recorded init_code_hash {}",
+            result.init_code_hash
+        ));
+    }
+    // The writes of init code that is not one of the known templates are unknown
+    let constructor_writes = if config.deploy_gas_limit.is_some() {
+        0
+    } else if result.contract_storage_slots()?.is_empty() {
+        return Err(
+            "Result records no storage slots, so its constructor's storage writes are unknown; \
+             pass --deploy-gas-limit"
+                .to_string(),
+        );
+    } else {
+        result.contract_storage()?.len()
+    };
+    let runtime_len = result.deploy_code()?.len();
+
+    let mut nonce = config.nonce_start;
+    let mut sign = |kind, to, gas_limit, value, data| {
+        let transaction = Eip1559Transaction {
+            chain_id: config.chain_id,
+            nonce,
+            max_priority_fee_per_gas: config.max_priority_fee_per_gas,
+            max_fee_per_gas: config.max_fee_per_gas,
+            gas_limit,
+            to,
+            value,
+            data,
+        };
+        nonce += 1;
+        let raw = transaction.sign(&config.sender);
+        SignedTransaction {
+            kind,
+            transaction,
+            raw,
+        }
+    };
+
+    let mut transactions = Vec::new();
+    for (contract, (_, auxiliaries)) in result.contracts.iter().zip(result.mined_accounts()?) {
+        let mut calldata = contract.salt.to_vec();
+        calldata.extend_from_slice(&init_code);
+        let gas_limit = config
+            .deploy_gas_limit
+            .unwrap_or_else(|| deployment_gas(&calldata, runtime_len, constructor_writes));
+        transactions.push(sign(TxKind::Deploy, deployer, gas_limit, 0, calldata));
+
+        for auxiliary in auxiliaries {
+            transactions.push(sign(
+                TxKind::Fund,
+                auxiliary,
+                TRANSFER_GAS,
+                config.funding_value,
+                Vec::new(),
+            ));
+        }
+    }
+    Ok(transactions)
+}
+
+/// Most wei the batch can cost the sender: every gas limit at the max fee, plus the values
+pub fn max_cost(transactions: &[SignedTransaction]) -> u128 {
+    transactions
+        .iter()
+        .map(|tx| {
+            let tx = &tx.transaction;
+            tx.gas_limit as u128 * tx.max_fee_per_gas + tx.value
+        })
+        .sum()
+}
+
+/// A JSON-RPC request sending one raw transaction
+#[derive(Serialize)]
+struct RpcRequest {
+    jsonrpc: &'static str,
+    id: usize,
+    method: &'static str,
+    params: [String; 1],
+}
+
+/// Write the raw transactions in `format`
+pub fn write_transactions(
+    transactions: &[SignedTransaction],
+    format: TxFormat,
+    output: &str,
+) -> Result<(), Error> {
+    let raw = transactions
+        .iter()
+        .map(|tx| format!("0x{}", hex::encode(&tx.raw)));
+    let content = match format {
+        TxFormat::Raw => raw.map(|tx| tx + "\n").collect(),
+        TxFormat::Rpc => {
+            let requests: Vec<RpcRequest> = raw
+                .enumerate()
+                .map(|(id, tx)| RpcRequest {
+                    jsonrpc: "2.0",
+                    id,
+                    method: "eth_sendRawTransaction",
+                    params: [tx],
+                })
+                .collect();
+            serde_json::to_string_pretty(&requests)
+                .map_err(|e| Error::InvalidInput(format!("Failed to serialize to JSON: {e}")))?
+        }
+    };
+
+    fs::write(output, content).map_err(|e| Error::io(output, e))?;
+    info!("{} transactions saved to: {output}", transactions.len());
+    Ok(())
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut hash = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::{Runtime, seeding_contract};
+    use crate::keys::address_of;
+    use secp256k1::PublicKey;
+    use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+
+    #[test]
+    fn test_signature_recovers_sender() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let transaction = Eip1559Transaction {
+            chain_id: 1337,
+            nonce: 3,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 30_000_000_000,
+            gas_limit: TRANSFER_GAS,
+            to: [0x42; 20],
+            value: 1,
+            data: Vec::new(),
+        };
+        let raw = transaction.sign(&secret_key);
+        assert_eq!(raw[0], EIP1559_TX_TYPE);
+
+        // The payload ends with the empty access list, y_parity, and r and s as 32-byte strings
+        let r_start = raw.len() - 66;
+        assert_eq!((raw[r_start], raw[r_start + 33]), (0xa0, 0xa0));
+        assert_eq!(raw[r_start - 2], 0xc0);
+        let y_parity = match raw[r_start - 1] {
+            0x80 => 0,
+            byte => byte as i32,
+        };
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&raw[r_start + 1..r_start + 33]);
+        compact[32..].copy_from_slice(&raw[r_start + 34..]);
+        let signature =
+            RecoverableSignature::from_compact(&compact, RecoveryId::from_i32(y_parity).unwrap())
+                .unwrap();
+
+        let message = Message::from_digest(transaction.signing_hash());
+        let signer = secp.recover_ecdsa(&message, &signature).unwrap();
+        assert_eq!(
+            address_of(&signer),
+            address_of(&PublicKey::from_secret_key(&secp, &secret_key))
+        );
+    }
+
+    #[test]
+    fn test_deployment_gas_counts_constructor_writes() {
+        let slots = [[0x11; 32], [0x22; 32]];
+        let bytecode = seeding_contract(&slots, Runtime::Erc20);
+        let mut result: Create2MiningResult = serde_json::from_value(serde_json::json!({
+            "deployer": NICKS_DEPLOYER,
+            "init_code_hash": format!("0x{}", hex::encode(keccak256(&bytecode.init_code))),
+            "init_code": format!("0x{}", hex::encode(&bytecode.init_code)),
+            "deploy_code": format!("0x{}", hex::encode(&bytecode.runtime_code)),
+            "storage_keys": [],
+            "target_depth": 2,
+            "num_contracts": 1,
+            "total_time": 0.0,
+            "contracts": [{
+                "salt": format!("0x{}", hex::encode([0u8; 32])),
+                "contract_address": format!("0x{}", hex::encode([0x42; 20])),
+                "auxiliary_accounts": [],
+            }],
+        }))
+        .unwrap();
+        let mut config = BatchConfig {
+            sender: SecretKey::from_slice(&[0x11; 32]).unwrap(),
+            chain_id: 1337,
+            nonce_start: 0,
+            max_fee_per_gas: 1,
+            max_priority_fee_per_gas: 1,
+            deploy_gas_limit: None,
+            funding_value: 1,
+        };
+
+        // Without recorded slots the constructor's writes are unknown
+        assert!(deployment_batch(&result, &config).is_err());
+        config.deploy_gas_limit = Some(1_000_000);
+        let batch = deployment_batch(&result, &config).unwrap();
+        assert_eq!(batch[0].transaction.gas_limit, 1_000_000);
+
+        // The mined slots plus the mint of the ERC20 constructor
+        result.storage_slots = slots
+            .iter()
+            .map(|s| format!("0x{}", hex::encode(s)))
+            .collect();
+        config.deploy_gas_limit = None;
+        let batch = deployment_batch(&result, &config).unwrap();
+        let calldata = &batch[0].transaction.data;
+        assert_eq!(
+            batch[0].transaction.gas_limit,
+            deployment_gas(calldata, bytecode.runtime_code.len(), 4)
+        );
+    }
+}
@@ -0,0 +1,564 @@
+//! # Verify Module
+//!
+//! Re-checks mined asset files before they are used, e.g. on shared benchmark networks.
+//! Every derived value in a file is recomputed from its inputs and compared with the
+//! recorded one:
+//! - CREATE2 results: the init code hash, each contract address (from deployer, salt and init
+//!   code hash), the number of auxiliaries and the nibbles each auxiliary's `keccak(address)`
+//!   shares with its contract's, the cluster prefix of salt-mined contracts, plain (or
+//!   decrypted) auxiliary keys against their addresses, and the mined storage slots against
+//!   the init code
+//! - Target results: each target's account-trie key and the nibbles each auxiliary shares with
+//!   it beyond the skipped levels
+//! - Storage results: each storage slot (from its key and the recorded layout), each trie key,
+//!   the branch structure and the recorded depth and shared-nibble counts
+//!
+//! Init code files (`.bin` or `.hex`, as in `mined_assets/`) are checked against the CREATE2
+//! results verified with them: the code must hash to one's init code hash and write its slots.
+//!
+//! Results of runs that ended early (a `status` other than `complete`) may hold fewer contracts
+//! or targets than requested, the last with fewer auxiliaries; everything they hold is checked.
+//!
+//! ## Key Functions
+//! - `verify_files`: Verifies result and init code files passed together
+//! - `verify_file`: Detects the kind of result file and verifies it
+//! - `verify_create2_result`: Verifies a `Create2MiningResult`
+//! - `verify_target_result`: Verifies a `TargetMiningResult`
+//! - `verify_storage_result`: Verifies a `StorageMiningResult`
+
+use log::info;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use std::fs;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::account_miner::{Create2MiningResult, TargetMiningResult, calculate_create2_address};
+use crate::contract::load_init_code;
+use crate::key_derivation::KeyScheme;
+use crate::keys::address_of;
+use crate::mpt;
+use crate::search::count_shared_nibbles;
+use crate::storage_layout::{KeyType, StorageLayout, address_key, parse_address, parse_word};
+use crate::storage_miner::{
+    StorageMiningResult, StorageSlot, branch_levels, calculate_trie_key, shared_with_branch,
+};
+
+/// Outcome of verifying one file
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// Kind of result the file holds
+    pub kind: &'static str,
+    /// Number of recomputed values
+    pub checks: usize,
+    /// Every recomputed value that differs from the recorded one
+    pub mismatches: Vec<String>,
+}
+
+impl Verification {
+    fn new(kind: &'static str) -> Self {
+        Verification {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Count a check, recording `mismatch` if it failed
+    fn check(&mut self, passed: bool, mismatch: impl FnOnce() -> String) {
+        self.checks += 1;
+        if !passed {
+            self.mismatches.push(mismatch());
+        }
+    }
+
+    /// Count a check of a recorded against a recomputed value
+    fn check_eq<T: PartialEq + std::fmt::Display>(&mut self, what: &str, recorded: T, computed: T) {
+        self.check(recorded == computed, || {
+            format!("{what}: recorded {recorded}, computed {computed}")
+        });
+    }
+}
+
+/// Whether `path` holds init code (hex bytecode, as the `.bin` assets) rather than a result
+pub fn is_init_code_file(path: &str) -> bool {
+    path.ends_with(".bin") || path.ends_with(".hex")
+}
+
+/// Verify result files and init code files (see `is_init_code_file`), in order. Init code is
+/// checked against the CREATE2 results among `paths`, as a JSON result does not name its
+/// `.bin`; `init_code` and `password` apply to the result files as in `verify_file`
+pub fn verify_files(
+    paths: &[String],
+    init_code: Option<&[u8]>,
+    password: Option<&str>,
+) -> Vec<Result<Verification, String>> {
+    let results: Vec<Create2MiningResult> = paths
+        .iter()
+        .filter(|path| !is_init_code_file(path))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    paths
+        .iter()
+        .map(|path| {
+            if is_init_code_file(path) {
+                verify_init_code_file(path, &results)
+            } else {
+                verify_file(path, init_code, password)
+            }
+        })
+        .collect()
+}
+
+/// Verify an init code file against the CREATE2 results given with it: it must hash to the
+/// init code hash of at least one, and write the storage slots of each it hashes for
+pub fn verify_init_code_file(
+    path: &str,
+    results: &[Create2MiningResult],
+) -> Result<Verification, String> {
+    if results.is_empty() {
+        return Err(format!(
+            "{path} is init code: pass the CREATE2 result it was mined for alongside it, or pass it with --init-code"
+        ));
+    }
+    let code = load_init_code(path).map_err(|e| e.to_string())?.init_code;
+    let hash = format!("0x{}", hex::encode(keccak256(&code)));
+
+    let mut verification = Verification::new("init code");
+    let matching: Vec<&Create2MiningResult> = results
+        .iter()
+        .filter(|result| result.init_code_hash.to_lowercase() == hash)
+        .collect();
+    verification.check(!matching.is_empty(), || {
+        let recorded: Vec<&str> = results
+            .iter()
+            .map(|result| result.init_code_hash.as_str())
+            .collect();
+        format!(
+            "keccak256 of the init code is {hash}, not the init code hash of any CREATE2 result given ({})",
+            recorded.join(", ")
+        )
+    });
+    for result in matching {
+        check_writes_slots(&mut verification, result, &code)?;
+    }
+    Ok(verification)
+}
+
+/// Verify a CREATE2, target or storage result file. `init_code` is the code a CREATE2 result
+/// must have been mined for, and `password` decrypts its auxiliary keys so they are checked too
+pub fn verify_file(
+    path: &str,
+    init_code: Option<&[u8]>,
+    password: Option<&str>,
+) -> Result<Verification, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let value: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid JSON in {path}: {e}"))?;
+
+    if value.get("contracts").is_some() {
+        let result: Create2MiningResult = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid CREATE2 result {path}: {e}"))?;
+        verify_create2_result(&result, init_code, password)
+    } else if value.get("targets").is_some() {
+        let result: TargetMiningResult = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid target result {path}: {e}"))?;
+        verify_target_result(&result)
+    } else if value.get("accounts").is_some() {
+        let result: StorageMiningResult = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid storage result {path}: {e}"))?;
+        verify_storage_result(&result, init_code)
+    } else {
+        Err(format!(
+            "{path} is neither a CREATE2, target nor storage result"
+        ))
+    }
+}
+
+/// Verify a CREATE2 result
+pub fn verify_create2_result(
+    result: &Create2MiningResult,
+    init_code: Option<&[u8]>,
+    password: Option<&str>,
+) -> Result<Verification, String> {
+    let mut verification = Verification::new("CREATE2");
+    let deployer = parse_address(&result.deployer)?;
+    let init_code_hash = parse_word(&result.init_code_hash)?;
+    let hash_hex = |hash: &[u8; 32]| format!("0x{}", hex::encode(hash));
+
+    // The recorded init code and the one given must both hash to the recorded hash
+    let recorded_code = result.init_code()?;
+    for (what, code) in [
+        ("init_code", &recorded_code[..]),
+        ("given init code", init_code.unwrap_or_default()),
+    ] {
+        if !code.is_empty() {
+            verification.check_eq(
+                &format!("keccak256 of {what}"),
+                hash_hex(&init_code_hash),
+                hash_hex(&keccak256(code)),
+            );
+        }
+    }
+
+    let code = init_code.unwrap_or(&recorded_code);
+    if !code.is_empty() {
+        check_writes_slots(&mut verification, result, code)?;
+    }
+
+    let complete = result.status.is_complete();
+    check_count(
+        &mut verification,
+        "Number of contracts",
+        result.num_contracts,
+        result.contracts.len(),
+        complete,
+    );
+    let cluster = result
+        .cluster
+        .as_ref()
+        .map(|cluster| parse_word(&cluster.target).map(|target| (target, cluster.nibbles)))
+        .transpose()?;
+    for (index, contract) in result.contracts.iter().enumerate() {
+        let what = format!("Contract {index}");
+        let address = parse_address(&contract.contract_address)?;
+        let computed = calculate_create2_address(&deployer, &contract.salt, &init_code_hash);
+        verification.check_eq(
+            &format!("{what} address"),
+            contract.contract_address.to_lowercase(),
+            format!("0x{}", hex::encode(computed)),
+        );
+
+        check_count(
+            &mut verification,
+            &format!("{what} auxiliaries"),
+            result.target_depth,
+            contract.auxiliary_accounts.len(),
+            complete || index + 1 < result.contracts.len(),
+        );
+        // Auxiliary `i` must share at least `i + 1` nibbles with the contract's hash
+        let contract_hash = mpt::account_key(&address);
+        if let Some((target, nibbles)) = &cluster {
+            let shared = count_shared_nibbles(&contract_hash, target);
+            verification.check(shared >= *nibbles, || {
+                format!("{what} shares {shared} nibbles with the cluster target, expected at least {nibbles}")
+            });
+        }
+        for (i, auxiliary) in contract.auxiliary_accounts.iter().enumerate() {
+            let shared = count_shared_nibbles(
+                &mpt::account_key(&parse_address(auxiliary)?),
+                &contract_hash,
+            );
+            verification.check(shared > i, || {
+                format!(
+                    "{what} auxiliary {auxiliary} shares {shared} nibbles with the contract, expected at least {}",
+                    i + 1
+                )
+            });
+        }
+    }
+
+    if result.key_encryption.is_some() && password.is_none() {
+        info!("Auxiliary keys are encrypted; pass a password file to verify them");
+    } else {
+        let secp = Secp256k1::new();
+        for (address, secret_key) in result.auxiliary_keys(password)? {
+            let derived = SecretKey::from_slice(&secret_key)
+                .map(|key| address_of(&PublicKey::from_secret_key(&secp, &key)));
+            verification.check(derived == Ok(address), || {
+                format!(
+                    "Auxiliary key of 0x{} does not control it",
+                    hex::encode(address)
+                )
+            });
+        }
+    }
+
+    Ok(verification)
+}
+
+/// Check that `code` writes every storage slot of a CREATE2 result
+fn check_writes_slots(
+    verification: &mut Verification,
+    result: &Create2MiningResult,
+    code: &[u8],
+) -> Result<(), String> {
+    let slots = result.contract_storage_slots()?;
+    for (slot, key) in slots.iter().zip(&result.storage_keys) {
+        // The contract writes either the slot itself or the key through its mapping. Address
+        // keys are written as addresses, which pad to the same word
+        let key = parse_word(key)?;
+        verification.check(pushes(code, slot) || pushes(code, &key), || {
+            format!(
+                "Storage slot 0x{} is not written by the init code",
+                hex::encode(slot)
+            )
+        });
+    }
+    Ok(())
+}
+
+/// Verify a result of mining auxiliaries around existing accounts
+pub fn verify_target_result(result: &TargetMiningResult) -> Result<Verification, String> {
+    let mut verification = Verification::new("target");
+    let levels = result.target_depth.saturating_sub(result.skip_nibbles);
+    let complete = result.status.is_complete();
+    for (index, target) in result.targets.iter().enumerate() {
+        let what = format!("Target {}", target.target);
+        let trie_key = parse_word(&target.trie_key)?;
+        verification.check_eq(
+            &format!("{what} trie key"),
+            target.trie_key.to_lowercase(),
+            format!("0x{}", hex::encode(mpt::parse_account_key(&target.target)?)),
+        );
+
+        check_count(
+            &mut verification,
+            &format!("{what} auxiliaries"),
+            levels,
+            target.auxiliary_accounts.len(),
+            complete || index + 1 < result.targets.len(),
+        );
+        // Auxiliary `i` must share at least `skip_nibbles + i + 1` nibbles with the target
+        for (i, auxiliary) in target.auxiliary_accounts.iter().enumerate() {
+            let shared =
+                count_shared_nibbles(&mpt::account_key(&parse_address(auxiliary)?), &trie_key);
+            let expected = result.skip_nibbles + i + 1;
+            verification.check(shared >= expected, || {
+                format!(
+                    "{what} auxiliary {auxiliary} shares {shared} nibbles with the target, expected at least {expected}"
+                )
+            });
+        }
+    }
+    Ok(verification)
+}
+
+/// Check a recorded count against the expected one, which it may fall short of unless `full`
+fn check_count(
+    verification: &mut Verification,
+    what: &str,
+    expected: usize,
+    recorded: usize,
+    full: bool,
+) {
+    if full {
+        verification.check_eq(what, expected, recorded);
+    } else {
+        verification.check(recorded <= expected, || {
+            format!("{what}: recorded {recorded}, expected at most {expected}")
+        });
+    }
+}
+
+/// Verify a storage result. `init_code` is the code of the contract that writes the slots
+pub fn verify_storage_result(
+    result: &StorageMiningResult,
+    init_code: Option<&[u8]>,
+) -> Result<Verification, String> {
+    let mut verification = Verification::new("storage");
+    let scheme = KeyScheme::from_name(&result.key_derivation)
+        .ok_or_else(|| format!("Unknown key derivation: {}", result.key_derivation))?;
+    let derivation = scheme.derivation();
+    let layout = StorageLayout {
+        root_slot: parse_word(&result.base_slot)?,
+        outer_keys: result
+            .outer_keys
+            .iter()
+            .map(|key| parse_word(key))
+            .collect::<Result<_, _>>()?,
+        key_type: result.key_type,
+    };
+
+    let mut branch = Vec::new();
+    for account in &result.accounts {
+        let key = match (&account.address, &account.key) {
+            (Some(address), _) if result.key_type == KeyType::Address => {
+                address_key(&parse_address(address)?)
+            }
+            (_, Some(key)) => parse_word(key)?,
+            _ => return Err(format!("Level at depth {} has no key", account.depth)),
+        };
+        let what = format!(
+            "Key 0x{}",
+            hex::encode(&key[32 - result.key_type.width()..])
+        );
+
+        let storage_key = layout.storage_slot(&key, derivation.as_ref());
+        let trie_key = calculate_trie_key(&storage_key);
+        verification.check_eq(
+            &format!("{what} storage slot"),
+            account.storage_slot.to_lowercase(),
+            format!("0x{}", hex::encode(storage_key)),
+        );
+        verification.check_eq(
+            &format!("{what} trie key"),
+            account.trie_key.to_lowercase(),
+            format!("0x{}", hex::encode(trie_key)),
+        );
+        if let Some(code) = init_code {
+            verification.check(pushes(code, &storage_key) || pushes(code, &key), || {
+                format!("{what} is not written by the init code")
+            });
+        }
+
+        branch.push(StorageSlot {
+            key,
+            key_type: result.key_type,
+            storage_key,
+            trie_key,
+            depth: account.depth,
+            found_after_secs: account.found_after_secs,
+            sibling: account.sibling,
+        });
+    }
+
+    let levels = branch_levels(&branch);
+    verification.check_eq("Depth", result.depth, levels.len());
+    let Some(deepest) = levels.last() else {
+        return Ok(verification);
+    };
+    let deepest_key = deepest.mined_key(result.key_mode);
+
+    for (index, level) in levels.iter().enumerate() {
+        verification.check_eq("Level depth", index, level.depth);
+    }
+    for (slot, account) in branch.iter().zip(&result.accounts) {
+        let what = format!(
+            "Key 0x{}",
+            hex::encode(&slot.key[32 - result.key_type.width()..])
+        );
+        verification.check_eq(
+            &format!("{what} shared nibbles"),
+            account.shared_nibbles,
+            shared_with_branch(slot, &levels, result.key_mode),
+        );
+
+        // Levels branch off the deepest key's path below their depth, siblings exactly at it
+        let shared = count_shared_nibbles(slot.mined_key(result.key_mode), deepest_key);
+        if slot.sibling {
+            verification.check(shared == slot.depth, || {
+                format!(
+                    "{what} is a sibling at depth {} but shares {shared} nibbles with the deepest level",
+                    slot.depth
+                )
+            });
+        } else if slot.depth + 1 < levels.len() {
+            verification.check(shared > slot.depth, || {
+                format!(
+                    "{what} is at depth {} but shares only {shared} nibbles with the deepest level",
+                    slot.depth
+                )
+            });
+        }
+    }
+
+    Ok(verification)
+}
+
+/// Whether `code` pushes `word`, with `PUSH32` or with its leading zero bytes stripped
+fn pushes(code: &[u8], word: &[u8; 32]) -> bool {
+    let stripped = &word[word.iter().take_while(|&&b| b == 0).count()..];
+    let mut full = vec![0x7f];
+    full.extend_from_slice(word);
+    let mut minimal = vec![0x5f + stripped.len() as u8];
+    minimal.extend_from_slice(stripped);
+    [full, minimal]
+        .iter()
+        .any(|pattern| code.windows(pattern.len()).any(|window| window == pattern))
+}
+
+/// Compute Keccak256 hash
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_miner::{KeyMode, StorageMiningConfig, mine_deep_branch};
+
+    #[test]
+    fn test_verify_storage_result_catches_tampering() {
+        let config = StorageMiningConfig {
+            target_depth: 3,
+            num_threads: 1,
+            use_cuda: false,
+            key_mode: KeyMode::TrieKey,
+            layout: StorageLayout::mapping(2),
+            derivation: KeyScheme::Vyper.derivation(),
+            full_width: true,
+            seed: 3,
+            budget: Default::default(),
+            checkpoint: None,
+        };
+        let mined = mine_deep_branch(&config).unwrap();
+        let mut result = StorageMiningResult::from_branch(&mined, &config, 0.0);
+        assert!(verify_storage_result(&result, None).unwrap().passed());
+
+        result.accounts[0].shared_nibbles += 1;
+        result.accounts[1].trie_key = result.accounts[2].trie_key.clone();
+        result.base_slot = "0x3".to_string();
+        let verification = verify_storage_result(&result, None).unwrap();
+        // Every storage slot and trie key is off under the wrong mapping slot
+        assert!(verification.mismatches.len() > 2 * mined.branch.len());
+    }
+
+    #[test]
+    fn test_verify_init_code_files_against_results() {
+        let slot = parse_word("0x1234").unwrap();
+        let code = [0x60, 0x01, 0x61, 0x12, 0x34, 0x55];
+        let result = Create2MiningResult {
+            deployer: format!("0x{}", hex::encode([0u8; 20])),
+            init_code_hash: format!("0x{}", hex::encode(keccak256(&code))),
+            init_code: String::new(),
+            deploy_code: String::new(),
+            storage_keys: vec![format!("0x{}", hex::encode([0x11; 20]))],
+            storage_slots: vec![format!("0x{}", hex::encode(slot))],
+            target_depth: 0,
+            num_contracts: 0,
+            seed: None,
+            total_time: 0.0,
+            status: Default::default(),
+            contracts: Vec::new(),
+            account_trie: None,
+            key_encryption: None,
+            cluster: None,
+        };
+        let dir = std::env::temp_dir().join(format!("verify-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        fs::write(path("result.json"), serde_json::to_string(&result).unwrap()).unwrap();
+        fs::write(path("good.bin"), hex::encode(code)).unwrap();
+        fs::write(path("other.bin"), "0x600055").unwrap();
+
+        let verified = verify_files(
+            &[path("good.bin"), path("result.json"), path("other.bin")],
+            None,
+            None,
+        );
+        let good = verified[0].as_ref().unwrap();
+        assert!(good.passed() && good.checks == 2);
+        assert!(verified[1].as_ref().unwrap().passed());
+        assert!(!verified[2].as_ref().unwrap().passed());
+        // Without a result to check against, init code cannot be verified
+        assert!(verify_files(&[path("good.bin")], None, None)[0].is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pushes_minimal_and_full_words() {
+        let word = parse_word("0x1234").unwrap();
+        assert!(pushes(&[0x00, 0x61, 0x12, 0x34, 0x55], &word));
+        let mut full = vec![0x7f];
+        full.extend_from_slice(&word);
+        assert!(pushes(&full, &word));
+        assert!(!pushes(&[0x62, 0x00, 0x12, 0x34], &word));
+    }
+}